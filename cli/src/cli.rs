@@ -108,8 +108,11 @@ pub(crate) struct PasswordArgs {
         help = "Password of archive. If password is not given it's asked from the tty"
     )]
     pub(crate) password: Option<Option<String>>,
-    #[arg(long, help = "Read password from specified file")]
-    pub(crate) password_file: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Read password from specified file. Can be specified multiple times to try each password when reading"
+    )]
+    pub(crate) password_file: Vec<PathBuf>,
 }
 
 #[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
@@ -138,6 +141,134 @@ pub(crate) enum SolidEntriesTransformStrategy {
     KeepSolid,
 }
 
+#[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[command(group(
+    ArgGroup::new("unstable-exclusion")
+        .args(["one_file_system", "exclude_caches", "exclude_vcs", "exclude_if_present", "pnaignore"])
+        .multiple(true)
+        .requires("unstable")
+))]
+pub(crate) struct ExclusionArgs {
+    #[arg(
+        long,
+        help = "Do not descend into directories on other file systems (unstable)"
+    )]
+    pub(crate) one_file_system: bool,
+    #[arg(
+        long,
+        help = "Exclude directories containing a valid CACHEDIR.TAG file (unstable)"
+    )]
+    pub(crate) exclude_caches: bool,
+    #[arg(
+        long,
+        help = "Exclude the directories and files of version control systems such as .git, .svn and .hg (unstable)"
+    )]
+    pub(crate) exclude_vcs: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Exclude directories containing FILE (unstable)"
+    )]
+    pub(crate) exclude_if_present: Vec<String>,
+    #[arg(
+        long,
+        help = "Ignore files matching the patterns of the .pnaignore file of each directory, in .gitignore syntax (unstable)"
+    )]
+    pub(crate) pnaignore: bool,
+}
+
+#[derive(Parser, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub(crate) struct ProgressArgs {
+    #[arg(
+        long,
+        help = "Show the progress with the throughput and the estimated remaining time on stderr, or log it periodically when stderr is not a terminal"
+    )]
+    pub(crate) progress: bool,
+    #[arg(
+        long,
+        help = "Print the total number of bytes processed and the throughput when finished"
+    )]
+    pub(crate) totals: bool,
+}
+
+#[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[command(group(ArgGroup::new("overwrite-policy").args(["overwrite", "keep_old_files", "keep_newer_files", "backup"])))]
+pub(crate) struct OverwriteArgs {
+    #[arg(
+        short = 'k',
+        long,
+        help = "Skip entries whose path already exists (unstable)"
+    )]
+    pub(crate) keep_old_files: bool,
+    #[arg(
+        long,
+        help = "Skip entries that are not newer than the existing file at their path (unstable)"
+    )]
+    pub(crate) keep_newer_files: bool,
+    #[arg(
+        long,
+        value_name = "CONTROL",
+        require_equals = true,
+        help = "Rename existing files before extracting over them, with a `~` suffix for simple or a `.~N~` suffix for numbered [default: simple] (unstable)"
+    )]
+    pub(crate) backup: Option<Option<BackupControl>>,
+    #[arg(
+        short = 'U',
+        long,
+        help = "Remove existing files before extracting over them instead of overwriting their contents, which replaces read-only files and breaks hard links (unstable)"
+    )]
+    pub(crate) unlink_first: bool,
+}
+
+impl OverwriteArgs {
+    #[inline]
+    pub(crate) fn strategy(&self, overwrite: bool) -> OverwriteStrategy {
+        if self.keep_old_files {
+            OverwriteStrategy::KeepOld
+        } else if self.keep_newer_files {
+            OverwriteStrategy::KeepNewer
+        } else if let Some(control) = self.backup {
+            OverwriteStrategy::Backup(control.unwrap_or_default())
+        } else if overwrite || self.unlink_first {
+            OverwriteStrategy::Always
+        } else {
+            OverwriteStrategy::Never
+        }
+    }
+}
+
+/// What to do when an entry is extracted to a path that already exists.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub(crate) enum OverwriteStrategy {
+    /// Fail with an error.
+    Never,
+    /// Replace the existing file.
+    Always,
+    /// Leave the existing file as is.
+    KeepOld,
+    /// Replace the existing file only if the entry is newer.
+    KeepNewer,
+    /// Rename the existing file, then extract.
+    Backup(BackupControl),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, ValueEnum)]
+pub(crate) enum BackupControl {
+    #[default]
+    Simple,
+    Numbered,
+}
+
+/// How to keep the access times of archived files from being updated by reading them.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, ValueEnum)]
+pub(crate) enum AtimePreserve {
+    /// Restore the access time after reading the file.
+    #[default]
+    Replace,
+    /// Read the file without updating its access time where the system supports it.
+    System,
+}
+
 #[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 #[command(group(ArgGroup::new("compression_method").args(["store", "deflate", "zstd", "xz"])))]
 pub(crate) struct CompressionAlgorithmArgs {
@@ -221,12 +352,20 @@ pub(crate) enum CipherMode {
 }
 
 #[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-#[command(group(ArgGroup::new("hash_algorithm").args(["argon2", "pbkdf2"])))]
+#[command(
+    group(ArgGroup::new("hash_algorithm").args(["argon2", "pbkdf2"])),
+    group(ArgGroup::new("unstable-archive-key").args(["archive_key"]).requires("unstable")),
+)]
 pub(crate) struct HashAlgorithmArgs {
     #[arg(long, help = "Use argon2 for password hashing")]
     pub(crate) argon2: Option<Option<Argon2idParams>>,
     #[arg(long, help = "Use pbkdf2 for password hashing")]
     pub(crate) pbkdf2: Option<Option<Pbkdf2Sha256Params>>,
+    #[arg(
+        long,
+        help = "Hash the password once per archive and derive the key of each entry from it (unstable)"
+    )]
+    pub(crate) archive_key: bool,
 }
 
 impl HashAlgorithmArgs {
@@ -1,9 +1,11 @@
 mod argon2id_params;
 mod datetime;
+mod entry_filter;
 mod pbkdf2_sha256_params;
 mod private_chunk_type;
 
 pub(crate) use argon2id_params::Argon2idParams;
 pub(crate) use datetime::DateTime;
+pub(crate) use entry_filter::EntryFilter;
 pub(crate) use pbkdf2_sha256_params::Pbkdf2Sha256Params;
 pub(crate) use private_chunk_type::PrivateChunkType;
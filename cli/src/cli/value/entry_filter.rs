@@ -0,0 +1,482 @@
+use super::DateTime;
+use bytesize::ByteSize;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, multispace0},
+    combinator::map,
+    multi::many0,
+    sequence::{delimited, preceded},
+    IResult, Parser,
+};
+use pna::{DataKind, NormalEntry};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    str::FromStr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Predicate over the header and metadata of entries given by `--where`.
+///
+/// Conditions such as `size > 10M` or `path ~ "*.log"` are combined with `&&`, `||`, `!` and
+/// parentheses. A condition on a value that an entry does not have, such as the owner of an
+/// entry archived without permissions, is false.
+#[derive(Clone, Debug)]
+pub(crate) struct EntryFilter {
+    source: String,
+    expr: Expr<Condition>,
+}
+
+impl EntryFilter {
+    /// Returns `true` if `entry` satisfies the filter.
+    #[inline]
+    pub(crate) fn matches<T>(&self, entry: &NormalEntry<T>) -> bool {
+        self.expr.eval(&|condition| condition.matches(entry))
+    }
+}
+
+impl PartialEq for EntryFilter {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for EntryFilter {}
+
+impl PartialOrd for EntryFilter {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EntryFilter {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.source.cmp(&other.source)
+    }
+}
+
+impl Hash for EntryFilter {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state)
+    }
+}
+
+impl FromStr for EntryFilter {
+    type Err = String;
+
+    /// `"field op value [(&&|||) ...]"`, where op is one of `==`, `!=`, `<`, `<=`, `>`, `>=`,
+    /// `~` (glob match) and `!~`.
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, expr) = parse_or(s).map_err(|it| it.to_string())?;
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            return Err(format!("unexpected value: {rest}"));
+        }
+        Ok(Self {
+            source: s.into(),
+            expr: expr.try_map(&mut |(field, op, value)| Condition::new(field, op, &value))?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr<T> {
+    Condition(T),
+    Not(Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+}
+
+impl<T> Expr<T> {
+    fn try_map<U, E>(self, f: &mut impl FnMut(T) -> Result<U, E>) -> Result<Expr<U>, E> {
+        Ok(match self {
+            Self::Condition(it) => Expr::Condition(f(it)?),
+            Self::Not(it) => Expr::Not(Box::new(it.try_map(f)?)),
+            Self::And(l, r) => Expr::And(Box::new(l.try_map(f)?), Box::new(r.try_map(f)?)),
+            Self::Or(l, r) => Expr::Or(Box::new(l.try_map(f)?), Box::new(r.try_map(f)?)),
+        })
+    }
+
+    fn eval(&self, f: &impl Fn(&T) -> bool) -> bool {
+        match self {
+            Self::Condition(it) => f(it),
+            Self::Not(it) => !it.eval(f),
+            Self::And(l, r) => l.eval(f) && r.eval(f),
+            Self::Or(l, r) => l.eval(f) || r.eval(f),
+        }
+    }
+}
+
+type RawCondition<'s> = (&'s str, &'s str, String);
+
+fn parse_or(s: &str) -> IResult<&str, Expr<RawCondition<'_>>> {
+    let (s, first) = parse_and(s)?;
+    let (s, rest) = many0(preceded(
+        delimited(multispace0, tag("||"), multispace0),
+        parse_and,
+    ))
+    .parse(s)?;
+    Ok((
+        s,
+        rest.into_iter()
+            .fold(first, |l, r| Expr::Or(Box::new(l), Box::new(r))),
+    ))
+}
+
+fn parse_and(s: &str) -> IResult<&str, Expr<RawCondition<'_>>> {
+    let (s, first) = parse_unary(s)?;
+    let (s, rest) = many0(preceded(
+        delimited(multispace0, tag("&&"), multispace0),
+        parse_unary,
+    ))
+    .parse(s)?;
+    Ok((
+        s,
+        rest.into_iter()
+            .fold(first, |l, r| Expr::And(Box::new(l), Box::new(r))),
+    ))
+}
+
+fn parse_unary(s: &str) -> IResult<&str, Expr<RawCondition<'_>>> {
+    preceded(
+        multispace0,
+        alt((
+            map(preceded((char('!'), multispace0), parse_unary), |it| {
+                Expr::Not(Box::new(it))
+            }),
+            delimited((char('('), multispace0), parse_or, (multispace0, char(')'))),
+            map(parse_condition, Expr::Condition),
+        )),
+    )
+    .parse(s)
+}
+
+fn parse_condition(s: &str) -> IResult<&str, RawCondition<'_>> {
+    (
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        delimited(
+            multispace0,
+            alt((
+                tag("=="),
+                tag("!="),
+                tag("<="),
+                tag(">="),
+                tag("!~"),
+                tag("<"),
+                tag(">"),
+                tag("~"),
+            )),
+            multispace0,
+        ),
+        alt((
+            parse_quoted,
+            map(
+                take_while1(|c: char| !c.is_whitespace() && !"()&|\"".contains(c)),
+                String::from,
+            ),
+        )),
+    )
+        .parse(s)
+}
+
+/// Parses a double quoted string, in which `\` escapes the next character.
+fn parse_quoted(s: &str) -> IResult<&str, String> {
+    let (rest, _) = char('"').parse(s)?;
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&rest[i + 1..], value)),
+            '\\' => match chars.next() {
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(nom::Err::Failure(nom::error::Error::new(
+        s,
+        nom::error::ErrorKind::Char,
+    )))
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    Kind(bool, DataKind),
+    Text(TextField, TextMatch),
+    Number(NumberField, Comparison, u128),
+    Time(TimeField, Comparison, Duration),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum TextField {
+    Path,
+    Uname,
+    Gname,
+}
+
+#[derive(Clone, Debug)]
+enum TextMatch {
+    Equal(bool, String),
+    Glob(bool, globset::GlobMatcher),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum NumberField {
+    Size,
+    Uid,
+    Gid,
+    Mode,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum TimeField {
+    Created,
+    Modified,
+    Accessed,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn test<T: Ord>(self, a: T, b: T) -> bool {
+        let ordering = a.cmp(&b);
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl Condition {
+    fn new(field: &str, op: &str, value: &str) -> Result<Self, String> {
+        let comparison = match op {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        };
+        let unsupported = || format!("`{op}` is not supported for {field}");
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid {field} `{value}`: {e}");
+        match field {
+            "kind" => {
+                let kind = match value {
+                    "file" => DataKind::File,
+                    "dir" | "directory" => DataKind::Directory,
+                    "symlink" => DataKind::SymbolicLink,
+                    "hardlink" => DataKind::HardLink,
+                    "whiteout" => DataKind::Whiteout,
+                    _ => return Err(invalid(&"expected file, dir, symlink, hardlink or whiteout")),
+                };
+                match comparison {
+                    Some(Comparison::Eq) => Ok(Self::Kind(true, kind)),
+                    Some(Comparison::Ne) => Ok(Self::Kind(false, kind)),
+                    _ => Err(unsupported()),
+                }
+            }
+            "path" | "uname" | "gname" => {
+                let field = match field {
+                    "path" => TextField::Path,
+                    "uname" => TextField::Uname,
+                    _ => TextField::Gname,
+                };
+                let text = match op {
+                    "==" => TextMatch::Equal(true, value.into()),
+                    "!=" => TextMatch::Equal(false, value.into()),
+                    "~" | "!~" => TextMatch::Glob(
+                        op == "~",
+                        globset::Glob::new(value)
+                            .map_err(|e| invalid(&e))?
+                            .compile_matcher(),
+                    ),
+                    _ => return Err(unsupported()),
+                };
+                Ok(Self::Text(field, text))
+            }
+            "size" | "uid" | "gid" | "mode" => {
+                let comparison = comparison.ok_or_else(unsupported)?;
+                let (field, value) = match field {
+                    "size" => (
+                        NumberField::Size,
+                        ByteSize::from_str(value).map_err(|e| invalid(&e))?.0 as u128,
+                    ),
+                    "uid" => (
+                        NumberField::Uid,
+                        value.parse().map_err(|e| invalid(&e))?,
+                    ),
+                    "gid" => (
+                        NumberField::Gid,
+                        value.parse().map_err(|e| invalid(&e))?,
+                    ),
+                    _ => (
+                        NumberField::Mode,
+                        u16::from_str_radix(value, 8).map_err(|e| invalid(&e))? as u128,
+                    ),
+                };
+                Ok(Self::Number(field, comparison, value))
+            }
+            "ctime" | "mtime" | "atime" => {
+                let comparison = comparison.ok_or_else(unsupported)?;
+                let field = match field {
+                    "ctime" => TimeField::Created,
+                    "mtime" => TimeField::Modified,
+                    _ => TimeField::Accessed,
+                };
+                let time = DateTime::from_str(value).map_err(|e| invalid(&e))?;
+                let since_unix_epoch = time
+                    .to_system_time()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                Ok(Self::Time(field, comparison, since_unix_epoch))
+            }
+            _ => Err(format!(
+                "unknown field `{field}`, expected one of path, kind, size, mtime, ctime, atime, uname, gname, uid, gid and mode"
+            )),
+        }
+    }
+
+    fn matches<T>(&self, entry: &NormalEntry<T>) -> bool {
+        let metadata = entry.metadata();
+        let permission = metadata.permission();
+        match self {
+            Self::Kind(equal, kind) => (entry.header().data_kind() == *kind) == *equal,
+            Self::Text(field, text) => {
+                let value = match field {
+                    TextField::Path => Some(entry.header().path().as_str()),
+                    TextField::Uname => permission.map(|it| it.uname()),
+                    TextField::Gname => permission.map(|it| it.gname()),
+                };
+                value.is_some_and(|value| match text {
+                    TextMatch::Equal(equal, expected) => (value == expected) == *equal,
+                    TextMatch::Glob(matched, glob) => glob.is_match(value) == *matched,
+                })
+            }
+            Self::Number(field, comparison, expected) => {
+                let value = match field {
+                    NumberField::Size => metadata.raw_file_size(),
+                    NumberField::Uid => permission.map(|it| it.uid() as u128),
+                    NumberField::Gid => permission.map(|it| it.gid() as u128),
+                    NumberField::Mode => permission.map(|it| (it.permissions() & 0o7777) as u128),
+                };
+                value.is_some_and(|value| comparison.test(value, *expected))
+            }
+            Self::Time(field, comparison, expected) => {
+                let value = match field {
+                    TimeField::Created => metadata.created(),
+                    TimeField::Modified => metadata.modified(),
+                    TimeField::Accessed => metadata.accessed(),
+                };
+                value.is_some_and(|value| comparison.test(value, *expected))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pna::{EntryBuilder, Permission, WriteOptions};
+    use std::io::Write;
+
+    fn file(name: &str, size: usize, modified: u64) -> NormalEntry {
+        let mut builder = EntryBuilder::new_file(name.into(), WriteOptions::store()).unwrap();
+        builder.write_all(&vec![0; size]).unwrap();
+        builder.modified(Duration::from_secs(modified));
+        builder.permission(Permission::new(
+            1000,
+            "www".into(),
+            100,
+            "users".into(),
+            0o644,
+        ));
+        builder.build().unwrap()
+    }
+
+    fn matches(filter: &str, entry: &NormalEntry) -> bool {
+        EntryFilter::from_str(filter).unwrap().matches(entry)
+    }
+
+    #[test]
+    fn match_kind_and_path() {
+        let entry = file("var/log/a.log", 0, 0);
+        assert!(matches("kind == file", &entry));
+        assert!(!matches("kind == dir", &entry));
+        assert!(matches("kind != symlink", &entry));
+        assert!(matches("path ~ \"*.log\"", &entry));
+        assert!(matches("path !~ *.txt", &entry));
+        assert!(matches("path == var/log/a.log", &entry));
+    }
+
+    #[test]
+    fn match_size_and_time() {
+        let entry = file("a", 2048, 1_700_000_000);
+        assert!(matches("size > 1KiB", &entry));
+        assert!(!matches("size >= 1M", &entry));
+        assert!(matches("size == 2048", &entry));
+        assert!(matches("mtime < 2024-01-01", &entry));
+        assert!(matches("mtime >= @1700000000", &entry));
+        assert!(!matches("ctime < 2024-01-01", &entry));
+    }
+
+    #[test]
+    fn match_owner_and_mode() {
+        let entry = file("a", 0, 0);
+        assert!(matches("uname == \"www\"", &entry));
+        assert!(matches("gname ~ us*", &entry));
+        assert!(matches("uid == 1000 && gid < 1000", &entry));
+        assert!(matches("mode == 644", &entry));
+        assert!(!matches("mode > 755", &entry));
+    }
+
+    #[test]
+    fn combine_conditions() {
+        let entry = file("a.log", 20, 0);
+        assert!(matches(
+            "kind == file && size > 10 && path ~ \"*.log\" && uname == \"www\"",
+            &entry
+        ));
+        assert!(matches("size > 100 || path ~ *.log", &entry));
+        assert!(!matches("!(size > 10)", &entry));
+        assert!(matches("!size > 100 && (uid == 0 || uid == 1000)", &entry));
+    }
+
+    #[test]
+    fn parse_quoted_escape() {
+        let entry = file("say \"hi\".txt", 0, 0);
+        assert!(matches(r#"path == "say \"hi\".txt""#, &entry));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(EntryFilter::from_str("").is_err());
+        assert!(EntryFilter::from_str("size >").is_err());
+        assert!(EntryFilter::from_str("size ~ 10").is_err());
+        assert!(EntryFilter::from_str("kind == socket").is_err());
+        assert!(EntryFilter::from_str("name == a").is_err());
+        assert!(EntryFilter::from_str("path == a &&").is_err());
+        assert!(EntryFilter::from_str("(path == a").is_err());
+        assert!(EntryFilter::from_str("path == \"a").is_err());
+        assert!(EntryFilter::from_str("mtime < yesterday-ish").is_err());
+    }
+}
@@ -1,31 +1,40 @@
 mod acl;
 pub mod append;
 pub mod bugreport;
+pub(crate) mod cat;
+mod checkpoint;
 mod chmod;
 mod chown;
 mod chunk;
 mod commons;
+pub(crate) mod compact;
 pub mod complete;
 pub(crate) mod concat;
 pub mod create;
 mod delete;
+pub(crate) mod diff;
 pub(super) mod experimental;
+pub(crate) mod export;
 pub mod extract;
+pub(crate) mod import;
 pub mod list;
 mod migrate;
+mod progress;
+mod snapshot;
 mod sort;
 pub mod split;
 pub(crate) mod stdio;
 pub(crate) mod strip;
 pub mod update;
+pub(crate) mod verify;
 pub mod xattr;
 
 use crate::cli::{CipherAlgorithmArgs, Cli, Commands, PasswordArgs};
 use std::{fs, io};
 
-fn ask_password(args: PasswordArgs) -> io::Result<Option<String>> {
-    if let Some(path) = args.password_file {
-        return Ok(Some(fs::read_to_string(path)?));
+fn ask_passwords(args: PasswordArgs) -> io::Result<Vec<String>> {
+    if !args.password_file.is_empty() {
+        return args.password_file.iter().map(fs::read_to_string).collect();
     };
     Ok(match args.password {
         Some(password @ Some(_)) => {
@@ -34,7 +43,17 @@ fn ask_password(args: PasswordArgs) -> io::Result<Option<String>> {
         }
         Some(None) => Some(gix_prompt::securely("Enter password: ").map_err(io::Error::other)?),
         None => None,
-    })
+    }
+    .into_iter()
+    .collect())
+}
+
+fn ask_password(args: PasswordArgs) -> io::Result<Option<String>> {
+    let passwords = ask_passwords(args)?;
+    if passwords.len() > 1 {
+        log::warn!("Multiple passwords were given, only the first one is used.");
+    }
+    Ok(passwords.into_iter().next())
 }
 
 fn check_password(password: &Option<String>, cipher_args: &CipherAlgorithmArgs) {
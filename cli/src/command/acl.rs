@@ -20,7 +20,7 @@ use nom::{
     combinator::{map, opt},
     Parser as _,
 };
-use pna::{Chunk, NormalEntry, RawChunk};
+use pna::{Chunk, NormalEntry, RawChunk, ReadOptions};
 use regex::Regex;
 use std::{
     collections::{HashMap, HashSet},
@@ -303,7 +303,7 @@ fn archive_get_acl(args: GetAclCommand) -> anyhow::Result<()> {
 
     run_entries(
         archives,
-        || password.as_deref(),
+        &ReadOptions::with_password(password.as_deref()),
         |entry| {
             let entry = entry?;
             let name = entry.header().path();
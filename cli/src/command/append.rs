@@ -1,18 +1,19 @@
 use crate::{
     cli::{
-        CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, FileArgs, HashAlgorithmArgs,
-        PasswordArgs,
+        AtimePreserve, CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, ExclusionArgs,
+        FileArgs, HashAlgorithmArgs, PasswordArgs,
     },
     command::{
         ask_password, check_password,
         commons::{
-            collect_items, create_entry, entry_option, CreateOptions, Exclude, KeepOptions,
-            OwnerOptions, PathTransformers, TimeOptions,
+            collect_items, create_entry, entry_option, to_entry_name, CreateOptions, Exclude,
+            HardLinks, KeepOptions, OwnerOptions, PathTransformers, PlannedAction, TimeOptions,
         },
         Command,
     },
     utils::{
         self,
+        fs::IdMap,
         re::{bsd::SubstitutionRule, gnu::TransformRule},
         PathPartExt,
     },
@@ -20,7 +21,8 @@ use crate::{
 use clap::{ArgGroup, Parser, ValueHint};
 use pna::Archive;
 use std::{
-    env, fs, io,
+    env, fs,
+    io::{self, prelude::*, SeekFrom},
     path::{Path, PathBuf},
 };
 
@@ -35,6 +37,10 @@ use std::{
     group(ArgGroup::new("unstable-gitignore").args(["gitignore"]).requires("unstable")),
     group(ArgGroup::new("unstable-substitution").args(["substitutions"]).requires("unstable")),
     group(ArgGroup::new("unstable-transform").args(["transforms"]).requires("unstable")),
+    group(ArgGroup::new("unstable-id-map").args(["owner_map", "group_map"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-atime-preserve").args(["atime_preserve"]).requires("unstable")),
+    group(ArgGroup::new("unstable-dry-run").args(["dry_run"]).requires("unstable")),
+    group(ArgGroup::new("unstable-hard-dereference").args(["hard_dereference"]).requires("unstable")),
     group(ArgGroup::new("path-transform").args(["substitutions", "transforms"])),
     group(ArgGroup::new("read-files-from").args(["files_from", "files_from_stdin"])),
     group(ArgGroup::new("store-uname").args(["uname"]).requires("keep_permission")),
@@ -109,6 +115,20 @@ pub(crate) struct AppendCommand {
         help = "This is equivalent to --uname \"\" --gname \"\". It causes user and group names to not be stored in the archive"
     )]
     pub(crate) numeric_owner: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map user names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) owner_map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map group names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) group_map: Option<PathBuf>,
     #[arg(long, help = "Overrides the creation time read from disk")]
     ctime: Option<DateTime>,
     #[arg(
@@ -145,8 +165,22 @@ pub(crate) struct AppendCommand {
     pub(crate) exclude_from: Option<String>,
     #[arg(long, help = "Ignore files from .gitignore (unstable)")]
     pub(crate) gitignore: bool,
+    #[command(flatten)]
+    pub(crate) exclusion: ExclusionArgs,
     #[arg(long, help = "Follow symbolic links")]
     pub(crate) follow_links: bool,
+    #[arg(
+        long,
+        help = "Archive hard-linked files as separate copies instead of hard link entries (unstable)"
+    )]
+    pub(crate) hard_dereference: bool,
+    #[arg(
+        long,
+        value_name = "METHOD",
+        require_equals = true,
+        help = "Preserve the access times of the files being archived, by restoring them after reading (replace) or by reading without updating them where the system supports it (system) [default: replace] (unstable)"
+    )]
+    pub(crate) atime_preserve: Option<Option<AtimePreserve>>,
     #[arg(
         short = 's',
         value_name = "PATTERN",
@@ -169,6 +203,12 @@ pub(crate) struct AppendCommand {
         value_hint = ValueHint::DirPath
     )]
     working_dir: Option<PathBuf>,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive (unstable)"
+    )]
+    pub(crate) dry_run: bool,
     #[command(flatten)]
     pub(crate) compression: CompressionAlgorithmArgs,
     #[command(flatten)]
@@ -213,6 +253,8 @@ fn append_to_archive(args: AppendCommand) -> anyhow::Result<()> {
         args.uid,
         args.gid,
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
     let time_options = TimeOptions {
         mtime: args.mtime.map(|it| it.to_system_time()),
@@ -221,17 +263,26 @@ fn append_to_archive(args: AppendCommand) -> anyhow::Result<()> {
         clamp_ctime: args.clamp_ctime,
         atime: args.atime.map(|it| it.to_system_time()),
         clamp_atime: args.clamp_atime,
+        mtime_only: false,
     };
-    let create_options = CreateOptions {
+    let mut create_options = CreateOptions {
         option,
         keep_options,
         owner_options,
         time_options,
         follow_links: args.follow_links,
+        hard_links: HardLinks::default(),
+        atime_preserve: args.atime_preserve.map(Option::unwrap_or_default),
     };
     let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
 
-    let archive = open_archive_then_seek_to_end(&archive_path)?;
+    let opened = if args.dry_run {
+        // Fails like a real run on a file that is not an archive, without locking it.
+        Archive::read_header(fs::File::open(&archive_path)?)?;
+        None
+    } else {
+        Some(open_archive_then_seek_to_end(&archive_path)?)
+    };
 
     let mut files = args.file.files;
     if args.files_from_stdin {
@@ -259,9 +310,23 @@ fn append_to_archive(args: AppendCommand) -> anyhow::Result<()> {
         args.gitignore,
         args.follow_links,
         exclude,
+        &args.exclusion,
     )?;
+    let Some((archive, rollback)) = opened else {
+        for item in &target_items {
+            PlannedAction::Add.print(to_entry_name(item, &path_transformers));
+        }
+        return Ok(());
+    };
+    create_options.hard_links =
+        HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?;
 
-    run_append_archive(&create_options, &path_transformers, archive, target_items)
+    rollback.complete(run_append_archive(
+        &create_options,
+        &path_transformers,
+        archive,
+        target_items,
+    ))
 }
 
 pub(crate) fn run_append_archive(
@@ -285,32 +350,252 @@ pub(crate) fn run_append_archive(
     });
 
     for entry in rx.into_iter() {
+        utils::signal::check_interrupt()?;
         archive.add_entry(entry?)?;
     }
+    utils::signal::check_interrupt()?;
     archive.finalize()?;
     Ok(())
 }
 
+/// State of the last part of an archive before appending to it, used to undo a failed append.
+///
+/// The state is also written to a journal next to the archive before anything is appended,
+/// so that an append cut short by a crash is undone the next time the archive is opened for
+/// appending. Holds the locks taken by [open_archive_then_seek_to_end] on every part of the
+/// archive until it is dropped.
+pub(crate) struct AppendRollback {
+    file: fs::File,
+    end: u64,
+    tail: Vec<u8>,
+    journal: PathBuf,
+    _locks: Vec<utils::fs::FileLock>,
+}
+
+impl AppendRollback {
+    /// Truncates the appended entries and restores the original end of the archive.
+    pub(crate) fn rollback(mut self) -> io::Result<()> {
+        restore_end(&mut self.file, self.end, &self.tail)?;
+        remove_journal(&self.journal)
+    }
+
+    /// Makes the appended entries durable and discards the journal.
+    pub(crate) fn commit(self) -> io::Result<()> {
+        self.file.sync_all()?;
+        remove_journal(&self.journal)
+    }
+
+    /// Commits the append if `result` is successful, otherwise rolls it back.
+    pub(crate) fn complete<T>(self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        match result {
+            Ok(value) => {
+                self.commit()?;
+                Ok(value)
+            }
+            Err(e) => {
+                log::warn!("Append failed, restoring the archive to its previous state");
+                if let Err(e) = self.rollback() {
+                    log::error!("Failed to restore the archive: {e}");
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Path of the journal of an append to the archive at `path`.
+fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".append-journal");
+    path.with_file_name(name)
+}
+
+/// Writes the journal, which holds the part number, the original length of that part and the
+/// bytes that followed the last entry.
+fn write_journal(path: &Path, part: usize, end: u64, tail: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(&(part as u64).to_le_bytes())?;
+    file.write_all(&end.to_le_bytes())?;
+    file.write_all(tail)?;
+    file.sync_all()?;
+    sync_parent(path)
+}
+
+fn remove_journal(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)?;
+    sync_parent(path)
+}
+
+/// Makes the creation or removal of `path` durable.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn restore_end(file: &mut fs::File, end: u64, tail: &[u8]) -> io::Result<()> {
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end))?;
+    file.write_all(tail)?;
+    file.sync_all()
+}
+
+/// Undoes an append that was interrupted before it completed, if its journal exists.
+fn recover_interrupted_append(archive_path: &Path, journal: &Path) -> io::Result<()> {
+    let data = match fs::read(journal) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let (Some(part), Some(end), Some(tail)) = (data.get(..8), data.get(8..16), data.get(16..))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is broken", journal.display()),
+        ));
+    };
+    let part = u64::from_le_bytes(part.try_into().unwrap_or_default()) as usize;
+    let end = u64::from_le_bytes(end.try_into().unwrap_or_default());
+    log::warn!(
+        "Restoring {} to its state before an interrupted append",
+        archive_path.display()
+    );
+    let path = if part == 1 {
+        archive_path.to_path_buf()
+    } else {
+        archive_path.with_part(part).unwrap()
+    };
+    let mut file = fs::File::options().write(true).open(&path)?;
+    if file.metadata()?.len() < end {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} does not match {}, remove it if the archive was replaced",
+                journal.display(),
+                path.display()
+            ),
+        ));
+    }
+    restore_end(&mut file, end, tail)?;
+    remove_journal(journal)
+}
+
 pub(crate) fn open_archive_then_seek_to_end(
     path: impl AsRef<Path>,
-) -> anyhow::Result<Archive<fs::File>> {
+) -> anyhow::Result<(Archive<fs::File>, AppendRollback)> {
     let archive_path = path.as_ref();
     let mut num = 1;
-    let file = fs::File::options()
-        .write(true)
-        .read(true)
-        .open(archive_path)?;
+    let (file, lock) = utils::fs::open_locked(archive_path)?;
+    let mut locks = vec![lock];
+    let journal = journal_path(archive_path);
+    recover_interrupted_append(archive_path, &journal)?;
+    let mut last = file.try_clone()?;
     let mut archive = Archive::read_header(file)?;
     loop {
         archive.seek_to_end()?;
         if !archive.has_next_archive() {
-            break Ok(archive);
+            break;
         }
         num += 1;
-        let file = fs::File::options()
-            .write(true)
-            .read(true)
-            .open(archive_path.with_part(num).unwrap())?;
+        let (file, lock) = utils::fs::open_locked(archive_path.with_part(num).unwrap())?;
+        locks.push(lock);
+        last = file.try_clone()?;
         archive = archive.read_next_archive(file)?;
     }
+    // `last` shares the file offset with the archive, so seek back after reading the tail.
+    let end = last.stream_position()?;
+    let mut tail = Vec::new();
+    last.read_to_end(&mut tail)?;
+    last.seek(SeekFrom::Start(end))?;
+    write_journal(&journal, num, end, &tail)?;
+    utils::signal::catch_interrupt()?;
+    let rollback = AppendRollback {
+        file: last,
+        end,
+        tail,
+        journal,
+        _locks: locks,
+    };
+    Ok((archive, rollback))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pna::{EntryBuilder, WriteOptions};
+
+    fn write_archive(path: &Path) -> Vec<u8> {
+        let mut archive = Archive::write_header(Vec::new()).unwrap();
+        let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"a").unwrap();
+        archive.add_entry(builder.build().unwrap()).unwrap();
+        let bytes = archive.finalize().unwrap();
+        fs::write(path, &bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn rollback_append() {
+        let path = env::temp_dir().join("pna_rollback_append.pna");
+        let original = write_archive(&path);
+
+        let (mut archive, rollback) = open_archive_then_seek_to_end(&path).unwrap();
+        let mut builder = EntryBuilder::new_file("b.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"b").unwrap();
+        archive.add_entry(builder.build().unwrap()).unwrap();
+        drop(archive);
+        assert_ne!(fs::read(&path).unwrap(), original);
+
+        rollback.rollback().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!journal_path(&path).exists());
+    }
+
+    #[test]
+    fn recover_after_crash() {
+        let path = env::temp_dir().join("pna_recover_after_crash.pna");
+        let original = write_archive(&path);
+
+        let (mut archive, rollback) = open_archive_then_seek_to_end(&path).unwrap();
+        let mut builder = EntryBuilder::new_file("b.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"b").unwrap();
+        archive.add_entry(builder.build().unwrap()).unwrap();
+        drop(archive);
+        // Neither committed nor rolled back, as if the process had been killed.
+        drop(rollback);
+        assert!(journal_path(&path).exists());
+
+        let (archive, rollback) = open_archive_then_seek_to_end(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), original);
+        archive.finalize().unwrap();
+        rollback.commit().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!journal_path(&path).exists());
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn lock_while_appending() {
+        let path = env::temp_dir().join("pna_lock_while_appending.pna");
+        write_archive(&path);
+
+        let (archive, rollback) = open_archive_then_seek_to_end(&path).unwrap();
+        let err = open_archive_then_seek_to_end(&path).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::WouldBlock)
+        );
+        drop(archive);
+        drop(rollback);
+        assert!(open_archive_then_seek_to_end(&path).is_ok());
+    }
 }
@@ -0,0 +1,86 @@
+use crate::{
+    cli::{EntryFilter, FileArgs, PasswordArgs},
+    command::{
+        ask_passwords,
+        commons::{collect_split_archives, run_process_archive, Exclude},
+        extract::entry_reader,
+        Command,
+    },
+    utils::GlobPatterns,
+};
+use clap::Parser;
+use pna::{DataKind, ReadOptions};
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+};
+
+#[derive(Parser, Clone, Debug)]
+pub(crate) struct CatCommand {
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[command(flatten)]
+    pub(crate) file: FileArgs,
+}
+
+impl Command for CatCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        cat_archive(self)
+    }
+}
+
+fn cat_archive(args: CatCommand) -> anyhow::Result<()> {
+    let passwords = ask_passwords(args.password)?;
+    let archives = collect_split_archives(&args.file.archive)?;
+    run_cat_archive(
+        archives
+            .into_iter()
+            .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+        args.file.files,
+        &passwords,
+        &Exclude::default(),
+        None,
+        None,
+    )
+}
+
+/// Writes the contents of the file entries matching `files`, not excluded by `exclude` and
+/// satisfying `filter` to stdout in archive order. If `selection` is given, only the entries
+/// at those positions are written, as returned by
+/// [select_versions](crate::command::commons::select_versions).
+pub(crate) fn run_cat_archive(
+    reader: impl IntoIterator<Item = impl Read>,
+    files: Vec<String>,
+    passwords: &[String],
+    exclude: &Exclude,
+    filter: Option<&EntryFilter>,
+    selection: Option<&HashSet<usize>>,
+) -> anyhow::Result<()> {
+    let read_options = ReadOptions::with_passwords(passwords);
+    let globs =
+        GlobPatterns::new(files).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stdout = io::stdout().lock();
+    let mut position = 0;
+    run_process_archive(reader, &read_options, |entry| {
+        let item = entry?;
+        let selected = selection.is_none_or(|it| it.contains(&position));
+        position += 1;
+        let item_path = item.header().path().as_str();
+        if !selected
+            || !globs.is_empty() && !globs.matches_any(item_path)
+            || exclude.excluded(item_path)
+            || filter.is_some_and(|it| !it.matches(&item))
+        {
+            log::debug!("Skip: {}", item.header().path());
+            return Ok(());
+        }
+        if item.header().data_kind() != DataKind::File {
+            return Ok(());
+        }
+        io::copy(&mut entry_reader(&item, &read_options)?, &mut stdout)?;
+        Ok(())
+    })?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::command::commons::ARCHIVE_HEADER_SIZE;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Progress of an interrupted `pna create --checkpoint`, stored next to the archive.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    version: u32,
+    split: bool,
+    /// Number of the archive part the last complete entry was written to.
+    part: usize,
+    /// Byte offset in that part just after the last complete entry.
+    offset: u64,
+    /// Input paths whose entries are fully written.
+    done: BTreeSet<String>,
+}
+
+impl Checkpoint {
+    const VERSION: u32 = 1;
+
+    /// Returns the path of the checkpoint file of `archive`.
+    pub(crate) fn path_for(archive: &Path) -> PathBuf {
+        let mut path = archive.as_os_str().to_owned();
+        path.push(".checkpoint");
+        PathBuf::from(path)
+    }
+
+    /// Loads the checkpoint left by an interrupted run that was written with the same
+    /// split mode.
+    pub(crate) fn load(path: &Path, split: bool) -> io::Result<Self> {
+        let bytes = fs::read(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("No checkpoint to resume from at {}: {e}", path.display()),
+            )
+        })?;
+        let checkpoint = serde_json::from_slice::<Self>(&bytes)?;
+        if checkpoint.version != Self::VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported checkpoint version {} in {}",
+                    checkpoint.version,
+                    path.display()
+                ),
+            ));
+        }
+        if checkpoint.split != split {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} was written {} --split, resume with the same options",
+                    path.display(),
+                    if checkpoint.split { "with" } else { "without" }
+                ),
+            ));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Returns `true` if the entry of `path` was fully written before the interruption.
+    pub(crate) fn is_done(&self, path: &Path) -> bool {
+        self.done.contains(path.to_string_lossy().as_ref())
+    }
+
+    /// Writes the checkpoint through a temporary file so that an interruption never leaves
+    /// a partially written checkpoint behind.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// Records the progress of an archive creation to its checkpoint file at most once per
+/// interval.
+pub(crate) struct Checkpointer {
+    path: PathBuf,
+    interval: Duration,
+    last_saved: Instant,
+    state: Checkpoint,
+    resumed: bool,
+}
+
+impl Checkpointer {
+    pub(crate) fn new(path: PathBuf, interval: Duration, split: bool) -> Self {
+        Self {
+            path,
+            interval,
+            last_saved: Instant::now(),
+            state: Checkpoint {
+                version: Checkpoint::VERSION,
+                split,
+                part: 1,
+                offset: ARCHIVE_HEADER_SIZE as u64,
+                done: BTreeSet::new(),
+            },
+            resumed: false,
+        }
+    }
+
+    pub(crate) fn resume(path: PathBuf, interval: Duration, state: Checkpoint) -> Self {
+        Self {
+            path,
+            interval,
+            last_saved: Instant::now(),
+            state,
+            resumed: true,
+        }
+    }
+
+    /// Returns the part and the offset to continue writing at, if resumed from a checkpoint.
+    pub(crate) fn resume_point(&self) -> Option<(usize, u64)> {
+        self.resumed.then_some((self.state.part, self.state.offset))
+    }
+
+    /// Records that the entry of `item` ends at `offset` in `part`.
+    pub(crate) fn entry_written(
+        &mut self,
+        item: &Path,
+        part: usize,
+        offset: u64,
+    ) -> io::Result<()> {
+        self.state.done.insert(item.to_string_lossy().into_owned());
+        self.state.part = part;
+        self.state.offset = offset;
+        if self.last_saved.elapsed() >= self.interval {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the current progress to the checkpoint file.
+    pub(crate) fn save(&mut self) -> io::Result<()> {
+        self.state.save(&self.path)?;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+
+    /// Removes the checkpoint file once the archive is complete.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,17 +1,19 @@
 use crate::{
-    cli::{PasswordArgs, SolidEntriesTransformStrategy, SolidEntriesTransformStrategyArgs},
+    cli::{
+        EntryFilter, PasswordArgs, SolidEntriesTransformStrategy, SolidEntriesTransformStrategyArgs,
+    },
     command::{
         ask_password,
         commons::{
-            collect_split_archives, run_transform_entry, TransformStrategyKeepSolid,
-            TransformStrategyUnSolid,
+            collect_split_archives, is_selected, run_entries, run_transform_entry, PlannedAction,
+            TransformStrategyKeepSolid, TransformStrategyUnSolid,
         },
         Command,
     },
     utils::{env::NamedTempFile, GlobPatterns, PathPartExt},
 };
 use bitflags::bitflags;
-use clap::{Parser, ValueHint};
+use clap::{ArgGroup, Parser, ValueHint};
 use nom::{
     branch::alt,
     character::complete::char,
@@ -19,10 +21,13 @@ use nom::{
     multi::{many0, many1, separated_list1},
     Parser as _,
 };
-use pna::NormalEntry;
+use pna::{NormalEntry, ReadOptions};
 use std::{io, ops::BitOr, path::PathBuf, str::FromStr};
 
 #[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[command(
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
+)]
 pub(crate) struct ChmodCommand {
     #[arg(value_hint = ValueHint::FilePath)]
     archive: PathBuf,
@@ -30,6 +35,18 @@ pub(crate) struct ChmodCommand {
     mode: Mode,
     #[arg(value_hint = ValueHint::AnyPath)]
     files: Vec<String>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    filter: Option<EntryFilter>,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive"
+    )]
+    dry_run: bool,
     #[command(flatten)]
     transform_strategy: SolidEntriesTransformStrategyArgs,
     #[command(flatten)]
@@ -45,7 +62,7 @@ impl Command for ChmodCommand {
 
 fn archive_chmod(args: ChmodCommand) -> anyhow::Result<()> {
     let password = ask_password(args.password)?;
-    if args.files.is_empty() {
+    if args.files.is_empty() && args.filter.is_none() {
         return Ok(());
     }
     let globs = GlobPatterns::new(args.files)
@@ -61,6 +78,21 @@ fn archive_chmod(args: ChmodCommand) -> anyhow::Result<()> {
     #[cfg(feature = "memmap")]
     let archives = mmaps.iter().map(|m| m.as_ref());
 
+    if args.dry_run {
+        run_entries(
+            archives,
+            &ReadOptions::with_password(password.as_deref()),
+            |entry| {
+                let entry = entry?;
+                if is_selected(&entry, &globs, args.filter.as_ref()) {
+                    PlannedAction::Replace.print(entry.header().path());
+                }
+                Ok(())
+            },
+        )?;
+        return Ok(());
+    }
+
     let output_path = args.archive.remove_part().unwrap();
     let mut temp_file =
         NamedTempFile::new(|| output_path.parent().unwrap_or_else(|| ".".as_ref()))?;
@@ -72,7 +104,7 @@ fn archive_chmod(args: ChmodCommand) -> anyhow::Result<()> {
             || password.as_deref(),
             |entry| {
                 let entry = entry?;
-                if globs.matches_any(entry.header().path()) {
+                if is_selected(&entry, &globs, args.filter.as_ref()) {
                     Ok(Some(transform_entry(entry, &args.mode)))
                 } else {
                     Ok(Some(entry))
@@ -86,7 +118,7 @@ fn archive_chmod(args: ChmodCommand) -> anyhow::Result<()> {
             || password.as_deref(),
             |entry| {
                 let entry = entry?;
-                if globs.matches_any(entry.header().path()) {
+                if is_selected(&entry, &globs, args.filter.as_ref()) {
                     Ok(Some(transform_entry(entry, &args.mode)))
                 } else {
                     Ok(Some(entry))
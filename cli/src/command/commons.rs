@@ -1,24 +1,33 @@
 use crate::{
-    cli::{CipherAlgorithmArgs, CompressionAlgorithmArgs, HashAlgorithmArgs},
+    cli::{
+        AtimePreserve, CipherAlgorithmArgs, CompressionAlgorithmArgs, EntryFilter, ExclusionArgs,
+        HashAlgorithmArgs,
+    },
     utils::{
         self,
+        env::NamedTempFile,
+        fs::IdMap,
         re::{
             bsd::{SubstitutionRule, SubstitutionRules},
             gnu::{TransformRule, TransformRules},
         },
-        BsdGlobPatterns, PathPartExt,
+        reference_from_parent, BsdGlobPatterns, GlobPatterns, PathPartExt,
     },
 };
 use path_slash::*;
 use pna::{
     prelude::*, Archive, EntryBuilder, EntryName, EntryPart, EntryReference, NormalEntry,
-    ReadEntry, SolidEntryBuilder, WriteOptions, MIN_CHUNK_BYTES_SIZE, PNA_HEADER,
+    ProgressObserver, ReadEntry, ReadOptions, SolidEntryBuilder, VersionPlan, VersionSelector,
+    WriteOptions, MIN_CHUNK_BYTES_SIZE, PNA_HEADER,
 };
 use std::{
     borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
     fs,
     io::{self, prelude::*},
     path::{Path, PathBuf},
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -36,6 +45,8 @@ pub(crate) struct OwnerOptions {
     pub(crate) gname: Option<String>,
     pub(crate) uid: Option<u32>,
     pub(crate) gid: Option<u32>,
+    pub(crate) owner_map: IdMap,
+    pub(crate) group_map: IdMap,
 }
 
 impl OwnerOptions {
@@ -46,6 +57,8 @@ impl OwnerOptions {
         uid: Option<u32>,
         gid: Option<u32>,
         numeric_owner: bool,
+        owner_map: IdMap,
+        group_map: IdMap,
     ) -> Self {
         Self {
             uname: if numeric_owner {
@@ -60,6 +73,8 @@ impl OwnerOptions {
             },
             uid,
             gid,
+            owner_map,
+            group_map,
         }
     }
 }
@@ -71,6 +86,76 @@ pub(crate) struct CreateOptions {
     pub(crate) owner_options: OwnerOptions,
     pub(crate) time_options: TimeOptions,
     pub(crate) follow_links: bool,
+    pub(crate) hard_links: HardLinks,
+    pub(crate) atime_preserve: Option<AtimePreserve>,
+}
+
+/// Items that are hard links to another item of the same run, which are archived as hard
+/// link entries instead of copies of the file.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub(crate) struct HardLinks(Arc<BTreeMap<PathBuf, PathBuf>>);
+
+impl HardLinks {
+    /// Finds the regular files among `items` that share a (device, inode) pair, and maps
+    /// each of them to an item archived as a file. With `dereference`, every file is
+    /// archived as a copy instead.
+    ///
+    /// Hard link references are resolved from the directory of the link, and extraction
+    /// skips references that contain `..` by default. So a file is only linked to an item
+    /// in the same directory or one of its subdirectories, and becomes a copy otherwise.
+    pub(crate) fn new(
+        items: &[PathBuf],
+        follow_links: bool,
+        dereference: bool,
+    ) -> io::Result<Self> {
+        if dereference {
+            return Ok(Self::default());
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let mut groups = HashMap::<_, Vec<_>>::new();
+            for item in items {
+                let meta = if follow_links {
+                    fs::metadata(item)
+                } else {
+                    fs::symlink_metadata(item)
+                }?;
+                if meta.is_file() && meta.nlink() > 1 {
+                    groups
+                        .entry((meta.dev(), meta.ino()))
+                        .or_default()
+                        .push(item);
+                }
+            }
+            let mut links = BTreeMap::new();
+            for mut group in groups.into_values() {
+                // Deeper items first, so that they become the originals.
+                group.sort_by_key(|it| std::cmp::Reverse(it.components().count()));
+                let mut originals = Vec::<&PathBuf>::new();
+                for item in group {
+                    let dir = item.parent().unwrap_or_else(|| "".as_ref());
+                    match originals.iter().find(|it| it.starts_with(dir)) {
+                        Some(original) => {
+                            links.insert(item.clone(), PathBuf::clone(original));
+                        }
+                        None => originals.push(item),
+                    }
+                }
+            }
+            Ok(Self(Arc::new(links)))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (items, follow_links);
+            Ok(Self::default())
+        }
+    }
+
+    /// Returns the item that `path` is a hard link to.
+    fn original(&self, path: &Path) -> Option<&Path> {
+        self.0.get(path).map(PathBuf::as_path)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -112,6 +197,8 @@ pub(crate) struct TimeOptions {
     pub(crate) clamp_ctime: bool,
     pub(crate) atime: Option<SystemTime>,
     pub(crate) clamp_atime: bool,
+    /// Store only the modification time, dropping the creation and access times.
+    pub(crate) mtime_only: bool,
 }
 
 pub(crate) fn collect_items(
@@ -121,6 +208,7 @@ pub(crate) fn collect_items(
     gitignore: bool,
     follow_links: bool,
     exclude: Exclude,
+    exclusion: &ExclusionArgs,
 ) -> io::Result<Vec<PathBuf>> {
     let mut files = files.into_iter();
     if let Some(p) = files.next() {
@@ -128,8 +216,38 @@ pub(crate) fn collect_items(
         for p in files {
             builder.add(p);
         }
-        builder.filter_entry(move |e| !exclude.excluded(e.path().to_slash_lossy()));
+        let ExclusionArgs {
+            one_file_system,
+            exclude_caches,
+            exclude_vcs,
+            exclude_if_present,
+            pnaignore,
+        } = exclusion.clone();
+        builder.filter_entry(move |e| {
+            if exclude.excluded(e.path().to_slash_lossy()) {
+                return false;
+            }
+            if exclude_vcs && e.file_name().to_str().is_some_and(is_vcs_name) {
+                return false;
+            }
+            if e.file_type().is_some_and(|ty| ty.is_dir()) {
+                if exclude_caches && is_cache_dir(e.path()) {
+                    return false;
+                }
+                if exclude_if_present
+                    .iter()
+                    .any(|name| e.path().join(name).exists())
+                {
+                    return false;
+                }
+            }
+            true
+        });
+        if pnaignore {
+            builder.add_custom_ignore_filename(".pnaignore");
+        }
         builder
+            .same_file_system(one_file_system)
             .max_depth(if recursive { None } else { Some(0) })
             .hidden(false)
             .ignore(false)
@@ -155,6 +273,46 @@ pub(crate) fn collect_items(
     }
 }
 
+/// Names of the directories and files that version control systems keep their data in,
+/// as excluded by `--exclude-vcs` of GNU tar.
+const VCS_NAMES: &[&str] = &[
+    "CVS",
+    ".cvsignore",
+    "RCS",
+    "SCCS",
+    ".git",
+    ".gitignore",
+    ".gitattributes",
+    ".gitmodules",
+    ".arch-ids",
+    "{arch}",
+    "=RELEASE-ID",
+    "=meta-update",
+    "=update",
+    ".bzr",
+    ".bzrignore",
+    ".bzrtags",
+    ".hg",
+    ".hgignore",
+    ".hgtags",
+    "_darcs",
+    ".svn",
+];
+
+fn is_vcs_name(name: &str) -> bool {
+    VCS_NAMES.contains(&name)
+}
+
+/// Returns `true` if `dir` contains a `CACHEDIR.TAG` file that starts with the signature
+/// defined by the Cache Directory Tagging Specification.
+fn is_cache_dir(dir: &Path) -> bool {
+    const SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+    let mut buf = [0; SIGNATURE.len()];
+    fs::File::open(dir.join("CACHEDIR.TAG"))
+        .and_then(|mut file| file.read_exact(&mut buf))
+        .is_ok_and(|_| buf == SIGNATURE)
+}
+
 pub(crate) fn collect_split_archives(first: impl AsRef<Path>) -> io::Result<Vec<fs::File>> {
     let mut archives = Vec::new();
     let mut n = 1;
@@ -175,14 +333,29 @@ pub(crate) fn create_entry(
         owner_options,
         time_options,
         follow_links,
+        hard_links,
+        atime_preserve,
     }: &CreateOptions,
     substitutions: &Option<PathTransformers>,
 ) -> io::Result<NormalEntry> {
-    let entry_name = if let Some(substitutions) = substitutions {
-        EntryName::from(substitutions.apply(path.to_string_lossy(), false, false))
-    } else {
-        EntryName::from_lossy(path)
-    };
+    let entry_name = to_entry_name(path, substitutions);
+    if let Some(original) = hard_links.original(path) {
+        let original = to_entry_name(original, substitutions);
+        let reference = EntryReference::from_lossy(reference_from_parent(
+            entry_name.as_path(),
+            original.as_path(),
+        ));
+        let entry = EntryBuilder::new_hard_link(entry_name, reference)?;
+        return apply_metadata(
+            entry,
+            path,
+            keep_options,
+            owner_options,
+            time_options,
+            fs::metadata,
+        )?
+        .build();
+    }
     if !follow_links && path.is_symlink() {
         let source = fs::read_link(path)?;
         let reference = if let Some(substitutions) = substitutions {
@@ -202,20 +375,37 @@ pub(crate) fn create_entry(
         .build();
     } else if path.is_file() {
         let mut entry = EntryBuilder::new_file(entry_name, option)?;
+        let mut file = match atime_preserve {
+            Some(AtimePreserve::System) => utils::fs::open_noatime(path)?,
+            _ => fs::File::open(path)?,
+        };
+        // Captured before reading, which updates it.
+        let accessed = match atime_preserve {
+            Some(AtimePreserve::Replace) => Some(file.metadata()?.accessed()?),
+            _ => None,
+        };
         #[cfg(feature = "memmap")]
         {
             const FILE_SIZE_THRESHOLD: u64 = 50 * 1024 * 1024;
-            let meta = fs::metadata(path)?;
-            if FILE_SIZE_THRESHOLD < meta.len() {
-                let file = utils::mmap::Mmap::open(path)?;
+            if FILE_SIZE_THRESHOLD < file.metadata()?.len() {
+                let file = utils::mmap::Mmap::try_from(file)?;
                 entry.write_all(&file[..])?;
             } else {
-                entry.write_all(&fs::read(path)?)?;
+                io::copy(&mut file, &mut entry)?;
             }
         }
         #[cfg(not(feature = "memmap"))]
         {
-            entry.write_all(&fs::read(path)?)?;
+            io::copy(&mut file, &mut entry)?;
+        }
+        if let Some(accessed) = accessed {
+            let times = utils::fs::PathTimes {
+                accessed: Some(accessed),
+                ..Default::default()
+            };
+            if let Err(e) = utils::fs::set_path_times(path, times) {
+                log::warn!("failed to restore access time of {}: {e}", path.display());
+            }
         }
         return apply_metadata(
             entry,
@@ -244,6 +434,31 @@ pub(crate) fn create_entry(
     ))
 }
 
+pub(crate) fn to_entry_name(path: &Path, substitutions: &Option<PathTransformers>) -> EntryName {
+    if let Some(substitutions) = substitutions {
+        EntryName::from(substitutions.apply(path.to_string_lossy(), false, false))
+    } else {
+        EntryName::from_lossy(path)
+    }
+}
+
+/// Creates the deletion marker of `path`, removed at the time of archiving, which is
+/// replaced or clamped by the modification time of `time_options`.
+pub(crate) fn create_whiteout(
+    path: &Path,
+    time_options: &TimeOptions,
+    substitutions: &Option<PathTransformers>,
+) -> io::Result<NormalEntry> {
+    let mut builder = EntryBuilder::new_whiteout(to_entry_name(path, substitutions));
+    let mtime = clamped_time(
+        Some(SystemTime::now()),
+        time_options.mtime,
+        time_options.clamp_mtime,
+    );
+    builder.modified(mtime.and_then(|m| m.duration_since(UNIX_EPOCH).ok()));
+    builder.build()
+}
+
 pub(crate) fn entry_option(
     compression: CompressionAlgorithmArgs,
     cipher: CipherAlgorithmArgs,
@@ -262,10 +477,70 @@ pub(crate) fn entry_option(
         })
         .cipher_mode(cipher.mode())
         .hash_algorithm(hash.algorithm())
+        .archive_key(hash.archive_key)
         .password(password);
     option_builder.build()
 }
 
+/// Resolves the name and id of the owner or group `id` to store in an entry, applying `map`
+/// and then the `name` and `id` overrides.
+#[cfg(unix)]
+fn resolve_owner(
+    id: u64,
+    map: &IdMap,
+    name: Option<&str>,
+    id_override: Option<u32>,
+    lookup: impl Fn(u64) -> io::Result<String>,
+) -> io::Result<(String, u64)> {
+    if let Some(id) = id_override {
+        let id = id.into();
+        let name = match name {
+            Some(name) => name.into(),
+            None => lookup(id)?,
+        };
+        return Ok((name, id));
+    }
+    Ok(match name {
+        Some(name) => {
+            let (_, id) = map.map(&lookup(id).unwrap_or_default(), id);
+            (name.into(), id)
+        }
+        None => {
+            let found = lookup(id)?;
+            let (name, id) = map.map(&found, id);
+            (name.into(), id)
+        }
+    })
+}
+
+/// Replaces the users and groups of the ACL entries by the ones they are mapped to.
+#[cfg(all(
+    feature = "acl",
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "macos",
+        windows
+    )
+))]
+pub(crate) fn map_acl_owners(
+    mut acl: crate::chunk::Acl,
+    owner_options: &OwnerOptions,
+) -> crate::chunk::Acl {
+    use crate::chunk::OwnerType;
+    for ace in &mut acl.entries {
+        let (map, identifier) = match &mut ace.owner_type {
+            OwnerType::User(identifier) => (&owner_options.owner_map, identifier),
+            OwnerType::Group(identifier) => (&owner_options.group_map, identifier),
+            _ => continue,
+        };
+        if let Some(mapped) = map.map_qualifier(&identifier.0) {
+            identifier.0 = mapped;
+        }
+    }
+    acl
+}
+
 #[cfg_attr(target_os = "wasi", allow(unused_variables))]
 pub(crate) fn apply_metadata<'p>(
     mut entry: EntryBuilder,
@@ -282,7 +557,8 @@ pub(crate) fn apply_metadata<'p>(
                 meta.created().ok(),
                 time_options.ctime,
                 time_options.clamp_ctime,
-            );
+            )
+            .filter(|_| !time_options.mtime_only);
             if let Some(c) = ctime {
                 if let Ok(created_since_unix_epoch) = c.duration_since(UNIX_EPOCH) {
                     entry.created(created_since_unix_epoch);
@@ -302,7 +578,8 @@ pub(crate) fn apply_metadata<'p>(
                 meta.accessed().ok(),
                 time_options.atime,
                 time_options.clamp_atime,
-            );
+            )
+            .filter(|_| !time_options.mtime_only);
             if let Some(a) = atime {
                 if let Ok(accessed_since_unix_epoch) = a.duration_since(UNIX_EPOCH) {
                     entry.accessed(accessed_since_unix_epoch);
@@ -315,27 +592,21 @@ pub(crate) fn apply_metadata<'p>(
             use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
             let mode = meta.permissions().mode() as u16;
-            let uid = owner_options.uid.unwrap_or(meta.uid());
-            let gid = owner_options.gid.unwrap_or(meta.gid());
-            entry.permission(pna::Permission::new(
-                uid.into(),
-                match owner_options.uname.as_deref() {
-                    None => User::from_uid(uid.into())?
-                        .name()
-                        .unwrap_or_default()
-                        .into(),
-                    Some(uname) => uname.into(),
-                },
-                gid.into(),
-                match owner_options.gname.as_deref() {
-                    None => Group::from_gid(gid.into())?
-                        .name()
-                        .unwrap_or_default()
-                        .into(),
-                    Some(gname) => gname.into(),
-                },
-                mode,
-            ));
+            let (uname, uid) = resolve_owner(
+                meta.uid().into(),
+                &owner_options.owner_map,
+                owner_options.uname.as_deref(),
+                owner_options.uid,
+                |uid| Ok(User::from_uid(uid)?.name().unwrap_or_default().into()),
+            )?;
+            let (gname, gid) = resolve_owner(
+                meta.gid().into(),
+                &owner_options.group_map,
+                owner_options.gname.as_deref(),
+                owner_options.gid,
+                |gid| Ok(Group::from_gid(gid)?.name().unwrap_or_default().into()),
+            )?;
+            entry.permission(pna::Permission::new(uid, uname, gid, gname, mode));
         }
         #[cfg(windows)]
         if keep_options.keep_permission {
@@ -346,11 +617,13 @@ pub(crate) fn apply_metadata<'p>(
             let mode = stat.st_mode;
             let user = sd.owner_sid()?;
             let group = sd.group_sid()?;
+            let (uname, uid) = owner_options.owner_map.map(&user.name, u64::MAX);
+            let (gname, gid) = owner_options.group_map.map(&group.name, u64::MAX);
             entry.permission(pna::Permission::new(
-                owner_options.uid.map_or(u64::MAX, Into::into),
-                owner_options.uname.clone().unwrap_or(user.name),
-                owner_options.gid.map_or(u64::MAX, Into::into),
-                owner_options.gname.clone().unwrap_or(group.name),
+                owner_options.uid.map_or(uid, Into::into),
+                owner_options.uname.as_deref().unwrap_or(uname).into(),
+                owner_options.gid.map_or(gid, Into::into),
+                owner_options.gname.as_deref().unwrap_or(gname).into(),
                 mode,
             ));
         }
@@ -366,7 +639,7 @@ pub(crate) fn apply_metadata<'p>(
         if keep_options.keep_acl {
             use crate::chunk;
             use pna::RawChunk;
-            let acl = utils::acl::get_facl(path)?;
+            let acl = map_acl_owners(utils::acl::get_facl(path)?, owner_options);
             entry.add_extra_chunk(RawChunk::from_data(chunk::faCl, acl.platform.to_bytes()));
             for ace in acl.entries {
                 entry.add_extra_chunk(RawChunk::from_data(chunk::faCe, ace.to_bytes()));
@@ -453,7 +726,7 @@ pub(crate) fn split_to_parts(
 pub(crate) trait TransformStrategy {
     fn transform<W, T, F>(
         archive: &mut Archive<W>,
-        password: Option<&str>,
+        passwords: &[&str],
         read_entry: io::Result<ReadEntry<T>>,
         transformer: F,
     ) -> io::Result<()>
@@ -470,7 +743,7 @@ pub(crate) struct TransformStrategyUnSolid;
 impl TransformStrategy for TransformStrategyUnSolid {
     fn transform<W, T, F>(
         archive: &mut Archive<W>,
-        password: Option<&str>,
+        passwords: &[&str],
         read_entry: io::Result<ReadEntry<T>>,
         mut transformer: F,
     ) -> io::Result<()>
@@ -483,6 +756,7 @@ impl TransformStrategy for TransformStrategyUnSolid {
     {
         match read_entry? {
             ReadEntry::Solid(s) => {
+                let password = s.matching_password(passwords)?;
                 for n in s.entries(password)? {
                     if let Some(entry) = transformer(n.map(Into::into))? {
                         archive.add_entry(entry)?;
@@ -505,7 +779,7 @@ pub(crate) struct TransformStrategyKeepSolid;
 impl TransformStrategy for TransformStrategyKeepSolid {
     fn transform<W, T, F>(
         archive: &mut Archive<W>,
-        password: Option<&str>,
+        passwords: &[&str],
         read_entry: io::Result<ReadEntry<T>>,
         mut transformer: F,
     ) -> io::Result<()>
@@ -518,6 +792,7 @@ impl TransformStrategy for TransformStrategyKeepSolid {
     {
         match read_entry? {
             ReadEntry::Solid(s) => {
+                let password = s.matching_password(passwords)?;
                 let header = s.header();
                 let mut builder = SolidEntryBuilder::new(
                     WriteOptions::builder()
@@ -575,18 +850,18 @@ where
     Ok(())
 }
 
-pub(crate) fn run_process_archive<'p, Provider, F>(
+pub(crate) fn run_process_archive<F>(
     archive_provider: impl IntoIterator<Item = impl Read>,
-    mut password_provider: Provider,
+    read_options: &ReadOptions,
     mut processor: F,
 ) -> io::Result<()>
 where
-    Provider: FnMut() -> Option<&'p str>,
     F: FnMut(io::Result<NormalEntry>) -> io::Result<()>,
 {
-    let password = password_provider();
     run_read_entries(archive_provider, |entry| match entry? {
-        ReadEntry::Solid(solid) => solid.entries(password)?.try_for_each(&mut processor),
+        ReadEntry::Solid(solid) => solid
+            .entries_with_options(read_options)?
+            .try_for_each(&mut processor),
         ReadEntry::Normal(regular) => processor(Ok(regular)),
     })
 }
@@ -632,27 +907,43 @@ where
     })
 }
 
+/// Reads the archive once to find the entries `selector` resolves, so that they can be picked
+/// on the next read without keeping the whole archive in memory.
+///
+/// Returns the positions of the selected entries in the order [run_process_archive] yields
+/// them, counting from 0.
+pub(crate) fn select_versions(
+    archive_provider: impl IntoIterator<Item = impl Read>,
+    read_options: &ReadOptions,
+    selector: VersionSelector,
+) -> io::Result<HashSet<usize>> {
+    let mut plan = VersionPlan::new(selector);
+    run_process_archive(archive_provider, read_options, |entry| {
+        plan.push(&entry?);
+        Ok(())
+    })?;
+    Ok(plan.selected())
+}
+
 #[cfg(feature = "memmap")]
-pub(crate) fn run_entries<'d, 'p, Provider, F>(
+pub(crate) fn run_entries<'d, F>(
     archives: impl IntoIterator<Item = &'d [u8]>,
-    mut password_provider: Provider,
+    read_options: &ReadOptions,
     mut processor: F,
 ) -> io::Result<()>
 where
-    Provider: FnMut() -> Option<&'p str>,
     F: FnMut(io::Result<NormalEntry<Cow<'d, [u8]>>>) -> io::Result<()>,
 {
-    let password = password_provider();
     run_read_entries_mem(archives, |entry| match entry? {
         ReadEntry::Solid(s) => s
-            .entries(password)?
+            .entries_with_options(read_options)?
             .try_for_each(|r| processor(r.map(Into::into))),
         ReadEntry::Normal(r) => processor(Ok(r)),
     })
 }
 
 #[cfg(feature = "memmap")]
-pub(crate) fn run_transform_entry<'d, 'p, W, Provider, F, Transform>(
+pub(crate) fn run_transform_entry<'d, 'p, W, Provider, Passwords, F, Transform>(
     writer: W,
     archives: impl IntoIterator<Item = &'d [u8]>,
     mut password_provider: Provider,
@@ -661,16 +952,17 @@ pub(crate) fn run_transform_entry<'d, 'p, W, Provider, F, Transform>(
 ) -> anyhow::Result<()>
 where
     W: Write,
-    Provider: FnMut() -> Option<&'p str>,
+    Provider: FnMut() -> Passwords,
+    Passwords: IntoIterator<Item = &'p str>,
     F: FnMut(
         io::Result<NormalEntry<Cow<'d, [u8]>>>,
     ) -> io::Result<Option<NormalEntry<Cow<'d, [u8]>>>>,
     Transform: TransformStrategy,
 {
-    let password = password_provider();
+    let passwords = password_provider().into_iter().collect::<Vec<_>>();
     let mut out_archive = Archive::write_header(writer)?;
     run_read_entries_mem(archives, |entry| {
-        Transform::transform(&mut out_archive, password, entry, &mut processor)
+        Transform::transform(&mut out_archive, &passwords, entry, &mut processor)
     })?;
     out_archive.finalize()?;
     Ok(())
@@ -689,7 +981,7 @@ where
 }
 
 #[cfg(not(feature = "memmap"))]
-pub(crate) fn run_transform_entry<'p, W, Provider, F, Transform>(
+pub(crate) fn run_transform_entry<'p, W, Provider, Passwords, F, Transform>(
     writer: W,
     archives: impl IntoIterator<Item = impl Read>,
     mut password_provider: Provider,
@@ -698,30 +990,30 @@ pub(crate) fn run_transform_entry<'p, W, Provider, F, Transform>(
 ) -> anyhow::Result<()>
 where
     W: Write,
-    Provider: FnMut() -> Option<&'p str>,
+    Provider: FnMut() -> Passwords,
+    Passwords: IntoIterator<Item = &'p str>,
     F: FnMut(io::Result<NormalEntry>) -> io::Result<Option<NormalEntry>>,
     Transform: TransformStrategy,
 {
-    let password = password_provider();
+    let passwords = password_provider().into_iter().collect::<Vec<_>>();
     let mut out_archive = Archive::write_header(writer)?;
     run_read_entries(archives, |entry| {
-        Transform::transform(&mut out_archive, password, entry, &mut processor)
+        Transform::transform(&mut out_archive, &passwords, entry, &mut processor)
     })?;
     out_archive.finalize()?;
     Ok(())
 }
 
 #[cfg(not(feature = "memmap"))]
-pub(crate) fn run_entries<'p, Provider, F>(
+pub(crate) fn run_entries<F>(
     archives: Vec<fs::File>,
-    password_provider: Provider,
+    read_options: &ReadOptions,
     processor: F,
 ) -> io::Result<()>
 where
-    Provider: FnMut() -> Option<&'p str>,
     F: FnMut(io::Result<NormalEntry>) -> io::Result<()>,
 {
-    run_process_archive(archives, password_provider, processor)
+    run_process_archive(archives, read_options, processor)
 }
 
 pub(crate) fn write_split_archive(
@@ -729,6 +1021,7 @@ pub(crate) fn write_split_archive(
     entries: impl Iterator<Item = io::Result<impl Entry + Sized>>,
     max_file_size: usize,
     overwrite: bool,
+    progress: Option<Arc<dyn ProgressObserver>>,
 ) -> anyhow::Result<()> {
     write_split_archive_path(
         archive,
@@ -736,6 +1029,7 @@ pub(crate) fn write_split_archive(
         |base, n| base.with_part(n).unwrap(),
         max_file_size,
         overwrite,
+        progress,
     )
 }
 
@@ -745,6 +1039,7 @@ pub(crate) fn write_split_archive_path<F, P>(
     mut get_part_path: F,
     max_file_size: usize,
     overwrite: bool,
+    progress: Option<Arc<dyn ProgressObserver>>,
 ) -> anyhow::Result<()>
 where
     F: FnMut(&Path, usize) -> P,
@@ -765,29 +1060,67 @@ where
             };
             Ok(())
         },
+        progress,
     )
 }
 
+/// Size of the header at the start of every archive part (PNA_HEADER + AHED).
+pub(crate) const ARCHIVE_HEADER_SIZE: usize = PNA_HEADER.len() + MIN_CHUNK_BYTES_SIZE + 8;
+
 pub(crate) fn write_split_archive_writer<W, F, C>(
     initial_writer: W,
     entries: impl Iterator<Item = io::Result<impl Entry + Sized>>,
-    mut get_next_writer: F,
+    get_next_writer: F,
     max_file_size: usize,
     mut on_complete: C,
+    progress: Option<Arc<dyn ProgressObserver>>,
 ) -> anyhow::Result<()>
 where
     W: Write,
     F: FnMut(usize) -> io::Result<W>,
     C: FnMut(usize) -> io::Result<()>,
 {
-    let mut part_num = 1;
     let mut writer = Archive::write_header(initial_writer)?;
+    writer.set_progress_observer(progress);
+    let part_num = continue_split_archive_writer(
+        writer,
+        1,
+        0,
+        entries.map(|it| it.map(|entry| ((), entry))),
+        get_next_writer,
+        max_file_size,
+        |_, _, _| Ok(()),
+    )?;
+    on_complete(part_num)?;
+    Ok(())
+}
 
+/// Writes `entries` to a split archive whose part `part_num` already contains
+/// `written_entry_size` bytes of entries after its header, then finalizes it.
+///
+/// `on_entry` is called with the key of each entry once it is completely written, along
+/// with the number of the part it ends in and the byte offset of its end in that part.
+/// Returns the number of the last part.
+pub(crate) fn continue_split_archive_writer<W, F, K, E, O>(
+    mut writer: Archive<W>,
+    mut part_num: usize,
+    mut written_entry_size: usize,
+    entries: impl Iterator<Item = io::Result<(K, E)>>,
+    mut get_next_writer: F,
+    max_file_size: usize,
+    mut on_entry: O,
+) -> anyhow::Result<usize>
+where
+    W: Write,
+    F: FnMut(usize) -> io::Result<W>,
+    E: Entry + Sized,
+    O: FnMut(K, usize, usize) -> io::Result<()>,
+{
     // NOTE: max_file_size - (PNA_HEADER + AHED + ANXT + AEND)
-    let max_file_size = max_file_size - (PNA_HEADER.len() + MIN_CHUNK_BYTES_SIZE * 3 + 8);
-    let mut written_entry_size = 0;
+    let max_file_size = max_file_size - (ARCHIVE_HEADER_SIZE + MIN_CHUNK_BYTES_SIZE * 2);
     for entry in entries {
-        let p = EntryPart::from(entry?);
+        let (key, entry) = entry?;
+        let p = EntryPart::from(entry);
         let parts = split_to_parts(
             p.as_ref(),
             max_file_size - written_entry_size,
@@ -802,13 +1135,13 @@ where
             }
             written_entry_size += writer.add_entry_part(part)?;
         }
+        on_entry(key, part_num, ARCHIVE_HEADER_SIZE + written_entry_size)?;
     }
     writer.finalize()?;
-    on_complete(part_num)?;
-    Ok(())
+    Ok(part_num)
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub(crate) struct Exclude {
     pub(crate) include: BsdGlobPatterns,
     pub(crate) exclude: BsdGlobPatterns,
@@ -822,6 +1155,64 @@ impl Exclude {
     }
 }
 
+/// Returns whether `entry` is chosen by the path `globs` and the `--where` `filter`. With a
+/// filter but no paths, every entry matching the filter is chosen.
+#[inline]
+pub(crate) fn is_selected<T>(
+    entry: &NormalEntry<T>,
+    globs: &GlobPatterns,
+    filter: Option<&EntryFilter>,
+) -> bool {
+    let path = entry.header().path();
+    match filter {
+        Some(filter) => (globs.is_empty() || globs.matches_any(path)) && filter.matches(entry),
+        None => globs.matches_any(path),
+    }
+}
+
+/// Action that a command given `--dry-run` reports instead of taking it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub(crate) enum PlannedAction {
+    Add,
+    Replace,
+    Skip,
+    Delete,
+    Overwrite,
+    Backup,
+}
+
+impl PlannedAction {
+    /// Prints the action on `target`, an entry name or a path, to stdout.
+    #[inline]
+    pub(crate) fn print(self, target: impl Display) {
+        println!("{self} {target}");
+    }
+}
+
+impl Display for PlannedAction {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Add => "add",
+            Self::Replace => "replace",
+            Self::Skip => "skip",
+            Self::Delete => "delete",
+            Self::Overwrite => "overwrite",
+            Self::Backup => "backup",
+        })
+    }
+}
+
+/// Returns the writer of an output archive, which is discarded when no temporary file is
+/// created for it because of `--dry-run`.
+#[inline]
+pub(crate) fn output_writer(temp_file: &mut Option<NamedTempFile>) -> Box<dyn Write + Send + '_> {
+    match temp_file {
+        Some(temp_file) => Box::new(temp_file.as_file_mut()),
+        None => Box::new(io::sink()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -874,7 +1265,16 @@ mod tests {
             env!("CARGO_MANIFEST_DIR"),
             "/../resources/test/raw",
         )];
-        let items = collect_items(source, false, false, false, false, empty_exclude()).unwrap();
+        let items = collect_items(
+            source,
+            false,
+            false,
+            false,
+            false,
+            empty_exclude(),
+            &ExclusionArgs::default(),
+        )
+        .unwrap();
         assert_eq!(items.into_iter().collect::<HashSet<_>>(), HashSet::new());
     }
 
@@ -884,7 +1284,16 @@ mod tests {
             env!("CARGO_MANIFEST_DIR"),
             "/../resources/test/raw",
         )];
-        let items = collect_items(source, false, true, false, false, empty_exclude()).unwrap();
+        let items = collect_items(
+            source,
+            false,
+            true,
+            false,
+            false,
+            empty_exclude(),
+            &ExclusionArgs::default(),
+        )
+        .unwrap();
         assert_eq!(
             items.into_iter().collect::<HashSet<_>>(),
             [concat!(
@@ -903,7 +1312,16 @@ mod tests {
             env!("CARGO_MANIFEST_DIR"),
             "/../resources/test/raw",
         )];
-        let items = collect_items(source, true, false, false, false, empty_exclude()).unwrap();
+        let items = collect_items(
+            source,
+            true,
+            false,
+            false,
+            false,
+            empty_exclude(),
+            &ExclusionArgs::default(),
+        )
+        .unwrap();
         assert_eq!(
             items.into_iter().collect::<HashSet<_>>(),
             [
@@ -0,0 +1,125 @@
+use crate::{
+    cli::{PasswordArgs, SolidEntriesTransformStrategy, SolidEntriesTransformStrategyArgs},
+    command::{
+        ask_passwords,
+        commons::{
+            collect_split_archives, run_entries, run_transform_entry, TransformStrategyKeepSolid,
+            TransformStrategyUnSolid,
+        },
+        Command,
+    },
+    utils::{env::NamedTempFile, PathPartExt},
+};
+use clap::{Parser, ValueHint};
+use pna::{DataKind, EntryName, NormalEntry, ReadOptions};
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Parser, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct CompactCommand {
+    #[arg(
+        long,
+        help = "Also remove the paths whose last entry is a deletion marker"
+    )]
+    pub(crate) drop_deleted: bool,
+    #[command(flatten)]
+    transform_strategy: SolidEntriesTransformStrategyArgs,
+    #[arg(long, help = "Output file path", value_hint = ValueHint::AnyPath)]
+    pub(crate) output: Option<PathBuf>,
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub(crate) archive: PathBuf,
+}
+
+impl Command for CompactCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        compact_archive(self)
+    }
+}
+
+struct Compactor {
+    /// Position of the last entry of each path, counting the entries in solid entries.
+    last: HashMap<EntryName, usize>,
+    drop_deleted: bool,
+    index: usize,
+    removed: usize,
+}
+
+impl Compactor {
+    fn retain<T>(&mut self, entry: NormalEntry<T>) -> Option<NormalEntry<T>> {
+        let index = self.index;
+        self.index += 1;
+        let is_last = self.last.get(entry.header().path()) == Some(&index);
+        let is_deleted = entry.header().data_kind() == DataKind::Whiteout;
+        if is_last && !(self.drop_deleted && is_deleted) {
+            Some(entry)
+        } else {
+            log::debug!("Remove: {}", entry.header().path());
+            self.removed += 1;
+            None
+        }
+    }
+}
+
+fn compact_archive(args: CompactCommand) -> anyhow::Result<()> {
+    let passwords = ask_passwords(args.password)?;
+    let archives = collect_split_archives(&args.archive)?;
+
+    #[cfg(feature = "memmap")]
+    let mmaps = archives
+        .into_iter()
+        .map(crate::utils::mmap::Mmap::try_from)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    #[cfg(feature = "memmap")]
+    let archives = mmaps.iter().map(|m| m.as_ref());
+
+    #[cfg(feature = "memmap")]
+    let scan = archives.clone();
+    #[cfg(not(feature = "memmap"))]
+    let (scan, archives) = (archives, collect_split_archives(&args.archive)?);
+
+    let mut last = HashMap::new();
+    let mut index = 0;
+    run_entries(scan, &ReadOptions::with_passwords(&passwords), |entry| {
+        last.insert(entry?.header().path().clone(), index);
+        index += 1;
+        Ok(())
+    })?;
+
+    let output_path = args
+        .output
+        .unwrap_or_else(|| args.archive.remove_part().unwrap());
+    let mut temp_file =
+        NamedTempFile::new(|| output_path.parent().unwrap_or_else(|| ".".as_ref()))?;
+
+    let mut compactor = Compactor {
+        last,
+        drop_deleted: args.drop_deleted,
+        index: 0,
+        removed: 0,
+    };
+    match args.transform_strategy.strategy() {
+        SolidEntriesTransformStrategy::UnSolid => run_transform_entry(
+            temp_file.as_file_mut(),
+            archives,
+            || passwords.iter().map(String::as_str),
+            |entry| Ok(compactor.retain(entry?)),
+            TransformStrategyUnSolid,
+        ),
+        SolidEntriesTransformStrategy::KeepSolid => run_transform_entry(
+            temp_file.as_file_mut(),
+            archives,
+            || passwords.iter().map(String::as_str),
+            |entry| Ok(compactor.retain(entry?)),
+            TransformStrategyKeepSolid,
+        ),
+    }?;
+
+    #[cfg(feature = "memmap")]
+    drop(mmaps);
+
+    temp_file.persist(output_path)?;
+    log::info!("Removed {} entries", compactor.removed);
+    Ok(())
+}
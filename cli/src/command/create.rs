@@ -1,30 +1,41 @@
 use crate::{
     cli::{
-        CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, FileArgs, HashAlgorithmArgs,
-        PasswordArgs,
+        AtimePreserve, CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, ExclusionArgs,
+        FileArgs, HashAlgorithmArgs, PasswordArgs, ProgressArgs,
     },
     command::{
         ask_password, check_password,
+        checkpoint::{Checkpoint, Checkpointer},
+        commons::ARCHIVE_HEADER_SIZE,
         commons::{
-            collect_items, create_entry, entry_option, write_split_archive, CreateOptions, Exclude,
-            KeepOptions, OwnerOptions, PathTransformers, TimeOptions,
+            collect_items, continue_split_archive_writer, create_entry, create_whiteout,
+            entry_option, to_entry_name, write_split_archive, CreateOptions, Exclude, HardLinks,
+            KeepOptions, OwnerOptions, PathTransformers, PlannedAction, TimeOptions,
         },
+        progress::{observer, ProgressReporter},
+        snapshot::Snapshot,
         Command,
     },
     utils::{
         self,
         fmt::DurationDisplay,
+        fs::IdMap,
         re::{bsd::SubstitutionRule, gnu::TransformRule},
+        PathPartExt,
     },
 };
 use bytesize::ByteSize;
 use clap::{ArgGroup, Parser, ValueHint};
-use pna::{Archive, SolidEntryBuilder, WriteOptions};
+use pna::{Archive, NormalEntry, ProgressObserver, SolidEntryBuilder, WriteOptions};
+use sha2::{Digest, Sha256};
 use std::{
+    collections::VecDeque,
     env, fs,
-    io::{self, prelude::*},
+    io::{self, prelude::*, SeekFrom},
+    iter,
     path::{Path, PathBuf},
-    time::Instant,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Parser, Clone, Debug)]
@@ -36,8 +47,15 @@ use std::{
     group(ArgGroup::new("unstable-files-from-stdin").args(["files_from_stdin"]).requires("unstable")),
     group(ArgGroup::new("unstable-exclude-from").args(["exclude_from"]).requires("unstable")),
     group(ArgGroup::new("unstable-gitignore").args(["gitignore"]).requires("unstable")),
+    group(ArgGroup::new("unstable-incremental").args(["incremental"]).requires("unstable")),
+    group(ArgGroup::new("unstable-checkpoint").args(["checkpoint", "resume"]).multiple(true).requires("unstable")),
     group(ArgGroup::new("unstable-substitution").args(["substitutions"]).requires("unstable")),
     group(ArgGroup::new("unstable-transform").args(["transforms"]).requires("unstable")),
+    group(ArgGroup::new("unstable-reproducible").args(["reproducible", "random_seed"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-id-map").args(["owner_map", "group_map"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-atime-preserve").args(["atime_preserve"]).requires("unstable")),
+    group(ArgGroup::new("unstable-dry-run").args(["dry_run"]).requires("unstable")),
+    group(ArgGroup::new("unstable-hard-dereference").args(["hard_dereference"]).requires("unstable")),
     group(ArgGroup::new("path-transform").args(["substitutions", "transforms"])),
     group(ArgGroup::new("read-files-from").args(["files_from", "files_from_stdin"])),
     group(ArgGroup::new("store-uname").args(["uname"]).requires("keep_permission")),
@@ -70,6 +88,12 @@ pub(crate) struct CreateCommand {
     no_recursive: bool,
     #[arg(long, help = "Overwrite file")]
     pub(crate) overwrite: bool,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive (unstable)"
+    )]
+    pub(crate) dry_run: bool,
     #[arg(long, help = "Archiving the directories")]
     pub(crate) keep_dir: bool,
     #[arg(
@@ -123,6 +147,20 @@ pub(crate) struct CreateCommand {
         help = "This is equivalent to --uname \"\" --gname \"\". It causes user and group names to not be stored in the archive"
     )]
     pub(crate) numeric_owner: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map user names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) owner_map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map group names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) group_map: Option<PathBuf>,
     #[arg(long, help = "Overrides the creation time read from disk")]
     ctime: Option<DateTime>,
     #[arg(
@@ -159,8 +197,55 @@ pub(crate) struct CreateCommand {
     pub(crate) exclude_from: Option<String>,
     #[arg(long, help = "Ignore files from .gitignore (unstable)")]
     pub(crate) gitignore: bool,
+    #[command(flatten)]
+    pub(crate) exclusion: ExclusionArgs,
     #[arg(long, help = "Follow symbolic links")]
     pub(crate) follow_links: bool,
+    #[arg(
+        long,
+        help = "Archive hard-linked files as separate copies instead of hard link entries (unstable)"
+    )]
+    pub(crate) hard_dereference: bool,
+    #[arg(
+        long,
+        value_name = "METHOD",
+        require_equals = true,
+        help = "Preserve the access times of the files being archived, by restoring them after reading (replace) or by reading without updating them where the system supports it (system) [default: replace] (unstable)"
+    )]
+    pub(crate) atime_preserve: Option<Option<AtimePreserve>>,
+    #[arg(
+        long,
+        help = "Create the same archive from the same files: sort the entries by name, clamp the modification times to SOURCE_DATE_EPOCH if it is set and --mtime is not given, store the owners as root (0) unless given, and drop the access and creation times. Encrypted archives also require --random-seed (unstable)",
+        conflicts_with_all = ["ctime", "clamp_ctime", "atime", "clamp_atime"]
+    )]
+    pub(crate) reproducible: bool,
+    #[arg(
+        long,
+        value_name = "SEED",
+        help = "Derive the salts and initialization vectors of encrypted entries from the given seed instead of generating them randomly. Do not reuse a seed for different contents (unstable)",
+        requires = "reproducible"
+    )]
+    pub(crate) random_seed: Option<String>,
+    #[arg(
+        long,
+        value_name = "SNAPSHOT",
+        help = "Archive only files changed since the state recorded in the snapshot file and record deleted files as deletion markers, then update the snapshot. Extract the archives of the chain in order to restore the final tree (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) incremental: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Record the progress to <ARCHIVE>.checkpoint at most every given seconds [default: 60], so that an interrupted creation can be continued with --resume (unstable)",
+        conflicts_with = "solid"
+    )]
+    pub(crate) checkpoint: Option<Option<u64>>,
+    #[arg(
+        long,
+        help = "Continue an interrupted creation from its checkpoint, skipping the files that are already archived (unstable)",
+        conflicts_with = "solid"
+    )]
+    pub(crate) resume: bool,
     #[arg(
         short = 's',
         value_name = "PATTERN",
@@ -192,6 +277,8 @@ pub(crate) struct CreateCommand {
     #[command(flatten)]
     pub(crate) password: PasswordArgs,
     #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
+    #[command(flatten)]
     pub(crate) file: FileArgs,
 }
 
@@ -206,9 +293,16 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
     let current_dir = env::current_dir()?;
     let password = ask_password(args.password)?;
     check_password(&password, &args.cipher);
+    if args.reproducible && password.is_some() && args.random_seed.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--reproducible requires --random-seed to create an encrypted archive",
+        )
+        .into());
+    }
     let start = Instant::now();
     let archive = &args.file.archive;
-    if !args.overwrite && archive.exists() {
+    if !args.overwrite && !args.resume && archive.exists() {
         return Err(io::Error::new(
             io::ErrorKind::AlreadyExists,
             format!("{} already exists", archive.display()),
@@ -233,17 +327,63 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
         }
     };
     let archive_path = current_dir.join(archive);
+    let snapshot_path = args.incremental.map(|it| current_dir.join(it));
+    let checkpoint_path =
+        (args.checkpoint.is_some() || args.resume).then(|| Checkpoint::path_for(&archive_path));
+    let resume = if args.resume {
+        let path = checkpoint_path.as_deref().expect("checkpoint path");
+        Some(Checkpoint::load(path, args.split.is_some())?)
+    } else {
+        None
+    };
     if let Some(working_dir) = args.working_dir {
         env::set_current_dir(working_dir)?;
     }
-    let target_items = collect_items(
+    let mut target_items = collect_items(
         &files,
         !args.no_recursive,
         args.keep_dir,
         args.gitignore,
         args.follow_links,
         exclude,
+        &args.exclusion,
     )?;
+    if args.reproducible {
+        target_items.sort();
+    }
+    let (snapshot, whiteouts) = match &snapshot_path {
+        Some(snapshot_path) => {
+            let previous = Snapshot::load(snapshot_path)?;
+            let current = Snapshot::capture(&target_items, args.follow_links)?;
+            target_items.retain(|it| previous.is_changed(it, &current));
+            let removed = previous.removed(&current);
+            (Some(current), removed)
+        }
+        None => (None, Vec::new()),
+    };
+    let hard_links = HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?;
+    let whiteouts = if let Some(resume) = &resume {
+        target_items.retain(|it| !resume.is_done(it));
+        whiteouts
+            .into_iter()
+            .filter(|it| !resume.is_done(it))
+            .collect()
+    } else {
+        whiteouts
+    };
+    let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
+    if args.dry_run {
+        if !args.resume && archive_path.exists() {
+            PlannedAction::Overwrite.print(archive.display());
+        }
+        for item in &target_items {
+            PlannedAction::Add.print(to_entry_name(item, &path_transformers));
+        }
+        for item in &whiteouts {
+            PlannedAction::Delete.print(to_entry_name(item, &path_transformers));
+        }
+        return Ok(());
+    }
 
     if let Some(parent) = archive_path.parent() {
         fs::create_dir_all(parent)?;
@@ -258,24 +398,44 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
         keep_xattr: args.keep_xattr,
         keep_acl: args.keep_acl,
     };
+    let root = |name: Option<String>| name.or_else(|| args.reproducible.then(|| "root".into()));
+    let root_id = |id: Option<u32>| id.or(args.reproducible.then_some(0));
     let owner_options = OwnerOptions::new(
-        args.uname,
-        args.gname,
-        args.uid,
-        args.gid,
+        root(args.uname),
+        root(args.gname),
+        root_id(args.uid),
+        root_id(args.gid),
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
-    let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
+    let (mtime, clamp_mtime) = match args.mtime {
+        Some(mtime) => (Some(mtime.to_system_time()), args.clamp_mtime),
+        None if args.reproducible => (source_date_epoch()?, true),
+        None => (None, false),
+    };
     let time_options = TimeOptions {
-        mtime: args.mtime.map(|it| it.to_system_time()),
-        clamp_mtime: args.clamp_mtime,
+        mtime,
+        clamp_mtime,
         ctime: args.ctime.map(|it| it.to_system_time()),
         clamp_ctime: args.clamp_ctime,
         atime: args.atime.map(|it| it.to_system_time()),
         clamp_atime: args.clamp_atime,
+        mtime_only: args.reproducible,
     };
     let password = password.as_deref();
-    let write_option = entry_option(args.compression, args.cipher, args.hash, password);
+    let mut write_option = entry_option(args.compression, args.cipher, args.hash, password);
+    if let Some(seed) = args.random_seed {
+        write_option = write_option
+            .into_builder()
+            .random_seed(Some(Sha256::digest(seed).into()))
+            .build();
+    }
+    let progress = ProgressReporter::new(
+        args.progress,
+        Some((target_items.len() + whiteouts.len()) as u64),
+        None,
+    );
     let creation_context = CreationContext {
         write_option,
         keep_options,
@@ -283,9 +443,27 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
         time_options,
         solid: args.solid,
         follow_links: args.follow_links,
+        hard_links,
+        atime_preserve: args.atime_preserve.map(Option::unwrap_or_default),
         path_transformers,
+        whiteouts,
+        progress: progress.clone(),
     };
-    if let Some(size) = max_file_size {
+    if let Some(checkpoint_path) = checkpoint_path {
+        let interval = Duration::from_secs(args.checkpoint.flatten().unwrap_or(60));
+        let checkpointer = match resume {
+            Some(resume) => Checkpointer::resume(checkpoint_path, interval, resume),
+            None => Checkpointer::new(checkpoint_path, interval, max_file_size.is_some()),
+        };
+        create_archive_with_checkpoint(
+            &archive_path,
+            creation_context,
+            target_items,
+            max_file_size,
+            args.overwrite || args.resume,
+            checkpointer,
+        )?;
+    } else if let Some(size) = max_file_size {
         create_archive_with_split(
             &archive_path,
             creation_context,
@@ -300,6 +478,12 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
             target_items,
         )?;
     }
+    if let Some(progress) = progress {
+        progress.finish("written");
+    }
+    if let (Some(snapshot), Some(snapshot_path)) = (snapshot, snapshot_path) {
+        snapshot.save(&snapshot_path)?;
+    }
     log::info!(
         "Successfully created an archive in {}",
         DurationDisplay(start.elapsed())
@@ -307,6 +491,23 @@ fn create_archive(args: CreateCommand) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Reads the `SOURCE_DATE_EPOCH` environment variable used by reproducible builds.
+fn source_date_epoch() -> io::Result<Option<SystemTime>> {
+    let Some(epoch) = env::var_os("SOURCE_DATE_EPOCH") else {
+        return Ok(None);
+    };
+    let secs = epoch
+        .to_str()
+        .and_then(|it| it.trim().parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid SOURCE_DATE_EPOCH: {epoch:?}"),
+            )
+        })?;
+    Ok(Some(UNIX_EPOCH + Duration::from_secs(secs)))
+}
+
 pub(crate) struct CreationContext {
     pub(crate) write_option: WriteOptions,
     pub(crate) keep_options: KeepOptions,
@@ -314,7 +515,66 @@ pub(crate) struct CreationContext {
     pub(crate) time_options: TimeOptions,
     pub(crate) solid: bool,
     pub(crate) follow_links: bool,
+    pub(crate) hard_links: HardLinks,
+    pub(crate) atime_preserve: Option<AtimePreserve>,
     pub(crate) path_transformers: Option<PathTransformers>,
+    /// Paths removed since the previous incremental backup, archived as deletion markers.
+    pub(crate) whiteouts: Vec<PathBuf>,
+    pub(crate) progress: Option<Arc<ProgressReporter>>,
+}
+
+/// Creates the entries of `target_items` in parallel, followed by the deletion markers of
+/// `whiteouts`, and passes them to `f` in the order of the given paths as they are created.
+///
+/// Only a few entries are created ahead of the one `f` is waiting for, so each entry can be
+/// written, and recorded to a checkpoint, before the rest of the files are read.
+fn with_entries<R>(
+    target_items: Vec<PathBuf>,
+    whiteouts: Vec<PathBuf>,
+    create_options: &CreateOptions,
+    path_transformers: &Option<PathTransformers>,
+    f: impl FnOnce(&mut dyn Iterator<Item = (PathBuf, io::Result<NormalEntry>)>) -> R,
+) -> R {
+    rayon::in_place_scope_fifo(|s| {
+        let lookahead = rayon::current_num_threads() * 2;
+        let mut target_items = target_items.into_iter();
+        let mut pending = VecDeque::with_capacity(lookahead);
+        let spawn = |file: PathBuf| {
+            let (tx, rx) = std::sync::mpsc::sync_channel(1);
+            s.spawn_fifo(move |_| {
+                log::debug!("Adding: {}", file.display());
+                let entry = create_entry(&file, create_options, path_transformers);
+                // The receiver is gone if writing the archive failed.
+                let _ = tx.send((file, entry));
+            });
+            rx
+        };
+        let mut entries = iter::from_fn(|| {
+            let free = lookahead - pending.len();
+            pending.extend(target_items.by_ref().take(free).map(spawn));
+            pending
+                .pop_front()
+                .map(|rx| rx.recv().expect("failed to create an entry"))
+        })
+        .chain(whiteouts.into_iter().map(|path| {
+            log::debug!("Removed: {}", path.display());
+            let entry = create_whiteout(&path, &create_options.time_options, path_transformers);
+            (path, entry)
+        }));
+        f(&mut entries)
+    })
+}
+
+/// Counts the entries given to [`continue_split_archive_writer`], which reports only the
+/// bytes of the parts it writes.
+fn count_entry(
+    progress: &Option<Arc<ProgressReporter>>,
+) -> impl Fn(&(PathBuf, io::Result<NormalEntry>)) + '_ {
+    move |(_, entry)| {
+        if let (Some(progress), Ok(entry)) = (progress, entry) {
+            progress.entry_finished(entry.header().path());
+        }
+    }
 }
 
 pub(crate) fn create_archive_file<W, F>(
@@ -326,7 +586,11 @@ pub(crate) fn create_archive_file<W, F>(
         time_options,
         solid,
         follow_links,
+        hard_links,
+        atime_preserve,
         path_transformers,
+        whiteouts,
+        progress,
     }: CreationContext,
     target_items: Vec<PathBuf>,
 ) -> anyhow::Result<()>
@@ -334,7 +598,6 @@ where
     W: Write,
     F: FnMut() -> io::Result<W> + Send,
 {
-    let (tx, rx) = std::sync::mpsc::channel();
     let option = if solid {
         WriteOptions::store()
     } else {
@@ -346,37 +609,34 @@ where
         owner_options,
         time_options,
         follow_links,
+        hard_links,
+        atime_preserve,
     };
-    rayon::scope_fifo(|s| {
-        for file in target_items {
-            let tx = tx.clone();
-            let create_options = create_options.clone();
-            let path_transformers = path_transformers.clone();
-            s.spawn_fifo(move |_| {
-                log::debug!("Adding: {}", file.display());
-                tx.send(create_entry(&file, &create_options, &path_transformers))
-                    .unwrap_or_else(|e| panic!("{e}: {}", file.display()));
-            })
-        }
-
-        drop(tx);
-    });
-
     let file = get_writer()?;
-    if solid {
-        let mut writer = Archive::write_solid_header(file, write_option)?;
-        for entry in rx.into_iter() {
-            writer.add_entry(entry?)?;
-        }
-        writer.finalize()?;
-    } else {
-        let mut writer = Archive::write_header(file)?;
-        for entry in rx.into_iter() {
-            writer.add_entry(entry?)?;
-        }
-        writer.finalize()?;
-    }
-    Ok(())
+    with_entries(
+        target_items,
+        whiteouts,
+        &create_options,
+        &path_transformers,
+        |entries| {
+            if solid {
+                let mut writer = Archive::write_solid_header(file, write_option)?;
+                writer.set_progress_observer(observer(&progress));
+                for (_, entry) in entries {
+                    writer.add_entry(entry?)?;
+                }
+                writer.finalize()?;
+            } else {
+                let mut writer = Archive::write_header(file)?;
+                writer.set_progress_observer(observer(&progress));
+                for (_, entry) in entries {
+                    writer.add_entry(entry?)?;
+                }
+                writer.finalize()?;
+            }
+            Ok(())
+        },
+    )
 }
 
 fn create_archive_with_split(
@@ -388,13 +648,16 @@ fn create_archive_with_split(
         time_options,
         solid,
         follow_links,
+        hard_links,
+        atime_preserve,
         path_transformers,
+        whiteouts,
+        progress,
     }: CreationContext,
     target_items: Vec<PathBuf>,
     max_file_size: usize,
     overwrite: bool,
 ) -> anyhow::Result<()> {
-    let (tx, rx) = std::sync::mpsc::channel();
     let option = if solid {
         WriteOptions::store()
     } else {
@@ -406,31 +669,133 @@ fn create_archive_with_split(
         owner_options,
         time_options,
         follow_links,
+        hard_links,
+        atime_preserve,
     };
-    rayon::scope_fifo(|s| -> anyhow::Result<()> {
-        for file in target_items {
-            let tx = tx.clone();
-            let create_options = create_options.clone();
-            let path_transformers = path_transformers.clone();
-            s.spawn_fifo(move |_| {
-                log::debug!("Adding: {}", file.display());
-                tx.send(create_entry(&file, &create_options, &path_transformers))
-                    .unwrap_or_else(|e| panic!("{e}: {}", file.display()));
-            })
-        }
+    with_entries(
+        target_items,
+        whiteouts,
+        &create_options,
+        &path_transformers,
+        |entries| {
+            if solid {
+                let mut entries_builder = SolidEntryBuilder::new(write_option)?;
+                for (_, entry) in entries.inspect(count_entry(&progress)) {
+                    entries_builder.add_entry(entry?)?;
+                }
+                let entries = entries_builder.build();
+                write_split_archive(
+                    archive,
+                    [entries].into_iter(),
+                    max_file_size,
+                    overwrite,
+                    observer(&progress),
+                )
+            } else {
+                write_split_archive(
+                    archive,
+                    entries
+                        .inspect(count_entry(&progress))
+                        .map(|(_, entry)| entry),
+                    max_file_size,
+                    overwrite,
+                    observer(&progress),
+                )
+            }
+        },
+    )
+}
 
-        drop(tx);
-        Ok(())
-    })?;
-    if solid {
-        let mut entries_builder = SolidEntryBuilder::new(write_option)?;
-        for entry in rx.into_iter() {
-            entries_builder.add_entry(entry?)?;
+/// Writes the archive like [`create_archive_file`] or [`create_archive_with_split`] while
+/// recording the progress to `checkpointer`. If the checkpointer was resumed, the archive
+/// is truncated to the checkpoint and continued from there, and completed parts are left
+/// untouched.
+fn create_archive_with_checkpoint(
+    archive: &Path,
+    CreationContext {
+        write_option,
+        keep_options,
+        owner_options,
+        time_options,
+        solid: _,
+        follow_links,
+        hard_links,
+        atime_preserve,
+        path_transformers,
+        whiteouts,
+        progress,
+    }: CreationContext,
+    target_items: Vec<PathBuf>,
+    max_file_size: Option<usize>,
+    overwrite: bool,
+    mut checkpointer: Checkpointer,
+) -> anyhow::Result<()> {
+    let create_options = CreateOptions {
+        option: write_option,
+        keep_options,
+        owner_options,
+        time_options,
+        follow_links,
+        hard_links,
+        atime_preserve,
+    };
+    let part_path = |n: usize| match max_file_size {
+        Some(_) => archive.with_part(n).unwrap(),
+        None => archive.to_path_buf(),
+    };
+    let (mut writer, part_num, offset) = match checkpointer.resume_point() {
+        Some((part_num, offset)) => {
+            log::info!("Resume from part {part_num} at offset {offset}");
+            let file = fs::File::options()
+                .read(true)
+                .write(true)
+                .open(part_path(part_num))?;
+            file.set_len(offset)?;
+            // `end` shares the file offset with the archive, so seek it past the last
+            // complete entry after reading the header.
+            let mut end = file.try_clone()?;
+            let writer = Archive::read_header(file)?;
+            end.seek(SeekFrom::Start(offset))?;
+            (writer, part_num, offset as usize)
+        }
+        None => {
+            let file = utils::fs::file_create(part_path(1), overwrite)?;
+            (Archive::write_header(file)?, 1, ARCHIVE_HEADER_SIZE)
+        }
+    };
+    writer.set_progress_observer(observer(&progress));
+    checkpointer.save()?;
+    let last_part = with_entries(
+        target_items,
+        whiteouts,
+        &create_options,
+        &path_transformers,
+        |entries| {
+            continue_split_archive_writer(
+                writer,
+                part_num,
+                offset - ARCHIVE_HEADER_SIZE,
+                entries
+                    .inspect(count_entry(&progress))
+                    .map(|(path, entry)| entry.map(|entry| (path, entry))),
+                |n| utils::fs::file_create(part_path(n), overwrite),
+                max_file_size.unwrap_or(usize::MAX),
+                |path: PathBuf, part_num, offset| {
+                    checkpointer.entry_written(&path, part_num, offset as u64)
+                },
+            )
+        },
+    )?;
+    if max_file_size.is_some() {
+        // Parts written after the checkpoint by the interrupted run are no longer referenced.
+        let mut n = last_part + 1;
+        while fs::remove_file(part_path(n)).is_ok() {
+            n += 1;
+        }
+        if last_part == 1 {
+            fs::rename(part_path(1), archive)?;
         }
-        let entries = entries_builder.build();
-        write_split_archive(archive, [entries].into_iter(), max_file_size, overwrite)?;
-    } else {
-        write_split_archive(archive, rx.into_iter(), max_file_size, overwrite)?;
     }
+    checkpointer.finish()?;
     Ok(())
 }
@@ -1,18 +1,20 @@
 use crate::{
     cli::{
-        FileArgs, PasswordArgs, SolidEntriesTransformStrategy, SolidEntriesTransformStrategyArgs,
+        EntryFilter, FileArgs, PasswordArgs, SolidEntriesTransformStrategy,
+        SolidEntriesTransformStrategyArgs,
     },
     command::{
         ask_password,
         commons::{
-            collect_split_archives, run_transform_entry, Exclude, TransformStrategyKeepSolid,
-            TransformStrategyUnSolid,
+            collect_split_archives, is_selected, run_entries, run_transform_entry, Exclude,
+            PlannedAction, TransformStrategyKeepSolid, TransformStrategyUnSolid,
         },
         Command,
     },
     utils::{self, env::NamedTempFile, GlobPatterns, PathPartExt},
 };
 use clap::{ArgGroup, Parser, ValueHint};
+use pna::ReadOptions;
 use std::{io, path::PathBuf};
 
 #[derive(Parser, Clone, Eq, PartialEq, Hash, Debug)]
@@ -22,6 +24,7 @@ use std::{io, path::PathBuf};
     group(ArgGroup::new("unstable-include").args(["include"]).requires("unstable")),
     group(ArgGroup::new("unstable-delete-exclude").args(["exclude"]).requires("unstable")),
     group(ArgGroup::new("unstable-exclude-from").args(["exclude_from"]).requires("unstable")),
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
     group(ArgGroup::new("read-files-from").args(["files_from", "files_from_stdin"])),
 )]
 pub(crate) struct DeleteCommand {
@@ -40,6 +43,18 @@ pub(crate) struct DeleteCommand {
     exclude: Option<Vec<String>>,
     #[arg(long, help = "Read exclude files from given path (unstable)", value_hint = ValueHint::FilePath)]
     exclude_from: Option<PathBuf>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    filter: Option<EntryFilter>,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive"
+    )]
+    dry_run: bool,
     #[command(flatten)]
     pub(crate) password: PasswordArgs,
     #[command(flatten)]
@@ -86,6 +101,24 @@ fn delete_file_from_archive(args: DeleteCommand) -> anyhow::Result<()> {
     #[cfg(feature = "memmap")]
     let archives = mmaps.iter().map(|m| m.as_ref());
 
+    if args.dry_run {
+        run_entries(
+            archives,
+            &ReadOptions::with_password(password.as_deref()),
+            |entry| {
+                let entry = entry?;
+                let entry_path = entry.header().path();
+                if is_selected(&entry, &globs, args.filter.as_ref())
+                    && !exclude.excluded(entry_path)
+                {
+                    PlannedAction::Delete.print(entry_path);
+                }
+                Ok(())
+            },
+        )?;
+        return Ok(());
+    }
+
     let output_path = args
         .output
         .unwrap_or_else(|| args.file.archive.remove_part().unwrap());
@@ -100,7 +133,9 @@ fn delete_file_from_archive(args: DeleteCommand) -> anyhow::Result<()> {
             |entry| {
                 let entry = entry?;
                 let entry_path = entry.header().path();
-                if globs.matches_any(entry_path) && !exclude.excluded(entry_path) {
+                if is_selected(&entry, &globs, args.filter.as_ref())
+                    && !exclude.excluded(entry_path)
+                {
                     return Ok(None);
                 }
                 Ok(Some(entry))
@@ -114,7 +149,9 @@ fn delete_file_from_archive(args: DeleteCommand) -> anyhow::Result<()> {
             |entry| {
                 let entry = entry?;
                 let entry_path = entry.header().path();
-                if globs.matches_any(entry_path) && !exclude.excluded(entry_path) {
+                if is_selected(&entry, &globs, args.filter.as_ref())
+                    && !exclude.excluded(entry_path)
+                {
                     return Ok(None);
                 }
                 Ok(Some(entry))
@@ -0,0 +1,638 @@
+#[cfg(feature = "acl")]
+use crate::{chunk::AcePlatform, ext::*};
+use crate::{
+    cli::{FileArgs, PasswordArgs},
+    command::{
+        ask_passwords,
+        commons::{
+            apply_metadata, collect_split_archives, run_process_archive, run_read_entries,
+            KeepOptions, OwnerOptions, TimeOptions,
+        },
+        extract::output_path,
+        Command,
+    },
+    utils::{fs::IdMap, GlobPatterns},
+};
+use clap::{Parser, ValueEnum, ValueHint};
+use pna::{
+    prelude::*, DataKind, EntryBuilder, ExtendedAttribute, NormalEntry, ReadEntry, ReadOptions,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::{self, Display, Formatter},
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Parser, Clone, Debug)]
+pub(crate) struct DiffCommand {
+    #[arg(
+        long,
+        value_name = "ARCHIVE",
+        help = "Compare with another archive instead of the filesystem",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) against: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "against",
+        help = "Read password of the archive given by --against from specified file. Can be specified multiple times"
+    )]
+    pub(crate) against_password_file: Vec<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with = "against",
+        help = "Compare the contents of files as well"
+    )]
+    pub(crate) content: bool,
+    #[arg(
+        long,
+        conflicts_with = "against",
+        help = "Remove the specified number of leading path elements. Path names with fewer elements will be silently skipped"
+    )]
+    pub(crate) strip_components: Option<usize>,
+    #[arg(long, help = "Output format", value_enum, default_value_t)]
+    pub(crate) format: Format,
+    #[arg(
+        short = 'C',
+        long = "cd",
+        aliases = ["directory"],
+        value_name = "DIRECTORY",
+        conflicts_with = "against",
+        help = "Change directories after opening the archive but before comparing entries",
+        value_hint = ValueHint::DirPath
+    )]
+    pub(crate) working_dir: Option<PathBuf>,
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[command(flatten)]
+    pub(crate) file: FileArgs,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, ValueEnum)]
+#[value(rename_all = "lower")]
+pub(crate) enum Format {
+    #[default]
+    Text,
+    #[value(name = "jsonl")]
+    JsonL,
+}
+
+impl Command for DiffCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        diff_archive(self)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DifferenceKind {
+    Missing,
+    Deleted,
+    Type,
+    Size,
+    Mode,
+    Uid,
+    Gid,
+    Mtime,
+    Xattr,
+    #[cfg_attr(not(feature = "acl"), allow(dead_code))]
+    Acl,
+    Contents,
+    Link,
+}
+
+impl DifferenceKind {
+    #[inline]
+    const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Deleted => "deleted",
+            Self::Type => "type",
+            Self::Size => "size",
+            Self::Mode => "mode",
+            Self::Uid => "uid",
+            Self::Gid => "gid",
+            Self::Mtime => "mtime",
+            Self::Xattr => "xattr",
+            Self::Acl => "acl",
+            Self::Contents => "contents",
+            Self::Link => "link",
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize)]
+struct Difference {
+    path: String,
+    kind: DifferenceKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filesystem: Option<String>,
+}
+
+impl Difference {
+    #[inline]
+    fn new(path: &str, kind: DifferenceKind) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+            archive: None,
+            filesystem: None,
+        }
+    }
+
+    #[inline]
+    fn values(mut self, archive: impl Display, filesystem: impl Display) -> Self {
+        self.archive = Some(archive.to_string());
+        self.filesystem = Some(filesystem.to_string());
+        self
+    }
+}
+
+impl Display for Difference {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            DifferenceKind::Missing => "Warning: Cannot stat: No such file or directory",
+            DifferenceKind::Deleted => "Deleted in archive but exists",
+            DifferenceKind::Type => "File type differs",
+            DifferenceKind::Size => "Size differs",
+            DifferenceKind::Mode => "Mode differs",
+            DifferenceKind::Uid => "Uid differs",
+            DifferenceKind::Gid => "Gid differs",
+            DifferenceKind::Mtime => "Mod time differs",
+            DifferenceKind::Xattr => "Extended attributes differ",
+            DifferenceKind::Acl => "ACL differs",
+            DifferenceKind::Contents => "Contents differ",
+            DifferenceKind::Link => "Link differs",
+        };
+        write!(f, "{}: {message}", self.path)
+    }
+}
+
+fn diff_archive(args: DiffCommand) -> anyhow::Result<()> {
+    let passwords = ask_passwords(args.password)?;
+    if let Some(against) = &args.against {
+        let against_passwords = if args.against_password_file.is_empty() {
+            passwords.clone()
+        } else {
+            ask_passwords(PasswordArgs {
+                password: None,
+                password_file: args.against_password_file,
+            })?
+        };
+        return diff_archives(
+            (&args.file.archive, &passwords),
+            (against, &against_passwords),
+            &args.file.files,
+            args.format,
+        );
+    }
+    let read_options = ReadOptions::with_passwords(&passwords);
+    let globs = GlobPatterns::new(&args.file.files)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let archives = collect_split_archives(&args.file.archive)?;
+    if let Some(working_dir) = args.working_dir {
+        env::set_current_dir(working_dir)?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut differences = 0usize;
+    run_process_archive(
+        archives
+            .into_iter()
+            .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+        &read_options,
+        |entry| {
+            let item = entry?;
+            let item_path = item.header().path().to_string();
+            if !globs.is_empty() && !globs.matches_any(&item_path) {
+                return Ok(());
+            }
+            let Some(path) = output_path(
+                item.header().path().as_path(),
+                args.strip_components,
+                &None,
+                None,
+            ) else {
+                return Ok(());
+            };
+            for difference in diff_entry(&item, &path, &read_options, args.content)? {
+                differences += 1;
+                match args.format {
+                    Format::Text => writeln!(stdout, "{difference}")?,
+                    Format::JsonL => {
+                        serde_json::to_writer(&mut stdout, &difference)?;
+                        writeln!(stdout)?;
+                    }
+                }
+            }
+            Ok(())
+        },
+    )?;
+    if differences != 0 {
+        return Err(io::Error::other(format!("{differences} differences found")).into());
+    }
+    Ok(())
+}
+
+/// Summary of an archived entry used to compare two archives.
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct EntrySummary {
+    kind: DataKind,
+    size: Option<u128>,
+    mode: Option<u16>,
+    uid: Option<u64>,
+    gid: Option<u64>,
+    mtime: Option<Duration>,
+    xattrs: Vec<ExtendedAttribute>,
+    digest: String,
+}
+
+impl EntrySummary {
+    fn new(item: &NormalEntry, read_options: &ReadOptions) -> io::Result<Self> {
+        let metadata = item.metadata();
+        let permission = metadata.permission();
+        let mut xattrs = item.xattrs().to_vec();
+        xattrs.sort();
+        let mut hasher = Sha256::new();
+        io::copy(&mut item.reader(read_options)?, &mut hasher)?;
+        Ok(Self {
+            kind: item.header().data_kind(),
+            size: metadata.raw_file_size(),
+            mode: permission.map(|it| it.permissions() & 0o7777),
+            uid: permission.map(|it| it.uid()),
+            gid: permission.map(|it| it.gid()),
+            mtime: metadata.modified(),
+            xattrs,
+            digest: format!("{:x}", hasher.finalize()),
+        })
+    }
+
+    fn changes(&self, other: &Self) -> Vec<DifferenceKind> {
+        if self.kind != other.kind {
+            return vec![DifferenceKind::Type];
+        }
+        [
+            (self.size != other.size, DifferenceKind::Size),
+            (self.mode != other.mode, DifferenceKind::Mode),
+            (self.uid != other.uid, DifferenceKind::Uid),
+            (self.gid != other.gid, DifferenceKind::Gid),
+            (self.mtime != other.mtime, DifferenceKind::Mtime),
+            (self.xattrs != other.xattrs, DifferenceKind::Xattr),
+            (self.digest != other.digest, DifferenceKind::Contents),
+        ]
+        .into_iter()
+        .filter_map(|(changed, kind)| changed.then_some(kind))
+        .collect()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum ArchiveDifference {
+    Added {
+        path: String,
+    },
+    Removed {
+        path: String,
+    },
+    Modified {
+        path: String,
+        changes: Vec<DifferenceKind>,
+    },
+    Renamed {
+        from: String,
+        path: String,
+    },
+}
+
+impl ArchiveDifference {
+    #[inline]
+    fn path(&self) -> &str {
+        match self {
+            Self::Added { path }
+            | Self::Removed { path }
+            | Self::Modified { path, .. }
+            | Self::Renamed { path, .. } => path,
+        }
+    }
+}
+
+impl Display for ArchiveDifference {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { path } => write!(f, "added: {path}"),
+            Self::Removed { path } => write!(f, "removed: {path}"),
+            Self::Modified { path, changes } => {
+                let changes = changes
+                    .iter()
+                    .map(|it| it.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "modified: {path} ({changes})")
+            }
+            Self::Renamed { from, path } => write!(f, "renamed: {from} -> {path}"),
+        }
+    }
+}
+
+fn diff_archives(
+    (archive, passwords): (&Path, &[String]),
+    (against, against_passwords): (&Path, &[String]),
+    files: &[String],
+    format: Format,
+) -> anyhow::Result<()> {
+    let globs =
+        GlobPatterns::new(files).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let old = summarize_archive(archive, passwords, &globs)?;
+    let new = summarize_archive(against, against_passwords, &globs)?;
+
+    let mut differences = Vec::new();
+    let mut removed = Vec::new();
+    for (path, summary) in &old {
+        match new.get(path) {
+            Some(other) => {
+                let changes = summary.changes(other);
+                if !changes.is_empty() {
+                    differences.push(ArchiveDifference::Modified {
+                        path: path.clone(),
+                        changes,
+                    });
+                }
+            }
+            None => removed.push(path),
+        }
+    }
+    let mut added = new
+        .iter()
+        .filter(|(path, _)| !old.contains_key(*path))
+        .fold(HashMap::<_, Vec<_>>::new(), |mut map, (path, summary)| {
+            map.entry((summary.kind, summary.digest.as_str()))
+                .or_default()
+                .push(path);
+            map
+        });
+    for path in removed {
+        let summary = &old[path];
+        let renamed = (summary.kind == DataKind::File)
+            .then(|| added.get_mut(&(summary.kind, summary.digest.as_str())))
+            .flatten()
+            .and_then(|candidates| (!candidates.is_empty()).then(|| candidates.remove(0)));
+        differences.push(match renamed {
+            Some(to) => ArchiveDifference::Renamed {
+                from: path.clone(),
+                path: to.clone(),
+            },
+            None => ArchiveDifference::Removed { path: path.clone() },
+        });
+    }
+    differences.extend(
+        added
+            .into_values()
+            .flatten()
+            .map(|path| ArchiveDifference::Added { path: path.clone() }),
+    );
+    differences.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let mut stdout = io::stdout().lock();
+    for difference in &differences {
+        match format {
+            Format::Text => writeln!(stdout, "{difference}")?,
+            Format::JsonL => {
+                serde_json::to_writer(&mut stdout, difference)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+    if !differences.is_empty() {
+        return Err(io::Error::other(format!("{} differences found", differences.len())).into());
+    }
+    Ok(())
+}
+
+fn summarize_archive(
+    path: &Path,
+    passwords: &[String],
+    globs: &GlobPatterns,
+) -> io::Result<BTreeMap<String, EntrySummary>> {
+    let read_options = ReadOptions::with_passwords(passwords);
+    let archives = collect_split_archives(path)?;
+    let mut summaries = BTreeMap::new();
+    let mut summarize = |item: NormalEntry| -> io::Result<()> {
+        let name = item.header().path().to_string();
+        if globs.is_empty() || globs.matches_any(&name) {
+            summaries.insert(name, EntrySummary::new(&item, &read_options)?);
+        }
+        Ok(())
+    };
+    run_read_entries(
+        archives
+            .into_iter()
+            .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+        |entry| match entry? {
+            ReadEntry::Solid(solid) => solid
+                .entries_with_options(&read_options)?
+                .try_for_each(|it| summarize(it?)),
+            ReadEntry::Normal(item) => summarize(item),
+        },
+    )?;
+    Ok(summaries)
+}
+
+fn diff_entry(
+    item: &NormalEntry,
+    path: &Path,
+    read_options: &ReadOptions,
+    content: bool,
+) -> io::Result<Vec<Difference>> {
+    let name = item.header().path().as_str();
+    let data_kind = item.header().data_kind();
+    let meta = match (fs::symlink_metadata(path), data_kind) {
+        (Ok(_), DataKind::Whiteout) => {
+            return Ok(vec![Difference::new(name, DifferenceKind::Deleted)]);
+        }
+        (Ok(meta), _) => meta,
+        (Err(e), DataKind::Whiteout) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        (Err(e), _) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(vec![Difference::new(name, DifferenceKind::Missing)]);
+        }
+        (Err(e), _) => return Err(e),
+    };
+    let same_kind = match data_kind {
+        DataKind::File | DataKind::HardLink => meta.is_file(),
+        DataKind::Directory => meta.is_dir(),
+        DataKind::SymbolicLink => meta.is_symlink(),
+        DataKind::Whiteout => false,
+        _ => return Ok(vec![Difference::new(name, DifferenceKind::Type)]),
+    };
+    if !same_kind {
+        return Ok(vec![Difference::new(name, DifferenceKind::Type)]);
+    }
+
+    let mut differences = Vec::new();
+    let metadata = item.metadata();
+    if data_kind == DataKind::File {
+        if let Some(size) = metadata.raw_file_size() {
+            if size != u128::from(meta.len()) {
+                differences
+                    .push(Difference::new(name, DifferenceKind::Size).values(size, meta.len()));
+            }
+        }
+    }
+
+    let keep_options = KeepOptions {
+        keep_timestamp: metadata.modified().is_some(),
+        keep_permission: metadata.permission().is_some(),
+        keep_xattr: !item.xattrs().is_empty(),
+        keep_acl: has_acl(item),
+    };
+    let owner_options = OwnerOptions::new(
+        None,
+        None,
+        None,
+        None,
+        false,
+        IdMap::default(),
+        IdMap::default(),
+    );
+    let time_options = TimeOptions {
+        mtime: None,
+        clamp_mtime: false,
+        ctime: None,
+        clamp_ctime: false,
+        atime: None,
+        clamp_atime: false,
+        mtime_only: false,
+    };
+    let current = apply_metadata(
+        EntryBuilder::new_dir(item.header().path().clone()),
+        path,
+        &keep_options,
+        &owner_options,
+        &time_options,
+        fs::symlink_metadata,
+    )?
+    .build()?;
+
+    if let (Some(archived), Some(current)) =
+        (metadata.permission(), current.metadata().permission())
+    {
+        let (archived_mode, current_mode) = (
+            archived.permissions() & 0o7777,
+            current.permissions() & 0o7777,
+        );
+        if archived_mode != current_mode {
+            differences.push(Difference::new(name, DifferenceKind::Mode).values(
+                format!("{archived_mode:04o}"),
+                format!("{current_mode:04o}"),
+            ));
+        }
+        if archived.uid() != current.uid() {
+            differences.push(
+                Difference::new(name, DifferenceKind::Uid).values(archived.uid(), current.uid()),
+            );
+        }
+        if archived.gid() != current.gid() {
+            differences.push(
+                Difference::new(name, DifferenceKind::Gid).values(archived.gid(), current.gid()),
+            );
+        }
+    }
+    if let (Some(archived), Some(current)) = (metadata.modified(), current.metadata().modified()) {
+        if archived.as_secs() != current.as_secs() {
+            differences.push(
+                Difference::new(name, DifferenceKind::Mtime)
+                    .values(archived.as_secs(), current.as_secs()),
+            );
+        }
+    }
+    if keep_options.keep_xattr {
+        let mut archived = item.xattrs().to_vec();
+        let mut current = current.xattrs().to_vec();
+        archived.sort();
+        current.sort();
+        if archived != current {
+            differences.push(Difference::new(name, DifferenceKind::Xattr));
+        }
+    }
+    #[cfg(feature = "acl")]
+    if keep_options.keep_acl {
+        let platform = AcePlatform::CURRENT;
+        if let Some(archived) = item.acl()?.get(&platform) {
+            if current.acl()?.get(&platform) != Some(archived) {
+                differences.push(Difference::new(name, DifferenceKind::Acl));
+            }
+        }
+    }
+
+    match data_kind {
+        DataKind::File
+            if content && differences.iter().all(|it| it.kind != DifferenceKind::Size) =>
+        {
+            let reader = item.reader(read_options)?;
+            if !same_contents(reader, fs::File::open(path)?)? {
+                differences.push(Difference::new(name, DifferenceKind::Contents));
+            }
+        }
+        DataKind::SymbolicLink => {
+            let archived = io::read_to_string(item.reader(read_options)?)?;
+            let current = fs::read_link(path)?;
+            if Path::new(&archived) != current {
+                differences.push(
+                    Difference::new(name, DifferenceKind::Link).values(archived, current.display()),
+                );
+            }
+        }
+        #[cfg(unix)]
+        DataKind::HardLink => {
+            use std::os::unix::fs::MetadataExt;
+
+            let archived = io::read_to_string(item.reader(read_options)?)?;
+            let original = path
+                .parent()
+                .map_or_else(|| PathBuf::from(&archived), |it| it.join(&archived));
+            let linked = fs::metadata(&original)
+                .is_ok_and(|it| it.dev() == meta.dev() && it.ino() == meta.ino());
+            if !linked {
+                differences.push(Difference::new(name, DifferenceKind::Link).values(archived, ""));
+            }
+        }
+        _ => (),
+    }
+    Ok(differences)
+}
+
+fn has_acl(item: &NormalEntry) -> bool {
+    item.extra_chunks()
+        .iter()
+        .any(|it| it.ty() == crate::chunk::faCl || it.ty() == crate::chunk::faCe)
+}
+
+fn same_contents(mut a: impl Read, mut b: impl Read) -> io::Result<bool> {
+    const BUFFER_SIZE: u64 = 64 * 1024;
+    let (mut buf_a, mut buf_b) = (Vec::new(), Vec::new());
+    loop {
+        buf_a.clear();
+        buf_b.clear();
+        let n = a.by_ref().take(BUFFER_SIZE).read_to_end(&mut buf_a)?;
+        b.by_ref().take(BUFFER_SIZE).read_to_end(&mut buf_b)?;
+        if buf_a != buf_b {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
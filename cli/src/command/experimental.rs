@@ -32,6 +32,12 @@ impl Command for ExperimentalCommand {
             ExperimentalCommands::Migrate(cmd) => cmd.execute(),
             ExperimentalCommands::Chunk(cmd) => cmd.execute(),
             ExperimentalCommands::Sort(cmd) => cmd.execute(),
+            ExperimentalCommands::Verify(cmd) => cmd.execute(),
+            ExperimentalCommands::Diff(cmd) => cmd.execute(),
+            ExperimentalCommands::Cat(cmd) => cmd.execute(),
+            ExperimentalCommands::Import(cmd) => cmd.execute(),
+            ExperimentalCommands::Export(cmd) => cmd.execute(),
+            ExperimentalCommands::Compact(cmd) => cmd.execute(),
         }
     }
 }
@@ -58,4 +64,16 @@ pub(crate) enum ExperimentalCommands {
     Chunk(command::chunk::ChunkCommand),
     #[command(about = "Sort entries in archive")]
     Sort(command::sort::SortCommand),
+    #[command(about = "Verify integrity of archive")]
+    Verify(command::verify::VerifyCommand),
+    #[command(about = "Compare archive with files")]
+    Diff(command::diff::DiffCommand),
+    #[command(about = "Write contents of entries to stdout")]
+    Cat(command::cat::CatCommand),
+    #[command(about = "Create archive from tar or zip archive")]
+    Import(command::import::ImportCommand),
+    #[command(about = "Convert archive to tar or zip archive")]
+    Export(command::export::ExportCommand),
+    #[command(about = "Remove superseded entries from archive")]
+    Compact(command::compact::CompactCommand),
 }
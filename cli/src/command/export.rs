@@ -0,0 +1,551 @@
+use crate::{
+    chunk::{AcePlatform, Flag, OwnerType, Permission as AcePermission},
+    cli::{EntryFilter, FileArgs, PasswordArgs},
+    command::{
+        ask_passwords,
+        commons::{collect_split_archives, run_process_archive},
+        extract::entry_reader,
+        Command,
+    },
+    ext::NormalEntryExt,
+    utils::{self, resolve_from_parent, GlobPatterns},
+};
+use clap::{ArgGroup, Parser, ValueEnum, ValueHint};
+use pna::{DataKind, EntryName, NormalEntry, ReadOptions};
+use std::{
+    io::{self, prelude::*},
+    path::PathBuf,
+    time::Duration,
+};
+
+#[derive(Parser, Clone, Debug)]
+#[command(
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
+)]
+pub(crate) struct ExportCommand {
+    #[arg(long, help = "Output format", value_enum)]
+    pub(crate) format: ExportFormat,
+    #[arg(
+        short,
+        long,
+        help = "Output file path. If not given the exported archive is written to the standard output",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) output: Option<PathBuf>,
+    #[arg(long, help = "Overwrite file", requires = "output")]
+    pub(crate) overwrite: bool,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    pub(crate) filter: Option<EntryFilter>,
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[command(flatten)]
+    pub(crate) file: FileArgs,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, ValueEnum)]
+#[value(rename_all = "lower")]
+pub(crate) enum ExportFormat {
+    /// GNU tar. Extended attributes and ACLs are not exported
+    Tar,
+    /// POSIX.1-2001 pax tar with extended attributes, ACLs and sub-second timestamps
+    Pax,
+    /// zip. Owners, extended attributes, ACLs and hard links are not exported
+    Zip,
+}
+
+impl Command for ExportCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        export_archive(self)
+    }
+}
+
+fn export_archive(args: ExportCommand) -> anyhow::Result<()> {
+    let passwords = ask_passwords(args.password)?;
+    let archives = collect_split_archives(&args.file.archive)?;
+    let archives = archives
+        .into_iter()
+        .map(|it| io::BufReader::with_capacity(64 * 1024, it));
+    let globs = GlobPatterns::new(&args.file.files)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let output = args
+        .output
+        .map(|path| utils::fs::file_create(path, args.overwrite))
+        .transpose()?;
+
+    match (args.format, output) {
+        (ExportFormat::Tar | ExportFormat::Pax, output) => {
+            let pax = args.format == ExportFormat::Pax;
+            match output {
+                Some(file) => {
+                    let writer = io::BufWriter::new(file);
+                    let mut exporter = TarExporter::new(writer, pax);
+                    run_export(
+                        archives,
+                        &globs,
+                        args.filter.as_ref(),
+                        &passwords,
+                        &mut exporter,
+                    )?;
+                    exporter.finish()?.flush()?;
+                }
+                None => {
+                    let mut exporter = TarExporter::new(io::stdout().lock(), pax);
+                    run_export(
+                        archives,
+                        &globs,
+                        args.filter.as_ref(),
+                        &passwords,
+                        &mut exporter,
+                    )?;
+                    exporter.finish()?.flush()?;
+                }
+            }
+        }
+        (ExportFormat::Zip, Some(file)) => {
+            let mut exporter = ZipExporter(zip::ZipWriter::new(io::BufWriter::new(file)));
+            run_export(
+                archives,
+                &globs,
+                args.filter.as_ref(),
+                &passwords,
+                &mut exporter,
+            )?;
+            exporter.0.finish()?.flush()?;
+        }
+        (ExportFormat::Zip, None) => {
+            let mut exporter = ZipExporter(zip::ZipWriter::new_stream(io::stdout().lock()));
+            run_export(
+                archives,
+                &globs,
+                args.filter.as_ref(),
+                &passwords,
+                &mut exporter,
+            )?;
+            exporter.0.finish()?.into_inner().flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn run_export(
+    archives: impl IntoIterator<Item = impl Read>,
+    globs: &GlobPatterns,
+    filter: Option<&EntryFilter>,
+    passwords: &[String],
+    exporter: &mut impl Exporter,
+) -> io::Result<()> {
+    let read_options = ReadOptions::with_passwords(passwords);
+    run_process_archive(archives, &read_options, |entry| {
+        let item = entry?;
+        if !globs.is_empty() && !globs.matches_any(item.header().path())
+            || filter.is_some_and(|it| !it.matches(&item))
+        {
+            log::debug!("Skip: {}", item.header().path());
+            return Ok(());
+        }
+        let mut data: Box<dyn Read> = if item.header().data_kind() == DataKind::Directory {
+            Box::new(io::empty())
+        } else {
+            Box::new(entry_reader(&item, &read_options)?)
+        };
+        let unsupported = exporter.add(&item, &mut data)?;
+        if !unsupported.is_empty() {
+            log::warn!(
+                "{}: {} cannot be represented in the exported archive",
+                item.header().path(),
+                unsupported.join(", ")
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Writes entries into an archive of another format.
+trait Exporter {
+    /// Adds an entry whose data is read from `data` and returns the names of the information
+    /// that could not be exported.
+    fn add(&mut self, item: &NormalEntry, data: &mut dyn Read) -> io::Result<Vec<&'static str>>;
+}
+
+struct TarExporter<W: Write> {
+    builder: tar::Builder<W>,
+    pax: bool,
+}
+
+impl<W: Write> TarExporter<W> {
+    fn new(writer: W, pax: bool) -> Self {
+        Self {
+            builder: tar::Builder::new(writer),
+            pax,
+        }
+    }
+
+    fn finish(self) -> io::Result<W> {
+        self.builder.into_inner()
+    }
+}
+
+impl<W: Write> Exporter for TarExporter<W> {
+    fn add(&mut self, item: &NormalEntry, data: &mut dyn Read) -> io::Result<Vec<&'static str>> {
+        let data_kind = item.header().data_kind();
+        if data_kind == DataKind::Whiteout {
+            return Ok(vec!["deletion marker"]);
+        }
+        let mut unsupported = Vec::new();
+        let metadata = item.metadata();
+        let mut path = item.header().path().to_string();
+        if data_kind == DataKind::Directory {
+            path.push('/');
+        }
+
+        let mut header = if self.pax {
+            tar::Header::new_ustar()
+        } else {
+            tar::Header::new_gnu()
+        };
+        let (entry_type, link_name) = match data_kind {
+            DataKind::File => (tar::EntryType::Regular, None),
+            DataKind::Directory => (tar::EntryType::Directory, None),
+            DataKind::SymbolicLink => (
+                tar::EntryType::Symlink,
+                Some(io::read_to_string(&mut *data)?),
+            ),
+            DataKind::HardLink => {
+                // Hard link entries refer to the original from their own directory, while tar
+                // link names are relative to the root of the archive.
+                let reference = io::read_to_string(&mut *data)?;
+                let original =
+                    resolve_from_parent(item.header().path().as_path(), reference.as_ref());
+                (
+                    tar::EntryType::Link,
+                    Some(EntryName::from_lossy(original).to_string()),
+                )
+            }
+            DataKind::Whiteout => unreachable!("deletion markers are skipped before"),
+            _ => return Ok(vec!["entry of unknown kind"]),
+        };
+        header.set_entry_type(entry_type);
+        // The size must be written before the data, so only entries without a recorded size
+        // are read into memory.
+        let mut buffered = Vec::new();
+        let mut buffered_reader;
+        let (size, data): (u64, &mut dyn Read) = match (data_kind, metadata.raw_file_size()) {
+            (DataKind::File, Some(size)) => (
+                u64::try_from(size).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                data,
+            ),
+            (DataKind::File, None) => {
+                data.read_to_end(&mut buffered)?;
+                buffered_reader = &buffered[..];
+                (buffered.len() as u64, &mut buffered_reader)
+            }
+            _ => (0, data),
+        };
+        let data = SizedReader {
+            inner: data,
+            remaining: size,
+        };
+        header.set_size(size);
+        let mut records = Vec::new();
+        match metadata.permission() {
+            Some(permission) => {
+                header.set_mode(u32::from(permission.permissions()) & 0o7777);
+                header.set_uid(permission.uid());
+                header.set_gid(permission.gid());
+                if header.set_username(permission.uname()).is_err() {
+                    records.push(("uname".into(), permission.uname().as_bytes().to_vec()));
+                }
+                if header.set_groupname(permission.gname()).is_err() {
+                    records.push(("gname".into(), permission.gname().as_bytes().to_vec()));
+                }
+            }
+            None => {
+                header.set_mode(if data_kind == DataKind::Directory {
+                    0o755
+                } else {
+                    0o644
+                });
+                header.set_uid(0);
+                header.set_gid(0);
+            }
+        }
+        header.set_mtime(metadata.modified().unwrap_or_default().as_secs());
+
+        if !self.pax {
+            if let Some(gnu) = header.as_gnu_mut() {
+                if let Some(atime) = metadata.accessed() {
+                    gnu.set_atime(atime.as_secs());
+                }
+                if let Some(ctime) = metadata.created() {
+                    gnu.set_ctime(ctime.as_secs());
+                }
+            }
+            if records
+                .iter()
+                .any(|(key, _)| key == "uname" || key == "gname")
+            {
+                unsupported.push("long owner names");
+            }
+            if !item.xattrs().is_empty() {
+                unsupported.push("extended attributes");
+            }
+            if !item.acl()?.is_empty() {
+                unsupported.push("ACL");
+            }
+            match link_name {
+                Some(link_name) => self.builder.append_link(&mut header, &path, &link_name)?,
+                None => self.builder.append_data(&mut header, &path, data)?,
+            }
+            return Ok(unsupported);
+        }
+
+        if header.set_path(&path).is_err() {
+            records.push(("path".into(), path.as_bytes().to_vec()));
+            set_truncated(&mut header.as_old_mut().name, &path);
+        }
+        if let Some(link_name) = &link_name {
+            if header.set_link_name(link_name).is_err() {
+                records.push(("linkpath".into(), link_name.as_bytes().to_vec()));
+                set_truncated(&mut header.as_old_mut().linkname, link_name);
+            }
+        }
+        for (key, time) in [
+            ("mtime", metadata.modified()),
+            ("atime", metadata.accessed()),
+            ("ctime", metadata.created()),
+        ] {
+            if let Some(time) = time {
+                records.push((key.into(), pax_time(time).into_bytes()));
+            }
+        }
+        for xattr in item.xattrs() {
+            records.push((
+                format!("SCHILY.xattr.{}", xattr.name()),
+                xattr.value().to_vec(),
+            ));
+        }
+        let acl = posix_acl(item)?;
+        if acl.lossy {
+            unsupported.push("non POSIX ACL entries");
+        }
+        if !acl.access.is_empty() {
+            records.push((
+                "SCHILY.acl.access".into(),
+                acl.access.join(",").into_bytes(),
+            ));
+        }
+        if !acl.default.is_empty() {
+            records.push((
+                "SCHILY.acl.default".into(),
+                acl.default.join(",").into_bytes(),
+            ));
+        }
+
+        if !records.is_empty() {
+            let records = records
+                .iter()
+                .flat_map(|(key, value)| pax_record(key, value))
+                .collect::<Vec<_>>();
+            let mut pax_header = tar::Header::new_ustar();
+            pax_header.set_entry_type(tar::EntryType::XHeader);
+            let name = format!("PaxHeaders/{}", path.trim_end_matches('/'));
+            if pax_header.set_path(&name).is_err() {
+                set_truncated(&mut pax_header.as_old_mut().name, &name);
+            }
+            pax_header.set_mode(0o644);
+            pax_header.set_uid(0);
+            pax_header.set_gid(0);
+            pax_header.set_mtime(header.mtime()?);
+            pax_header.set_size(records.len() as u64);
+            pax_header.set_cksum();
+            self.builder.append(&pax_header, &records[..])?;
+        }
+        header.set_cksum();
+        self.builder.append(&header, data)?;
+        Ok(unsupported)
+    }
+}
+
+/// Reads exactly `remaining` bytes, so that the data written after a tar header always matches
+/// the size recorded in it.
+struct SizedReader<R: Read> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for SizedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            return if self.inner.read(&mut [0])? == 0 {
+                Ok(0)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Entry data is larger than its recorded size",
+                ))
+            };
+        }
+        let len = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Entry data is smaller than its recorded size",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn set_truncated(field: &mut [u8], value: &str) {
+    let len = value.len().min(field.len());
+    field.fill(0);
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+fn pax_time(time: Duration) -> String {
+    match time.subsec_nanos() {
+        0 => time.as_secs().to_string(),
+        nanos => format!("{}.{:09}", time.as_secs(), nanos)
+            .trim_end_matches('0')
+            .into(),
+    }
+}
+
+/// Encodes a pax extended header record `"<length> <key>=<value>\n"`.
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + 1;
+    while len.to_string().len() + rest != len {
+        len = len.to_string().len() + rest;
+    }
+    let mut record = format!("{len} {key}=").into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+#[derive(Default)]
+struct PosixAcl {
+    access: Vec<String>,
+    default: Vec<String>,
+    lossy: bool,
+}
+
+/// Converts the ACL chunks of the entry into POSIX.1e text form used by the `SCHILY.acl.*` records.
+fn posix_acl(item: &NormalEntry) -> io::Result<PosixAcl> {
+    let mut acls = item.acl()?;
+    let mut result = PosixAcl::default();
+    let aces = acls
+        .remove(&AcePlatform::Linux)
+        .or_else(|| acls.remove(&AcePlatform::General))
+        .unwrap_or_default();
+    result.lossy = !acls.is_empty();
+    for ace in aces {
+        if !ace.allow {
+            result.lossy = true;
+            continue;
+        }
+        let tag = match &ace.owner_type {
+            OwnerType::Owner => "user:".into(),
+            OwnerType::User(id) => format!("user:{id}"),
+            OwnerType::OwnerGroup => "group:".into(),
+            OwnerType::Group(id) => format!("group:{id}"),
+            OwnerType::Mask => "mask:".into(),
+            OwnerType::Other => "other:".into(),
+        };
+        let permission = [
+            (AcePermission::READ, 'r'),
+            (AcePermission::WRITE, 'w'),
+            (AcePermission::EXECUTE, 'x'),
+        ]
+        .into_iter()
+        .map(|(flag, c)| {
+            if ace.permission.contains(flag) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+        let entry = format!("{tag}:{permission}");
+        if ace.flags.contains(Flag::DEFAULT) {
+            result.default.push(entry);
+        } else {
+            result.access.push(entry);
+        }
+    }
+    Ok(result)
+}
+
+struct ZipExporter<W: Write + Seek>(zip::ZipWriter<W>);
+
+impl<W: Write + Seek> Exporter for ZipExporter<W> {
+    fn add(&mut self, item: &NormalEntry, data: &mut dyn Read) -> io::Result<Vec<&'static str>> {
+        let mut unsupported = Vec::new();
+        let metadata = item.metadata();
+        let data_kind = item.header().data_kind();
+        let path = item.header().path().as_str();
+        let mut options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(
+                metadata
+                    .raw_file_size()
+                    .is_none_or(|size| size >= u128::from(u32::MAX)),
+            );
+        if let Some(permission) = metadata.permission() {
+            options = options.unix_permissions(u32::from(permission.permissions()) & 0o7777);
+            unsupported.push("owner");
+        }
+        if let Some(mtime) = metadata.modified().and_then(zip_datetime) {
+            options = options.last_modified_time(mtime);
+        }
+        if !item.xattrs().is_empty() {
+            unsupported.push("extended attributes");
+        }
+        if !item.acl()?.is_empty() {
+            unsupported.push("ACL");
+        }
+        match data_kind {
+            DataKind::File => {
+                self.0.start_file(path, options)?;
+                io::copy(data, &mut self.0)?;
+            }
+            DataKind::Directory => self.0.add_directory(path, options)?,
+            DataKind::SymbolicLink => {
+                self.0
+                    .add_symlink(path, io::read_to_string(&mut *data)?, options)?
+            }
+            DataKind::HardLink => unsupported.push("hard link"),
+            DataKind::Whiteout => unsupported.push("deletion marker"),
+            _ => unsupported.push("entry of unknown kind"),
+        }
+        Ok(unsupported)
+    }
+}
+
+fn zip_datetime(time: Duration) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+
+    let time = chrono::DateTime::from_timestamp(i64::try_from(time.as_secs()).ok()?, 0)?;
+    zip::DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
@@ -1,27 +1,34 @@
 #[cfg(feature = "memmap")]
 use crate::command::commons::run_entries;
-#[cfg(any(unix, windows))]
+#[cfg(windows)]
 use crate::utils::fs::chown;
 use crate::{
-    cli::{FileArgs, PasswordArgs},
+    cli::{
+        BackupControl, DateTime, EntryFilter, FileArgs, OverwriteArgs, OverwriteStrategy,
+        PasswordArgs, ProgressArgs,
+    },
     command::{
-        ask_password,
+        ask_passwords,
+        cat::run_cat_archive,
         commons::{
-            collect_split_archives, run_process_archive, Exclude, KeepOptions, OwnerOptions,
-            PathTransformers,
+            collect_split_archives, run_process_archive, select_versions, Exclude, KeepOptions,
+            OwnerOptions, PathTransformers, PlannedAction,
         },
+        progress::{observer, ProgressReporter},
         Command,
     },
     utils::{
         self,
         fmt::DurationDisplay,
-        fs::{Group, User},
+        fs::{Beneath, BeneathEntry, Group, IdMap, User},
         re::{bsd::SubstitutionRule, gnu::TransformRule},
-        GlobPatterns,
+        resolve_from_parent, GlobPatterns,
     },
 };
 use clap::{ArgGroup, Parser, ValueHint};
-use pna::{prelude::*, DataKind, EntryReference, NormalEntry, Permission, ReadOptions};
+use pna::{
+    prelude::*, DataKind, EntryReference, NormalEntry, Permission, ReadOptions, VersionSelector,
+};
 use std::io::Read;
 #[cfg(target_os = "macos")]
 use std::os::macos::fs::FileTimesExt;
@@ -29,9 +36,12 @@ use std::os::macos::fs::FileTimesExt;
 use std::os::windows::fs::FileTimesExt;
 use std::{
     borrow::Cow,
+    cmp::Reverse,
+    collections::HashSet,
     env, fs, io,
-    path::{Component, PathBuf},
-    time::Instant,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Instant, UNIX_EPOCH},
 };
 
 #[derive(Parser, Clone, Debug)]
@@ -39,6 +49,7 @@ use std::{
     group(ArgGroup::new("unstable-include").args(["include"]).requires("unstable")),
     group(ArgGroup::new("unstable-exclude").args(["exclude"]).requires("unstable")),
     group(ArgGroup::new("unstable-exclude-from").args(["exclude_from"]).requires("unstable")),
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
     group(ArgGroup::new("unstable-acl").args(["keep_acl"]).requires("unstable")),
     group(ArgGroup::new("unstable-substitution").args(["substitutions"]).requires("unstable")),
     group(ArgGroup::new("unstable-transform").args(["transforms"]).requires("unstable")),
@@ -46,6 +57,14 @@ use std::{
     group(ArgGroup::new("owner-flag").args(["same_owner", "no_same_owner"])),
     group(ArgGroup::new("user-flag").args(["numeric_owner", "uname"])),
     group(ArgGroup::new("group-flag").args(["numeric_owner", "gname"])),
+    group(ArgGroup::new("version-selector").args(["at", "version"])),
+    group(ArgGroup::new("unstable-keep-directory-symlink").args(["keep_directory_symlink"]).requires("unstable")),
+    group(ArgGroup::new("unstable-version-selector").args(["at", "version"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-overwrite-policy").args(["keep_old_files", "keep_newer_files", "backup", "unlink_first"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-atomic").args(["atomic", "fsync"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-to-stdout").args(["to_stdout"]).requires("unstable")),
+    group(ArgGroup::new("unstable-id-map").args(["owner_map", "group_map"]).multiple(true).requires("unstable")),
+    group(ArgGroup::new("unstable-dry-run").args(["dry_run"]).requires("unstable")),
 )]
 #[cfg_attr(windows, command(
     group(ArgGroup::new("windows-unstable-keep-permission").args(["keep_permission"]).requires("unstable")),
@@ -53,8 +72,33 @@ use std::{
 pub(crate) struct ExtractCommand {
     #[arg(long, help = "Overwrite file")]
     pub(crate) overwrite: bool,
+    #[command(flatten)]
+    pub(crate) overwrite_policy: OverwriteArgs,
+    #[arg(
+        long,
+        help = "Write each file to a temporary file next to it and rename it into place once it is completely extracted (unstable)"
+    )]
+    pub(crate) atomic: bool,
+    #[arg(
+        long,
+        help = "Flush each extracted file to the disk before moving on (unstable)"
+    )]
+    pub(crate) fsync: bool,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing any file (unstable)"
+    )]
+    pub(crate) dry_run: bool,
     #[arg(long, help = "Output directory of extracted files", value_hint = ValueHint::DirPath)]
     pub(crate) out_dir: Option<PathBuf>,
+    #[arg(
+        short = 'O',
+        long,
+        conflicts_with = "out_dir",
+        help = "Write the contents of extracted files to the standard output instead of creating them (unstable)"
+    )]
+    pub(crate) to_stdout: bool,
     #[command(flatten)]
     pub(crate) password: PasswordArgs,
     #[arg(
@@ -100,6 +144,20 @@ pub(crate) struct ExtractCommand {
         help = "This is equivalent to --uname \"\" --gname \"\". It causes user and group names in the archive to be ignored in favor of the numeric user and group ids."
     )]
     pub(crate) numeric_owner: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map user names and ids in the archive with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) owner_map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map group names and ids in the archive with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id (unstable)",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) group_map: Option<PathBuf>,
     #[arg(
         long,
         help = "Process only files or directories that match the specified pattern. Note that exclusions specified with --exclude take precedence over inclusions"
@@ -109,6 +167,12 @@ pub(crate) struct ExtractCommand {
     exclude: Option<Vec<String>>,
     #[arg(long, help = "Read exclude files from given path (unstable)", value_hint = ValueHint::FilePath)]
     exclude_from: Option<PathBuf>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    filter: Option<EntryFilter>,
     #[arg(
         long,
         help = "Remove the specified number of leading path elements. Path names with fewer elements will be silently skipped"
@@ -153,6 +217,26 @@ pub(crate) struct ExtractCommand {
         help = "Allow extract symlink and hardlink that contains root path or parent path"
     )]
     allow_unsafe_links: bool,
+    #[arg(
+        long,
+        help = "Follow symbolic links to directories that already exist in the output directory instead of refusing to extract through them (unstable)"
+    )]
+    keep_directory_symlink: bool,
+    #[arg(
+        long,
+        value_name = "DATETIME",
+        help = "Extract each file as it was at the given time, using the last version whose recorded modification time is at or before it. Versions stored without a modification time are not extracted (unstable)"
+    )]
+    at: Option<DateTime>,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Extract the N-th version of each file that was added to the archive multiple times (unstable)"
+    )]
+    version: Option<u64>,
+    #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
     #[command(flatten)]
     pub(crate) file: FileArgs,
 }
@@ -164,12 +248,31 @@ impl Command for ExtractCommand {
     }
 }
 fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
-    let password = ask_password(args.password)?;
+    let passwords = ask_passwords(args.password)?;
     let start = Instant::now();
     log::info!("Extract archive {}", args.file.archive.display());
 
-    let archives = collect_split_archives(&args.file.archive)?;
+    let version = match (args.at, args.version) {
+        (Some(at), _) => Some(VersionSelector::AsOf(
+            at.to_system_time()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        )),
+        (_, Some(n)) => Some(VersionSelector::Nth(n as usize)),
+        (None, None) => None,
+    };
 
+    let selection = match version {
+        Some(version) => Some(select_versions(
+            collect_split_archives(&args.file.archive)?
+                .into_iter()
+                .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+            &ReadOptions::with_passwords(&passwords),
+            version,
+        )?),
+        None => None,
+    };
+    let archives = collect_split_archives(&args.file.archive)?;
     let exclude = {
         let mut exclude = args.exclude.unwrap_or_default();
         if let Some(p) = args.exclude_from {
@@ -180,6 +283,18 @@ fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
             exclude: exclude.into(),
         }
     };
+    if args.to_stdout {
+        return run_cat_archive(
+            archives
+                .into_iter()
+                .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+            args.file.files,
+            &passwords,
+            &exclude,
+            args.filter.as_ref(),
+            selection.as_ref(),
+        );
+    }
 
     let keep_options = KeepOptions {
         keep_timestamp: args.keep_timestamp,
@@ -193,18 +308,28 @@ fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
         args.uid,
         args.gid,
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
     let output_options = OutputOption {
-        overwrite: args.overwrite,
+        overwrite: args.overwrite_policy.strategy(args.overwrite),
+        unlink_first: args.overwrite_policy.unlink_first,
+        atomic: args.atomic,
+        fsync: args.fsync,
+        dry_run: args.dry_run,
         allow_unsafe_links: args.allow_unsafe_links,
+        keep_directory_symlink: args.keep_directory_symlink,
         strip_components: args.strip_components,
         out_dir: args.out_dir,
         exclude,
+        filter: args.filter,
         keep_options,
         owner_options,
         same_owner: !args.no_same_owner,
         path_transformers: PathTransformers::new(args.substitutions, args.transforms),
+        progress: ProgressReporter::new(args.progress, None, None),
     };
+    let progress = output_options.progress.clone();
     if let Some(working_dir) = args.working_dir {
         env::set_current_dir(working_dir)?;
     }
@@ -223,7 +348,8 @@ fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
             .into_iter()
             .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
         args.file.files,
-        || password.as_deref(),
+        &passwords,
+        selection.as_ref(),
         output_options,
     )?;
 
@@ -239,9 +365,13 @@ fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
     run_extract_archive(
         archives,
         args.file.files,
-        || password.as_deref(),
+        &passwords,
+        selection.as_ref(),
         output_options,
     )?;
+    if let Some(progress) = progress {
+        progress.finish("read");
+    }
     log::info!(
         "Successfully extracted an archive in {}",
         DurationDisplay(start.elapsed())
@@ -251,41 +381,57 @@ fn extract_archive(args: ExtractCommand) -> anyhow::Result<()> {
 
 #[derive(Clone, Debug)]
 pub(crate) struct OutputOption {
-    pub(crate) overwrite: bool,
+    pub(crate) overwrite: OverwriteStrategy,
+    pub(crate) unlink_first: bool,
+    pub(crate) atomic: bool,
+    pub(crate) fsync: bool,
+    pub(crate) dry_run: bool,
     pub(crate) allow_unsafe_links: bool,
+    pub(crate) keep_directory_symlink: bool,
     pub(crate) strip_components: Option<usize>,
     pub(crate) out_dir: Option<PathBuf>,
     pub(crate) exclude: Exclude,
+    pub(crate) filter: Option<EntryFilter>,
     pub(crate) keep_options: KeepOptions,
     pub(crate) owner_options: OwnerOptions,
     pub(crate) same_owner: bool,
     pub(crate) path_transformers: Option<PathTransformers>,
+    pub(crate) progress: Option<Arc<ProgressReporter>>,
 }
 
-pub(crate) fn run_extract_archive_reader<'p, Provider>(
+pub(crate) fn run_extract_archive_reader(
     reader: impl IntoIterator<Item = impl Read> + Send,
     files: Vec<String>,
-    mut password_provider: Provider,
+    passwords: &[String],
+    selection: Option<&HashSet<usize>>,
     args: OutputOption,
-) -> anyhow::Result<()>
-where
-    Provider: FnMut() -> Option<&'p str> + Send,
-{
-    let password = password_provider();
+) -> anyhow::Result<()> {
+    let read_options = &ReadOptions::with_passwords(passwords)
+        .into_builder()
+        .progress_observer(observer(&args.progress))
+        .build();
     let globs =
         GlobPatterns::new(files).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    let out = open_out_dir(&args)?;
     let mut link_entries = Vec::new();
+    let mut dir_entries = Vec::new();
 
-    let (tx, rx) = std::sync::mpsc::channel();
     rayon::scope_fifo(|s| -> anyhow::Result<()> {
-        run_process_archive(reader, password_provider, |entry| {
-            let item = entry?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut position = 0;
+        let mut in_flight = 0;
+        let mut process = |item: NormalEntry| {
+            let selected = selection.is_none_or(|it| it.contains(&position));
+            position += 1;
             let item_path = item.header().path().to_string();
-            if !globs.is_empty() && !globs.matches_any(&item_path) {
+            if !selected || !globs.is_empty() && !globs.matches_any(&item_path) {
                 log::debug!("Skip: {}", item.header().path());
                 return Ok(());
             }
+            let Some(out) = &out else {
+                return plan_entry(&item, read_options, &args);
+            };
             if matches!(
                 item.header().data_kind(),
                 DataKind::SymbolicLink | DataKind::HardLink
@@ -293,52 +439,84 @@ where
                 link_entries.push(item);
                 return Ok(());
             }
+            if item.header().data_kind() == DataKind::Directory {
+                extract_entry(&item, read_options, out, &args)?;
+                dir_entries.push(item);
+                return Ok(());
+            }
+            if item.header().data_kind() == DataKind::Whiteout {
+                // A marker removes what the entries before it extracted, and a later entry
+                // may be extracted at its path again, so it is applied in archive order.
+                for _ in 0..std::mem::take(&mut in_flight) {
+                    if let Some(result) = recv_result(&rx) {
+                        result?;
+                    }
+                }
+                for item in link_entries.drain(..) {
+                    extract_entry(&item, read_options, out, &args)?;
+                }
+                return extract_entry(&item, read_options, out, &args);
+            }
             let tx = tx.clone();
             let args = args.clone();
+            in_flight += 1;
             s.spawn_fifo(move |_| {
-                tx.send(extract_entry(item, password, &args))
+                tx.send(extract_entry(&item, read_options, out, &args))
                     .unwrap_or_else(|e| panic!("{e}: {item_path}"));
             });
             Ok(())
-        })?;
+        };
+        run_process_archive(reader, read_options, |entry| process(entry?))?;
         drop(tx);
+        while let Some(result) = recv_result(&rx) {
+            result?;
+        }
         Ok(())
     })?;
-    for result in rx {
-        result?;
-    }
-    for item in link_entries {
-        extract_entry(item, password, &args)?;
+    if let Some(out) = &out {
+        for item in link_entries {
+            extract_entry(&item, read_options, out, &args)?;
+        }
+        restore_directories(dir_entries, out, &args)?;
     }
     Ok(())
 }
 
 #[cfg(feature = "memmap")]
-pub(crate) fn run_extract_archive<'d, 'p, Provider>(
+pub(crate) fn run_extract_archive<'d>(
     archives: impl IntoIterator<Item = &'d [u8]> + Send,
     files: Vec<String>,
-    mut password_provider: Provider,
+    passwords: &[String],
+    selection: Option<&HashSet<usize>>,
     args: OutputOption,
-) -> io::Result<()>
-where
-    Provider: FnMut() -> Option<&'p str> + Send,
-{
+) -> io::Result<()> {
+    let read_options = &ReadOptions::with_passwords(passwords)
+        .into_builder()
+        .progress_observer(observer(&args.progress))
+        .build();
+    let out = open_out_dir(&args)?;
     rayon::scope_fifo(|s| {
-        let password = password_provider();
         let globs =
             GlobPatterns::new(files).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
         let mut link_entries = Vec::<NormalEntry>::new();
+        let mut dir_entries = Vec::<NormalEntry>::new();
 
         let (tx, rx) = std::sync::mpsc::channel();
 
-        run_entries(archives, password_provider, |entry| {
-            let item = entry?;
+        let mut position = 0;
+        let mut in_flight = 0;
+        let mut process = |item: NormalEntry<Cow<'d, [u8]>>| {
+            let selected = selection.is_none_or(|it| it.contains(&position));
+            position += 1;
             let item_path = item.header().path().to_string();
-            if !globs.is_empty() && !globs.matches_any(&item_path) {
+            if !selected || !globs.is_empty() && !globs.matches_any(&item_path) {
                 log::debug!("Skip: {}", item.header().path());
                 return Ok(());
             }
+            let Some(out) = &out else {
+                return plan_entry(&item, read_options, &args);
+            };
             if matches!(
                 item.header().data_kind(),
                 DataKind::SymbolicLink | DataKind::HardLink
@@ -346,175 +524,512 @@ where
                 link_entries.push(item.into());
                 return Ok(());
             }
+            if item.header().data_kind() == DataKind::Directory {
+                extract_entry(&item, read_options, out, &args)?;
+                dir_entries.push(item.into());
+                return Ok(());
+            }
+            if item.header().data_kind() == DataKind::Whiteout {
+                // A marker removes what the entries before it extracted, and a later entry
+                // may be extracted at its path again, so it is applied in archive order.
+                for _ in 0..std::mem::take(&mut in_flight) {
+                    if let Some(result) = recv_result(&rx) {
+                        result?;
+                    }
+                }
+                for item in link_entries.drain(..) {
+                    extract_entry(&item, read_options, out, &args)?;
+                }
+                return extract_entry(&item, read_options, out, &args);
+            }
             let tx = tx.clone();
             let args = args.clone();
+            in_flight += 1;
             s.spawn_fifo(move |_| {
-                tx.send(extract_entry(item, password, &args))
+                tx.send(extract_entry(&item, read_options, out, &args))
                     .unwrap_or_else(|e| panic!("{e}: {item_path}"));
             });
             Ok(())
-        })?;
+        };
+        run_entries(archives, read_options, |entry| process(entry?))?;
         drop(tx);
-        for result in rx {
+        while let Some(result) = recv_result(&rx) {
             result?;
         }
 
-        for item in link_entries {
-            extract_entry(item, password, &args)?;
+        if let Some(out) = &out {
+            for item in link_entries {
+                extract_entry(&item, read_options, out, &args)?;
+            }
+            restore_directories(dir_entries, out, &args)?;
         }
         Ok(())
     })
 }
 
-pub(crate) fn extract_entry<T>(
-    item: NormalEntry<T>,
-    password: Option<&str>,
-    OutputOption {
-        overwrite,
-        allow_unsafe_links,
-        strip_components,
-        out_dir,
-        exclude,
-        keep_options,
-        owner_options,
-        same_owner,
-        path_transformers,
-    }: &OutputOption,
+/// Receives the result of an entry extracted on the pool, or `None` once all of them were
+/// received. Other pool work is run while waiting, since this itself runs on a worker thread
+/// that may be the only one.
+fn recv_result(rx: &std::sync::mpsc::Receiver<io::Result<()>>) -> Option<io::Result<()>> {
+    use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+    loop {
+        match rx.try_recv() {
+            Ok(result) => return Some(result),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {}
+        }
+        if rayon::yield_now() != Some(rayon::Yield::Executed) {
+            match rx.recv_timeout(std::time::Duration::from_millis(1)) {
+                Ok(result) => return Some(result),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+}
+
+/// Opens the output directory, or returns `None` for a dry run, which must not create it.
+fn open_out_dir(args: &OutputOption) -> io::Result<Option<Beneath>> {
+    if args.dry_run {
+        return Ok(None);
+    }
+    Beneath::open(
+        args.out_dir.as_deref().unwrap_or_else(|| ".".as_ref()),
+        args.keep_directory_symlink,
+    )
+    .map(Some)
+}
+
+/// Returns the path of `item` relative to the output directory, or `None` if it is not
+/// extracted.
+fn relative_output_path<'a, T>(
+    item: &'a NormalEntry<T>,
+    args: &OutputOption,
+) -> Option<Cow<'a, Path>>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    if args.exclude.excluded(item.header().path().as_str())
+        || args.filter.as_ref().is_some_and(|it| !it.matches(item))
+    {
+        return None;
+    }
+    output_path(
+        item.header().path().as_path(),
+        args.strip_components,
+        &args.path_transformers,
+        None,
+    )
+}
+
+/// Prints what extracting `item` would do, without changing the filesystem.
+fn plan_entry<T>(
+    item: &NormalEntry<T>,
+    read_options: &ReadOptions,
+    args: &OutputOption,
 ) -> io::Result<()>
 where
     T: AsRef<[u8]>,
     pna::RawChunk<T>: Chunk,
 {
-    let same_owner = *same_owner;
-    let overwrite = *overwrite;
-    let item_path = item.header().path().as_str();
-    if exclude.excluded(item_path) {
+    let Some(relative_path) = relative_output_path(item, args) else {
         return Ok(());
-    }
-    let item_path = item.header().path().as_path();
-    log::debug!("Extract: {}", item_path.display());
-    let item_path = if let Some(strip_count) = *strip_components {
-        if item_path.components().count() <= strip_count {
+    };
+    let path = match &args.out_dir {
+        Some(out_dir) => out_dir.join(&relative_path),
+        None => relative_path.to_path_buf(),
+    };
+    let data_kind = item.header().data_kind();
+    if matches!(data_kind, DataKind::SymbolicLink | DataKind::HardLink) && !args.allow_unsafe_links
+    {
+        let original = link_reference(item, read_options, &args.path_transformers)?;
+        if skips_unsafe_link(data_kind, &original) {
+            PlannedAction::Skip.print(path.display());
             return Ok(());
         }
-        Cow::from(PathBuf::from_iter(item_path.components().skip(strip_count)))
-    } else {
-        Cow::from(item_path)
-    };
-    let item_path = if let Some(transformers) = path_transformers {
-        Cow::from(PathBuf::from(transformers.apply(
-            item_path.to_string_lossy(),
-            false,
-            false,
-        )))
-    } else {
-        item_path
+    }
+    let action = match fs::symlink_metadata(&path) {
+        Ok(_) if data_kind == DataKind::Whiteout => PlannedAction::Delete,
+        Ok(existing) => {
+            let merge = existing.is_dir() && data_kind == DataKind::Directory;
+            match args.overwrite {
+                OverwriteStrategy::Never => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", path.display()),
+                    ));
+                }
+                OverwriteStrategy::KeepOld if !merge => PlannedAction::Skip,
+                OverwriteStrategy::KeepNewer if !merge && !is_newer(item, &existing) => {
+                    PlannedAction::Skip
+                }
+                OverwriteStrategy::Backup(_) if !merge => PlannedAction::Backup,
+                _ if args.unlink_first && !merge => PlannedAction::Replace,
+                _ => PlannedAction::Overwrite,
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if data_kind == DataKind::Whiteout {
+                return Ok(());
+            }
+            PlannedAction::Add
+        }
+        Err(e) => return Err(e),
     };
-    let path = if let Some(out_dir) = out_dir {
-        Cow::from(out_dir.join(item_path))
-    } else {
-        item_path
+    action.print(path.display());
+    Ok(())
+}
+
+/// Writes an entry to the filesystem below `out`.
+///
+/// Directories are only created, their metadata is restored by [restore_directories] once
+/// their contents are extracted.
+pub(crate) fn extract_entry<T>(
+    item: &NormalEntry<T>,
+    read_options: &ReadOptions,
+    out: &Beneath,
+    args: &OutputOption,
+) -> io::Result<()>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    let Some(relative_path) = relative_output_path(item, args) else {
+        return Ok(());
     };
-    if path.exists() && !overwrite {
-        return Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            format!("{} already exists", path.display()),
-        ));
-    }
-    log::debug!("start: {}", path.display());
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+    let OutputOption {
+        overwrite,
+        unlink_first,
+        atomic,
+        fsync,
+        allow_unsafe_links,
+        path_transformers,
+        keep_options,
+        owner_options,
+        same_owner,
+        ..
+    } = args;
+    let same_owner = *same_owner;
+    log::debug!("Extract: {}", item.header().path());
+    let path = out.path(&relative_path);
+    if item.header().data_kind() == DataKind::Whiteout {
+        log::debug!("Remove: {}", path.display());
+        return match fs::symlink_metadata(&path) {
+            Ok(_) => out.remove_all(&relative_path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        };
     }
-    let permissions = if keep_options.keep_permission {
-        item.metadata()
-            .permission()
-            .and_then(|p| permissions(p, owner_options))
-    } else {
-        None
+    // Checked before the existing file is moved away or removed for it.
+    let link = match item.header().data_kind() {
+        kind @ (DataKind::SymbolicLink | DataKind::HardLink) => {
+            let original = link_reference(item, read_options, path_transformers)?;
+            if !allow_unsafe_links && skips_unsafe_link(kind, &original) {
+                return Ok(());
+            }
+            Some(original)
+        }
+        _ => None,
     };
-    match item.header().data_kind() {
-        DataKind::File => {
-            let mut file = utils::fs::file_create(&path, overwrite)?;
-            if keep_options.keep_timestamp {
-                let mut times = fs::FileTimes::new();
-                if let Some(accessed) = item.metadata().accessed_time() {
-                    times = times.set_accessed(accessed);
+    let overwrite = match fs::symlink_metadata(&path) {
+        Ok(existing) => {
+            // Extracting a directory over an existing one merges their contents.
+            let merge = existing.is_dir() && item.header().data_kind() == DataKind::Directory;
+            match *overwrite {
+                OverwriteStrategy::Never => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", path.display()),
+                    ));
+                }
+                OverwriteStrategy::KeepOld if !merge => {
+                    log::debug!("Keep existing: {}", path.display());
+                    return Ok(());
+                }
+                OverwriteStrategy::KeepNewer if !merge && !is_newer(item, &existing) => {
+                    log::debug!("Keep newer existing: {}", path.display());
+                    return Ok(());
                 }
-                if let Some(modified) = item.metadata().modified_time() {
-                    times = times.set_modified(modified);
+                OverwriteStrategy::Backup(control) if !merge => {
+                    let backup = backup_path(out, &relative_path, control)?;
+                    log::debug!(
+                        "Backup: {} -> {}",
+                        path.display(),
+                        out.path(&backup).display()
+                    );
+                    out.rename(&relative_path, &backup)?;
+                    false
                 }
-                #[cfg(any(windows, target_os = "macos"))]
-                if let Some(created) = item.metadata().created_time() {
-                    times = times.set_created(created);
+                _ if *unlink_first && !merge => {
+                    out.remove_all(&relative_path)?;
+                    false
                 }
-                file.set_times(times)?;
+                _ => true,
             }
-            let mut reader = item.reader(ReadOptions::with_password(password))?;
-            io::copy(&mut reader, &mut file)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+    log::debug!("start: {}", path.display());
+    let file = match item.header().data_kind() {
+        DataKind::File if *atomic => {
+            let (file, temp) = out.create_temp_file(&relative_path)?;
+            let temp = TempFile {
+                out,
+                path: Some(temp),
+            };
+            let file = write_file(file, item, read_options, keep_options, *fsync)?;
+            restore_metadata(
+                &out.entry_of(temp.path(), file)?,
+                item,
+                keep_options,
+                owner_options,
+                same_owner,
+            )?;
+            temp.persist(&relative_path)?;
+            if *fsync {
+                out.sync_parent(&relative_path)?;
+            }
+            log::debug!("end: {}", path.display());
+            return Ok(());
+        }
+        DataKind::File => {
+            let file = out.create_file(&relative_path, overwrite)?;
+            Some(write_file(file, item, read_options, keep_options, *fsync)?)
         }
         DataKind::Directory => {
-            fs::create_dir_all(&path)?;
+            out.create_dir_all(&relative_path)?;
+            log::debug!("end: {}", path.display());
+            return Ok(());
         }
         DataKind::SymbolicLink => {
-            let reader = item.reader(ReadOptions::with_password(password))?;
-            let original = io::read_to_string(reader)?;
-            let original = if let Some(substitutions) = path_transformers {
-                substitutions.apply(original, true, false)
-            } else {
-                original
-            };
-            let original = EntryReference::from_lossy(original);
-            if !allow_unsafe_links && is_unsafe_link(&original) {
-                log::warn!("Skipped extract symlink that contains unsafe link. if you need to extract it, use with `--allow-unsafe-links`");
-                return Ok(());
-            }
+            let original = link.expect("the reference of a link is read before");
             if overwrite && fs::symlink_metadata(&path).is_ok() {
-                utils::fs::remove_path_all(&path)?;
+                out.remove_all(&relative_path)?;
             }
-            utils::fs::symlink(original, &path)?;
+            out.symlink(original.as_path(), &relative_path)?;
+            None
         }
         DataKind::HardLink => {
-            let reader = item.reader(ReadOptions::with_password(password))?;
-            let original = io::read_to_string(reader)?;
-            let original = if let Some(substitutions) = path_transformers {
-                substitutions.apply(original, true, false)
+            let original = link.expect("the reference of a link is read before");
+            let target = resolve_from_parent(&relative_path, original.as_path());
+            if overwrite && path.exists() {
+                out.remove_all(&relative_path)?;
+            }
+            if is_inside(&target) {
+                out.hard_link(&target, &relative_path)?;
             } else {
-                original
-            };
-            let original = EntryReference::from_lossy(original);
-            if !allow_unsafe_links && is_unsafe_link(&original) {
-                log::warn!("Skipped extract hardlink that contains unsafe link, if you need to extract it, use with `--allow-unsafe-links`");
-                return Ok(());
+                // The link may point anywhere, so resolve it like the user asked for.
+                out.hard_link_outside(original.as_path(), &relative_path)?;
             }
-            let mut original = Cow::from(original.as_path());
-            if let Some(parent) = path.parent() {
-                original = Cow::from(parent.join(original));
+            None
+        }
+        DataKind::Whiteout => unreachable!("whiteout entries are applied before"),
+        kind => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{}: Unsupported entry kind {kind:?}", item.header().path()),
+            ))
+        }
+    };
+    let entry = match file {
+        Some(file) => out.entry_of(&relative_path, file)?,
+        None => out.entry(&relative_path)?,
+    };
+    restore_metadata(&entry, item, keep_options, owner_options, same_owner)?;
+    log::debug!("end: {}", path.display());
+    Ok(())
+}
+
+/// A file being extracted with `--atomic`, which is removed when dropped unless it was renamed
+/// into place.
+struct TempFile<'a> {
+    out: &'a Beneath,
+    path: Option<PathBuf>,
+}
+
+impl TempFile<'_> {
+    fn path(&self) -> &Path {
+        self.path
+            .as_deref()
+            .expect("temporary file is not persisted yet")
+    }
+
+    /// Renames the file to `to`, replacing the existing one.
+    fn persist(mut self, to: &Path) -> io::Result<()> {
+        self.out.rename(self.path(), to)?;
+        self.path = None;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile<'_> {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            if let Err(e) = self.out.remove_all(path) {
+                log::warn!(
+                    "failed to remove temporary file {}: {e}",
+                    self.out.path(path).display()
+                );
             }
-            if overwrite && path.exists() {
-                utils::fs::remove_path_all(&path)?;
+        }
+    }
+}
+
+/// Restores the metadata of extracted directories, deepest first, so that extracting their
+/// contents does not change their timestamps and a read-only mode does not prevent it.
+fn restore_directories<T>(
+    mut items: Vec<NormalEntry<T>>,
+    out: &Beneath,
+    args: &OutputOption,
+) -> io::Result<()>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    items.sort_by_key(|it| Reverse(it.header().path().as_path().components().count()));
+    for item in items {
+        let Some(relative_path) = relative_output_path(&item, args) else {
+            continue;
+        };
+        // Removed by a later deletion marker, or replaced by something that is not a
+        // directory, whose metadata must be left alone.
+        if !fs::symlink_metadata(out.path(&relative_path)).is_ok_and(|it| it.is_dir()) {
+            continue;
+        }
+        let entry = out.entry(&relative_path)?;
+        // Before the mode, which may deny access to the directory.
+        if args.keep_options.keep_timestamp {
+            let metadata = item.metadata();
+            entry.set_times(utils::fs::PathTimes {
+                accessed: metadata.accessed_time(),
+                modified: metadata.modified_time(),
+                created: metadata.created_time(),
+            })?;
+        }
+        restore_metadata(
+            &entry,
+            &item,
+            &args.keep_options,
+            &args.owner_options,
+            args.same_owner,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the contents of `item` to `file`, optionally flushing them to the disk, and returns
+/// the file to restore its metadata through.
+fn write_file<T>(
+    mut file: fs::File,
+    item: &NormalEntry<T>,
+    read_options: &ReadOptions,
+    keep_options: &KeepOptions,
+    fsync: bool,
+) -> io::Result<fs::File>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    let mut reader = entry_reader(item, read_options)?;
+    io::copy(&mut reader, &mut file)?;
+    if keep_options.keep_timestamp {
+        file.set_times(file_times(item))?;
+    }
+    if fsync {
+        file.sync_all()?;
+    }
+    Ok(file)
+}
+
+/// Returns `true` if `item` was modified after the `existing` file.
+fn is_newer<T>(item: &NormalEntry<T>, existing: &fs::Metadata) -> bool {
+    match (item.metadata().modified_time(), existing.modified()) {
+        (Some(modified), Ok(existing)) => modified > existing,
+        _ => true,
+    }
+}
+
+/// Returns the path below `out` to move the existing file at `path` to before replacing it.
+fn backup_path(out: &Beneath, path: &Path, control: BackupControl) -> io::Result<PathBuf> {
+    let mut backup = path.as_os_str().to_owned();
+    match control {
+        BackupControl::Simple => backup.push("~"),
+        BackupControl::Numbered => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let mut last = 0;
+            for entry_name in out.read_dir(path.parent().unwrap_or_else(|| "".as_ref()))? {
+                let n = entry_name
+                    .to_string_lossy()
+                    .strip_prefix(name.as_ref())
+                    .and_then(|it| it.strip_prefix(".~"))
+                    .and_then(|it| it.strip_suffix('~'))
+                    .and_then(|it| it.parse::<u64>().ok());
+                last = last.max(n.unwrap_or(0));
             }
-            fs::hard_link(original, &path)?;
+            backup.push(format!(".~{}~", last + 1));
         }
     }
+    Ok(PathBuf::from(backup))
+}
+
+fn file_times<T>(item: &NormalEntry<T>) -> fs::FileTimes {
+    let mut times = fs::FileTimes::new();
+    if let Some(accessed) = item.metadata().accessed_time() {
+        times = times.set_accessed(accessed);
+    }
+    if let Some(modified) = item.metadata().modified_time() {
+        times = times.set_modified(modified);
+    }
+    #[cfg(any(windows, target_os = "macos"))]
+    if let Some(created) = item.metadata().created_time() {
+        times = times.set_created(created);
+    }
+    times
+}
+
+/// Applies the owner, permissions, extended attributes and ACL of `item` to `entry`.
+fn restore_metadata<T>(
+    entry: &BeneathEntry,
+    item: &NormalEntry<T>,
+    keep_options: &KeepOptions,
+    owner_options: &OwnerOptions,
+    same_owner: bool,
+) -> io::Result<()>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    let permissions = if keep_options.keep_permission {
+        item.metadata()
+            .permission()
+            .and_then(|p| permissions(p, owner_options))
+    } else {
+        None
+    };
     #[cfg(unix)]
     if let Some((p, u, g)) = permissions {
-        use std::os::unix::fs::PermissionsExt;
         if same_owner {
-            match chown(&path, u, g) {
+            match entry.chown(u, g) {
                 Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-                    log::warn!("failed to restore owner of {}: {}", path.display(), e)
+                    log::warn!(
+                        "failed to restore owner of {}: {}",
+                        entry.path().display(),
+                        e
+                    )
                 }
                 r => r?,
             }
         }
-        fs::set_permissions(&path, fs::Permissions::from_mode(p.permissions().into()))?;
+        entry.chmod(p.permissions().into())?;
     };
     #[cfg(windows)]
     if let Some((p, u, g)) = permissions {
         if same_owner {
-            chown(&path, u, g)?;
+            chown(entry.path(), u, g)?;
         }
-        utils::os::windows::fs::chmod(&path, p.permissions())?;
+        utils::os::windows::fs::chmod(entry.path(), p.permissions())?;
     }
     #[cfg(not(any(unix, windows)))]
     if let Some(_) = permissions {
@@ -522,7 +1037,7 @@ where
     }
     #[cfg(unix)]
     if keep_options.keep_xattr {
-        utils::os::unix::fs::xattrs::set_xattrs(&path, item.xattrs())?;
+        entry.set_xattrs(item.xattrs())?;
     }
     #[cfg(not(unix))]
     if keep_options.keep_xattr {
@@ -538,20 +1053,30 @@ where
         ))]
         if keep_options.keep_acl {
             use crate::chunk::{acl_convert_current_platform, AcePlatform, Acl};
+            use crate::command::commons::map_acl_owners;
             use crate::ext::*;
             use itertools::Itertools;
 
+            // Symbolic links have no ACL of their own.
+            #[cfg(unix)]
+            let path = entry.fd_path();
+            #[cfg(windows)]
+            let path = Some(entry.path());
             let platform = AcePlatform::CURRENT;
             let acls = item.acl()?;
-            if let Some((platform, acl)) = acls.into_iter().find_or_first(|(p, _)| p.eq(&platform))
+            if let Some((path, (platform, acl))) =
+                path.zip(acls.into_iter().find_or_first(|(p, _)| p.eq(&platform)))
             {
                 if !acl.is_empty() {
                     utils::acl::set_facl(
-                        &path,
-                        acl_convert_current_platform(Acl {
-                            platform,
-                            entries: acl,
-                        }),
+                        path,
+                        acl_convert_current_platform(map_acl_owners(
+                            Acl {
+                                platform,
+                                entries: acl,
+                            },
+                            owner_options,
+                        )),
                     )?;
                 }
             }
@@ -570,10 +1095,54 @@ where
     if keep_options.keep_acl {
         log::warn!("Please enable `acl` feature and rebuild and install pna.");
     }
-    log::debug!("end: {}", path.display());
     Ok(())
 }
 
+/// Resolves the path on the filesystem of an entry, or `None` if the entry is skipped
+/// because it has fewer path elements than `strip_components`.
+pub(crate) fn output_path<'a>(
+    item_path: &'a Path,
+    strip_components: Option<usize>,
+    path_transformers: &Option<PathTransformers>,
+    out_dir: Option<&Path>,
+) -> Option<Cow<'a, Path>> {
+    let item_path = if let Some(strip_count) = strip_components {
+        if item_path.components().count() <= strip_count {
+            return None;
+        }
+        Cow::from(PathBuf::from_iter(item_path.components().skip(strip_count)))
+    } else {
+        Cow::from(item_path)
+    };
+    let item_path = if let Some(transformers) = path_transformers {
+        Cow::from(PathBuf::from(transformers.apply(
+            item_path.to_string_lossy(),
+            false,
+            false,
+        )))
+    } else {
+        item_path
+    };
+    Some(if let Some(out_dir) = out_dir {
+        Cow::from(out_dir.join(item_path))
+    } else {
+        item_path
+    })
+}
+
+/// Returns the reader of the entry, reporting the entry path when it cannot be decrypted.
+pub(crate) fn entry_reader<'a, T>(
+    item: &'a NormalEntry<T>,
+    read_options: &ReadOptions,
+) -> io::Result<impl Read + 'a>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    item.reader(read_options)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", item.header().path())))
+}
+
 fn permissions<'p>(
     permission: &'p Permission,
     owner_options: &'_ OwnerOptions,
@@ -581,18 +1150,18 @@ fn permissions<'p>(
     let user = if let Some(uid) = owner_options.uid {
         User::from_uid(uid.into())
     } else {
-        search_owner(
-            owner_options.uname.as_deref().unwrap_or(permission.uname()),
-            permission.uid(),
-        )
+        let (uname, uid) = owner_options
+            .owner_map
+            .map(permission.uname(), permission.uid());
+        search_owner(owner_options.uname.as_deref().unwrap_or(uname), uid)
     };
     let group = if let Some(gid) = owner_options.gid {
         Group::from_gid(gid.into())
     } else {
-        search_group(
-            owner_options.gname.as_deref().unwrap_or(permission.gname()),
-            permission.gid(),
-        )
+        let (gname, gid) = owner_options
+            .group_map
+            .map(permission.gname(), permission.gid());
+        search_group(owner_options.gname.as_deref().unwrap_or(gname), gid)
     };
     Some((permission, user.ok(), group.ok()))
 }
@@ -613,6 +1182,49 @@ fn search_group(name: &str, id: u64) -> io::Result<Group> {
     Group::from_gid((id as u32).into())
 }
 
+/// Reads the reference of a link entry, with the path transformers applied.
+fn link_reference<T>(
+    item: &NormalEntry<T>,
+    read_options: &ReadOptions,
+    path_transformers: &Option<PathTransformers>,
+) -> io::Result<EntryReference>
+where
+    T: AsRef<[u8]>,
+    pna::RawChunk<T>: Chunk,
+{
+    let reader = entry_reader(item, read_options)?;
+    let original = io::read_to_string(reader)?;
+    let original = if let Some(substitutions) = path_transformers {
+        substitutions.apply(original, true, false)
+    } else {
+        original
+    };
+    Ok(EntryReference::from_lossy(original))
+}
+
+/// Returns `true`, warning about it, if the link of `kind` to `original` must not be created
+/// without `--allow-unsafe-links`.
+fn skips_unsafe_link(kind: DataKind, original: &EntryReference) -> bool {
+    match kind {
+        DataKind::SymbolicLink if is_unsafe_link(original) => {
+            log::warn!("Skipped extract symlink that contains unsafe link. if you need to extract it, use with `--allow-unsafe-links`");
+            true
+        }
+        DataKind::HardLink if is_unsafe_link(original) => {
+            log::warn!("Skipped extract hardlink that contains unsafe link, if you need to extract it, use with `--allow-unsafe-links`");
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the relative `path` stays inside the directory it is relative to.
+#[inline]
+fn is_inside(path: &Path) -> bool {
+    path.components()
+        .all(|it| matches!(it, Component::Normal(_)))
+}
+
 #[inline]
 fn is_unsafe_link(reference: &EntryReference) -> bool {
     reference.as_path().components().any(|it| {
@@ -0,0 +1,352 @@
+use crate::{
+    cli::{CipherAlgorithmArgs, CompressionAlgorithmArgs, HashAlgorithmArgs, PasswordArgs},
+    command::{ask_password, check_password, commons::entry_option, Command},
+    utils::{self, fmt::DurationDisplay, reference_from_parent},
+};
+use clap::{Parser, ValueEnum, ValueHint};
+use pna::{
+    Archive, EntryBuilder, EntryName, EntryReference, ExtendedAttribute, NormalEntry, Permission,
+    WriteOptions,
+};
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, prelude::*, SeekFrom},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+#[derive(Parser, Clone, Debug)]
+pub(crate) struct ImportCommand {
+    #[arg(long, help = "Overwrite file")]
+    pub(crate) overwrite: bool,
+    #[arg(long, help = "Create an archive in solid mode")]
+    pub(crate) solid: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "Format of the source archive. If not given it's detected from the contents"
+    )]
+    pub(crate) from: Option<SourceFormat>,
+    #[command(flatten)]
+    pub(crate) compression: CompressionAlgorithmArgs,
+    #[command(flatten)]
+    pub(crate) cipher: CipherAlgorithmArgs,
+    #[command(flatten)]
+    pub(crate) hash: HashAlgorithmArgs,
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[arg(help = "Archive file path to create", value_hint = ValueHint::FilePath)]
+    pub(crate) archive: PathBuf,
+    #[arg(help = "Source tar or zip archive", value_hint = ValueHint::FilePath)]
+    pub(crate) source: PathBuf,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, ValueEnum)]
+#[value(rename_all = "lower")]
+pub(crate) enum SourceFormat {
+    /// tar archive, optionally compressed with gzip or zstd
+    Tar,
+    /// zip archive
+    Zip,
+}
+
+impl Command for ImportCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        import_archive(self)
+    }
+}
+
+fn import_archive(args: ImportCommand) -> anyhow::Result<()> {
+    let password = ask_password(args.password)?;
+    check_password(&password, &args.cipher);
+    let start = Instant::now();
+    log::info!(
+        "Import {} into {}",
+        args.source.display(),
+        args.archive.display()
+    );
+    let write_option = entry_option(
+        args.compression,
+        args.cipher,
+        args.hash,
+        password.as_deref(),
+    );
+    let option = if args.solid {
+        WriteOptions::store()
+    } else {
+        write_option.clone()
+    };
+
+    let mut source = fs::File::open(&args.source)?;
+    let magic = read_magic(&mut source)?;
+    let format = args.from.unwrap_or(if magic.starts_with(b"PK") {
+        SourceFormat::Zip
+    } else {
+        SourceFormat::Tar
+    });
+
+    let file = utils::fs::file_create(&args.archive, args.overwrite)?;
+    if args.solid {
+        let mut writer = Archive::write_solid_header(file, write_option)?;
+        import_entries(source, &magic, format, &option, |entry| {
+            writer.add_entry(entry).map(|_| ())
+        })?;
+        writer.finalize()?;
+    } else {
+        let mut writer = Archive::write_header(file)?;
+        import_entries(source, &magic, format, &option, |entry| {
+            writer.add_entry(entry).map(|_| ())
+        })?;
+        writer.finalize()?;
+    }
+    log::info!(
+        "Successfully imported an archive in {}",
+        DurationDisplay(start.elapsed())
+    );
+    Ok(())
+}
+
+fn read_magic(source: &mut fs::File) -> io::Result<Vec<u8>> {
+    let mut magic = Vec::with_capacity(4);
+    Read::by_ref(source).take(4).read_to_end(&mut magic)?;
+    source.seek(SeekFrom::Start(0))?;
+    Ok(magic)
+}
+
+fn import_entries(
+    source: fs::File,
+    magic: &[u8],
+    format: SourceFormat,
+    option: &WriteOptions,
+    add_entry: impl FnMut(NormalEntry) -> io::Result<()>,
+) -> io::Result<()> {
+    match format {
+        SourceFormat::Zip => import_zip(source, option, add_entry),
+        SourceFormat::Tar => {
+            let reader = io::BufReader::with_capacity(64 * 1024, source);
+            match magic {
+                [0x1f, 0x8b, ..] => {
+                    import_tar(flate2::bufread::GzDecoder::new(reader), option, add_entry)
+                }
+                [0x28, 0xb5, 0x2f, 0xfd] => import_tar(
+                    zstd::stream::read::Decoder::with_buffer(reader)?,
+                    option,
+                    add_entry,
+                ),
+                _ => import_tar(reader, option, add_entry),
+            }
+        }
+    }
+}
+
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+fn import_tar(
+    reader: impl Read,
+    option: &WriteOptions,
+    mut add_entry: impl FnMut(NormalEntry) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut pax = Vec::new();
+        if let Some(extensions) = entry.pax_extensions()? {
+            for extension in extensions {
+                let extension = extension?;
+                if let Ok(key) = extension.key() {
+                    pax.push((key.to_owned(), extension.value_bytes().to_vec()));
+                }
+            }
+        }
+        let pax_value = |key: &str| {
+            pax.iter()
+                .find(|(k, _)| k == key)
+                .and_then(|(_, v)| std::str::from_utf8(v).ok())
+        };
+
+        let path = entry.path()?.into_owned();
+        let name = EntryName::from_lossy(&path);
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let link_name = entry.link_name()?.map(Cow::into_owned);
+        let permission = Permission::new(
+            pax_value("uid")
+                .and_then(|it| it.parse().ok())
+                .map_or_else(|| header.uid(), Ok)?,
+            pax_value("uname")
+                .map(Into::into)
+                .or_else(|| header.username().ok().flatten().map(Into::into))
+                .unwrap_or_default(),
+            pax_value("gid")
+                .and_then(|it| it.parse().ok())
+                .map_or_else(|| header.gid(), Ok)?,
+            pax_value("gname")
+                .map(Into::into)
+                .or_else(|| header.groupname().ok().flatten().map(Into::into))
+                .unwrap_or_default(),
+            header.mode()? as u16,
+        );
+        let mtime = pax_value("mtime")
+            .and_then(parse_pax_time)
+            .map_or_else(|| header.mtime().map(Duration::from_secs), Ok)?;
+        let gnu = header.as_gnu();
+        let atime = pax_value("atime")
+            .and_then(parse_pax_time)
+            .or_else(|| gnu.and_then(|it| it.atime().ok()).map(Duration::from_secs));
+        let ctime = pax_value("ctime")
+            .and_then(parse_pax_time)
+            .or_else(|| gnu.and_then(|it| it.ctime().ok()).map(Duration::from_secs));
+
+        let mut builder = match (entry_type, link_name) {
+            (tar::EntryType::Directory, _) => EntryBuilder::new_dir(name),
+            (tar::EntryType::Symlink, Some(link_name)) => {
+                EntryBuilder::new_symbolic_link(name, EntryReference::from_lossy(link_name))?
+            }
+            (tar::EntryType::Link, Some(link_name)) => {
+                // tar names the target from the root, while hard link entries refer to it
+                // from the directory of the link.
+                let target = EntryName::from_lossy(link_name);
+                let reference = reference_from_parent(name.as_path(), target.as_path());
+                EntryBuilder::new_hard_link(name, EntryReference::from_lossy(reference))?
+            }
+            (
+                tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::GNUSparse,
+                _,
+            ) => {
+                let mut builder = EntryBuilder::new_file(name, option)?;
+                io::copy(&mut entry, &mut builder)?;
+                builder
+            }
+            (entry_type, _) => {
+                log::warn!(
+                    "Skip unsupported entry type {entry_type:?}: {}",
+                    path.display()
+                );
+                continue;
+            }
+        };
+        builder.permission(permission).modified(mtime);
+        if let Some(atime) = atime {
+            builder.accessed(atime);
+        }
+        if let Some(ctime) = ctime {
+            builder.created(ctime);
+        }
+        for (key, value) in &pax {
+            if let Some(name) = key.strip_prefix(PAX_XATTR_PREFIX) {
+                builder.add_xattr(ExtendedAttribute::new(name.into(), value.clone()));
+            }
+        }
+        add_entry(builder.build()?)?;
+    }
+    Ok(())
+}
+
+/// Parses a PAX timestamp of the form `seconds[.fraction]`.
+fn parse_pax_time(value: &str) -> Option<Duration> {
+    let (secs, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let secs = secs.parse().ok()?;
+    let nanos = fraction
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .try_fold(0u32, |acc, it| {
+            it.is_ascii_digit().then(|| acc * 10 + u32::from(it - b'0'))
+        })?;
+    Some(Duration::new(secs, nanos))
+}
+
+fn import_zip(
+    reader: fs::File,
+    option: &WriteOptions,
+    mut add_entry: impl FnMut(NormalEntry) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = EntryName::from(file.name());
+        let mtime = zip_mtime(&file);
+        let mode = file.unix_mode();
+        let owner = file.extra_data().and_then(zip_unix_owner);
+
+        let mut builder = if file.is_dir() {
+            EntryBuilder::new_dir(name)
+        } else if file.is_symlink() {
+            let target = io::read_to_string(&mut file)?;
+            EntryBuilder::new_symbolic_link(name, EntryReference::from(target))?
+        } else {
+            let mut builder = EntryBuilder::new_file(name, option)?;
+            io::copy(&mut file, &mut builder)?;
+            builder
+        };
+        if let Some(mtime) = mtime {
+            builder.modified(mtime);
+        }
+        if let Some(mode) = mode {
+            let (uid, gid) = owner.unwrap_or_default();
+            builder.permission(Permission::new(
+                uid,
+                String::new(),
+                gid,
+                String::new(),
+                mode as u16,
+            ));
+        }
+        add_entry(builder.build()?)?;
+    }
+    Ok(())
+}
+
+fn zip_mtime<R: Read>(file: &zip::read::ZipFile<'_, R>) -> Option<Duration> {
+    file.extra_data_fields()
+        .find_map(|it| match it {
+            zip::ExtraField::ExtendedTimestamp(ts) => ts.mod_time(),
+            _ => None,
+        })
+        .map(|it| Duration::from_secs(it.into()))
+        .or_else(|| {
+            let dt = file.last_modified()?;
+            let timestamp = chrono::NaiveDate::from_ymd_opt(
+                dt.year().into(),
+                dt.month().into(),
+                dt.day().into(),
+            )?
+            .and_hms_opt(dt.hour().into(), dt.minute().into(), dt.second().into())?
+            .and_utc()
+            .timestamp();
+            u64::try_from(timestamp).ok().map(Duration::from_secs)
+        })
+}
+
+/// Reads uid and gid from the Info-ZIP "new Unix" extra field (0x7875).
+fn zip_unix_owner(mut extra: &[u8]) -> Option<(u64, u64)> {
+    fn read_id(data: &[u8]) -> Option<(u64, &[u8])> {
+        let (&len, data) = data.split_first()?;
+        let len = usize::from(len);
+        if len > 8 || data.len() < len {
+            return None;
+        }
+        let (id, rest) = data.split_at(len);
+        let id = id
+            .iter()
+            .rev()
+            .fold(0u64, |acc, it| (acc << 8) | u64::from(*it));
+        Some((id, rest))
+    }
+
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let size = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+        let data = extra.get(4..4 + size)?;
+        if id == 0x7875 {
+            let (_version, data) = data.split_first()?;
+            let (uid, data) = read_id(data)?;
+            let (gid, _) = read_id(data)?;
+            return Some((uid, gid));
+        }
+        extra = &extra[4 + size..];
+    }
+    None
+}
@@ -2,9 +2,9 @@
 use crate::command::commons::run_read_entries_mem;
 use crate::{
     chunk,
-    cli::{FileArgs, PasswordArgs},
+    cli::{EntryFilter, FileArgs, PasswordArgs},
     command::{
-        ask_password,
+        ask_passwords,
         commons::{collect_split_archives, run_read_entries, Exclude},
         Command,
     },
@@ -46,6 +46,8 @@ use tabled::{
     group(ArgGroup::new("unstable-acl").args(["show_acl"]).requires("unstable")),
     group(ArgGroup::new("unstable-private-chunk").args(["show_private"]).requires("unstable")),
     group(ArgGroup::new("unstable-format").args(["format"]).requires("unstable")),
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
+    group(ArgGroup::new("unstable-versions").args(["versions"]).requires("unstable")),
 )]
 pub(crate) struct ListCommand {
     #[arg(short, long, help = "Display extended file metadata as a table")]
@@ -87,6 +89,11 @@ pub(crate) struct ListCommand {
     hide_control_chars: bool,
     #[arg(long, help = "Display type indicator by entry kinds")]
     classify: bool,
+    #[arg(
+        long,
+        help = "Display every version of the files that were added to the archive multiple times, grouped by path (unstable)"
+    )]
+    versions: bool,
     #[arg(
         long,
         help = "Process only files or directories that match the specified pattern. Note that exclusions specified with --exclude take precedence over inclusions"
@@ -96,6 +103,12 @@ pub(crate) struct ListCommand {
     exclude: Option<Vec<String>>,
     #[arg(long, help = "Read exclude files from given path (unstable)", value_hint = ValueHint::FilePath)]
     exclude_from: Option<PathBuf>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    filter: Option<EntryFilter>,
     #[command(flatten)]
     pub(crate) password: PasswordArgs,
     #[command(flatten)]
@@ -126,6 +139,9 @@ enum EntryType {
     Directory(String),
     SymbolicLink(String, String),
     HardLink(String, String),
+    Whiteout(String),
+    /// An entry of a kind added to the format after this version
+    Unknown(String, DataKind),
 }
 
 impl EntryType {
@@ -135,7 +151,9 @@ impl EntryType {
             EntryType::File(name)
             | EntryType::Directory(name)
             | EntryType::SymbolicLink(name, _)
-            | EntryType::HardLink(name, _) => name,
+            | EntryType::HardLink(name, _)
+            | EntryType::Whiteout(name)
+            | EntryType::Unknown(name, _) => name,
         }
     }
 }
@@ -153,6 +171,7 @@ struct TableRow {
     xattrs: Vec<ExtendedAttribute>,
     acl: HashMap<chunk::AcePlatform, Vec<chunk::Ace>>,
     privates: Vec<RawChunk>,
+    version: Option<usize>,
 }
 
 impl TableRow {
@@ -162,7 +181,7 @@ impl TableRow {
     }
 }
 
-impl<T> TryFrom<(&NormalEntry<T>, Option<&str>, Option<&SolidHeader>)> for TableRow
+impl<T> TryFrom<(&NormalEntry<T>, &ReadOptions, Option<&SolidHeader>)> for TableRow
 where
     T: AsRef<[u8]> + Clone,
     RawChunk<T>: Chunk,
@@ -171,7 +190,7 @@ where
     type Error = io::Error;
     #[inline]
     fn try_from(
-        (entry, password, solid): (&NormalEntry<T>, Option<&str>, Option<&SolidHeader>),
+        (entry, read_options, solid): (&NormalEntry<T>, &ReadOptions, Option<&SolidHeader>),
     ) -> Result<Self, Self::Error> {
         let header = entry.header();
         let metadata = entry.metadata();
@@ -205,19 +224,21 @@ where
                 DataKind::SymbolicLink => EntryType::SymbolicLink(
                     header.path().to_string(),
                     entry
-                        .reader(ReadOptions::with_password(password))
+                        .reader(read_options)
                         .and_then(io::read_to_string)
                         .unwrap_or_else(|_| "-".into()),
                 ),
                 DataKind::HardLink => EntryType::HardLink(
                     header.path().to_string(),
                     entry
-                        .reader(ReadOptions::with_password(password))
+                        .reader(read_options)
                         .and_then(io::read_to_string)
                         .unwrap_or_else(|_| "-".into()),
                 ),
                 DataKind::Directory => EntryType::Directory(header.path().to_string()),
                 DataKind::File => EntryType::File(header.path().to_string()),
+                DataKind::Whiteout => EntryType::Whiteout(header.path().to_string()),
+                kind => EntryType::Unknown(header.path().to_string(), kind),
             },
             xattrs: entry.xattrs().to_vec(),
             acl,
@@ -227,12 +248,13 @@ where
                 .filter(|it| it.ty() != chunk::faCe && it.ty() != chunk::faCl)
                 .map(|it| (*it).clone().into())
                 .collect::<Vec<_>>(),
+            version: None,
         })
     }
 }
 
 fn list_archive(args: ListCommand) -> anyhow::Result<()> {
-    let password = ask_password(args.password)?;
+    let passwords = ask_passwords(args.password)?;
     let options = ListOptions {
         long: args.long,
         header: args.header,
@@ -249,7 +271,9 @@ fn list_archive(args: ListCommand) -> anyhow::Result<()> {
         numeric_owner: args.numeric_owner,
         hide_control_chars: args.hide_control_chars,
         classify: args.classify,
+        versions: args.versions,
         format: args.format,
+        filter: args.filter,
     };
     let files_globs = GlobPatterns::new(&args.file.files)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
@@ -273,7 +297,7 @@ fn list_archive(args: ListCommand) -> anyhow::Result<()> {
             archives
                 .into_iter()
                 .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
-            password.as_deref(),
+            &passwords,
             files_globs,
             exclude,
             options,
@@ -281,7 +305,7 @@ fn list_archive(args: ListCommand) -> anyhow::Result<()> {
     }
     #[cfg(feature = "memmap")]
     {
-        run_list_archive_mem(archives, password.as_deref(), files_globs, exclude, options)
+        run_list_archive_mem(archives, &passwords, files_globs, exclude, options)
     }
 }
 
@@ -323,29 +347,45 @@ pub(crate) struct ListOptions {
     pub(crate) numeric_owner: bool,
     pub(crate) hide_control_chars: bool,
     pub(crate) classify: bool,
+    pub(crate) versions: bool,
     pub(crate) format: Option<Format>,
+    pub(crate) filter: Option<EntryFilter>,
+}
+
+impl ListOptions {
+    #[inline]
+    fn is_listed<T>(&self, entry: &NormalEntry<T>) -> bool {
+        self.filter.as_ref().is_none_or(|it| it.matches(entry))
+    }
 }
 
 pub(crate) fn run_list_archive(
     archive_provider: impl IntoIterator<Item = impl Read>,
-    password: Option<&str>,
+    passwords: &[String],
     files_globs: GlobPatterns,
     exclude: Exclude,
     args: ListOptions,
 ) -> anyhow::Result<()> {
+    let read_options = ReadOptions::with_passwords(passwords);
     let mut entries = Vec::new();
 
     run_read_entries(archive_provider, |entry| {
         match entry? {
             ReadEntry::Solid(solid) if args.solid => {
-                for entry in solid.entries(password)? {
-                    entries.push((&entry?, password, Some(solid.header())).try_into()?)
+                for entry in solid.entries_with_options(&read_options)? {
+                    let entry = entry?;
+                    if args.is_listed(&entry) {
+                        entries.push((&entry, &read_options, Some(solid.header())).try_into()?);
+                    }
                 }
             }
             ReadEntry::Solid(_) => {
                 log::warn!("This archive contain solid mode entry. if you need to show it use --solid option.");
             }
-            ReadEntry::Normal(item) => entries.push((&item, password, None).try_into()?),
+            ReadEntry::Normal(item) if args.is_listed(&item) => {
+                entries.push((&item, &read_options, None).try_into()?)
+            }
+            ReadEntry::Normal(_) => {}
         }
         Ok(())
     })?;
@@ -356,11 +396,12 @@ pub(crate) fn run_list_archive(
 #[cfg(feature = "memmap")]
 pub(crate) fn run_list_archive_mem(
     archives: Vec<std::fs::File>,
-    password: Option<&str>,
+    passwords: &[String],
     files_globs: GlobPatterns,
     exclude: Exclude,
     args: ListOptions,
 ) -> anyhow::Result<()> {
+    let read_options = ReadOptions::with_passwords(passwords);
     let mut entries = Vec::new();
     let mmaps = archives
         .into_iter()
@@ -371,14 +412,20 @@ pub(crate) fn run_list_archive_mem(
     run_read_entries_mem(archives, |entry| {
         match entry? {
             ReadEntry::Solid(solid) if args.solid => {
-                for entry in solid.entries(password)? {
-                    entries.push((&entry?, password, Some(solid.header())).try_into()?);
+                for entry in solid.entries_with_options(&read_options)? {
+                    let entry = entry?;
+                    if args.is_listed(&entry) {
+                        entries.push((&entry, &read_options, Some(solid.header())).try_into()?);
+                    }
                 }
             }
             ReadEntry::Solid(_) => {
                 log::warn!("This archive contain solid mode entry. if you need to show it use --solid option.");
             }
-            ReadEntry::Normal(item) => entries.push((&item, password, None).try_into()?),
+            ReadEntry::Normal(item) if args.is_listed(&item) => {
+                entries.push((&item, &read_options, None).try_into()?)
+            }
+            ReadEntry::Normal(_) => {}
         }
         Ok(())
     })?;
@@ -397,6 +444,11 @@ fn print_entries(
         .filter(|r| globs.is_empty() || globs.matches_any(r.entry_type.name()))
         .filter(|r| !exclude.excluded(r.entry_type.name()))
         .collect::<Vec<_>>();
+    let entries = if options.versions {
+        group_versions(entries)
+    } else {
+        entries
+    };
     match options.format {
         Some(Format::JsonL) => json_line_entries(entries),
         Some(Format::Table) => detail_list_entries(entries, options),
@@ -406,6 +458,24 @@ fn print_entries(
     }
 }
 
+/// Groups the rows by path in the order each path first appears, numbering the versions of
+/// each path from 1.
+fn group_versions(entries: Vec<TableRow>) -> Vec<TableRow> {
+    let mut groups = Vec::<Vec<TableRow>>::new();
+    let mut index = HashMap::new();
+    for mut entry in entries {
+        let i = *index
+            .entry(entry.entry_type.name().to_owned())
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        entry.version = Some(groups[i].len() + 1);
+        groups[i].push(entry);
+    }
+    groups.into_iter().flatten().collect()
+}
+
 struct SimpleListDisplay<'a> {
     entries: &'a [TableRow],
     options: &'a ListOptions,
@@ -416,6 +486,15 @@ impl<'a> Display for SimpleListDisplay<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         use core::fmt::Write;
         self.entries.iter().try_for_each(|path| {
+            if let Some(version) = path.version {
+                let time = match self.options.time_field {
+                    TimeField::Created => path.created,
+                    TimeField::Modified => path.modified,
+                    TimeField::Accessed => path.accessed,
+                }
+                .map_or_else(|| "-".into(), |d| datetime(self.options.time_format, d));
+                write!(f, "{version:>4}  {time:>12}  ")?;
+            }
             let name = path.entry_type.name();
             if self.options.hide_control_chars {
                 Display::fmt(&hide_control_chars(name), f)
@@ -425,6 +504,7 @@ impl<'a> Display for SimpleListDisplay<'a> {
             match &path.entry_type {
                 EntryType::Directory(_) if self.options.classify => f.write_char('/')?,
                 EntryType::SymbolicLink(_, _) if self.options.classify => f.write_char('@')?,
+                EntryType::Whiteout(_) if self.options.classify => f.write_char('%')?,
                 _ => (),
             };
             f.write_char('\n')
@@ -445,7 +525,7 @@ fn simple_list_entries(entries: Vec<TableRow>, options: ListOptions) {
 fn detail_list_entries(entries: impl IntoIterator<Item = TableRow>, options: ListOptions) {
     let underline = Color::new("\x1B[4m", "\x1B[0m");
     let reset = Color::new("\x1B[8m", "\x1B[0m");
-    let header = [
+    let mut header = vec![
         "Encryption",
         "Compression",
         "Permissions",
@@ -456,6 +536,9 @@ fn detail_list_entries(entries: impl IntoIterator<Item = TableRow>, options: Lis
         options.time_field.as_str(),
         "Name",
     ];
+    if options.versions {
+        header.push("Version");
+    }
     let mut acl_rows = Vec::new();
     let mut xattr_rows = Vec::new();
     let mut builder = TableBuilder::new();
@@ -475,7 +558,7 @@ fn detail_list_entries(entries: impl IntoIterator<Item = TableRow>, options: Lis
             || "-".into(),
             |it| it.owner_display(options.numeric_owner).to_string(),
         );
-        builder.push_record([
+        let mut record = vec![
             content.encryption,
             content.compression,
             paint_permission(&content.entry_type, permission_mode, has_xattr, has_acl),
@@ -497,7 +580,11 @@ fn detail_list_entries(entries: impl IntoIterator<Item = TableRow>, options: Lis
                     EntryType::SymbolicLink(name, link_to) if options.classify => {
                         format!("{name}@ -> {link_to}")
                     }
-                    EntryType::File(path) | EntryType::Directory(path) => path,
+                    EntryType::Whiteout(path) if options.classify => format!("{path}%"),
+                    EntryType::File(path)
+                    | EntryType::Directory(path)
+                    | EntryType::Whiteout(path)
+                    | EntryType::Unknown(path, _) => path,
                     EntryType::SymbolicLink(path, link_to) | EntryType::HardLink(path, link_to) => {
                         format!("{path} -> {link_to}")
                     }
@@ -508,7 +595,11 @@ fn detail_list_entries(entries: impl IntoIterator<Item = TableRow>, options: Lis
                     name
                 }
             },
-        ]);
+        ];
+        if let Some(version) = content.version {
+            record.push(version.to_string());
+        }
+        builder.push_record(record);
         if options.show_acl {
             let acl = content.acl.into_iter().flat_map(|(platform, ace)| {
                 ace.into_iter().map(move |it| chunk::AceWithPlatform {
@@ -650,6 +741,8 @@ fn kind_paint(kind: &EntryType) -> impl Display + 'static {
         EntryType::File(_) | EntryType::HardLink(_, _) => STYLE_HYPHEN.paint('.'),
         EntryType::Directory(_) => STYLE_DIR.paint('d'),
         EntryType::SymbolicLink(_, _) => STYLE_LINK.paint('l'),
+        EntryType::Whiteout(_) => STYLE_HYPHEN.paint('w'),
+        EntryType::Unknown(_, _) => STYLE_HYPHEN.paint('?'),
     }
 }
 
@@ -689,6 +782,8 @@ fn kind_char(kind: &EntryType) -> char {
         EntryType::File(_) | EntryType::HardLink(_, _) => '.',
         EntryType::Directory(_) => 'd',
         EntryType::SymbolicLink(_, _) => 'l',
+        EntryType::Whiteout(_) => 'w',
+        EntryType::Unknown(_, _) => '?',
     }
 }
 
@@ -739,6 +834,8 @@ struct FileInfo {
     accessed: String,
     acl: Vec<AclEntry>,
     xattr: Vec<XAttr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -805,6 +902,7 @@ fn json_line_entries(entries: impl IntoParallelIterator<Item = TableRow>) {
                         value: base64::engine::general_purpose::STANDARD.encode(x.value()),
                     })
                     .collect(),
+                version: it.version,
             }
         })
         .collect::<Vec<_>>();
@@ -878,6 +976,8 @@ fn tree_entries(entries: Vec<TableRow>, options: ListOptions) {
         EntryType::Directory(name) => (name.as_str(), DataKind::Directory),
         EntryType::SymbolicLink(name, _) => (name.as_str(), DataKind::SymbolicLink),
         EntryType::HardLink(name, _) => (name.as_str(), DataKind::HardLink),
+        EntryType::Whiteout(name) => (name.as_str(), DataKind::Whiteout),
+        EntryType::Unknown(name, kind) => (name.as_str(), *kind),
     });
     let map = build_tree_map(entries);
     let tree = build_term_tree(&map, Cow::Borrowed(""), None, DataKind::Directory, &options);
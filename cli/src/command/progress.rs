@@ -0,0 +1,263 @@
+use crate::{cli::ProgressArgs, utils::fmt::DurationDisplay};
+use bytesize::ByteSize;
+use pna::{EntryName, ProgressObserver};
+use std::{
+    fmt::Write as _,
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+const BAR_WIDTH: usize = 30;
+const NAME_WIDTH: usize = 40;
+
+/// Reports the progress of a command given `--progress` or `--totals`.
+///
+/// The progress is drawn as a line on stderr when it is a terminal, and logged every few
+/// seconds otherwise. The bar and the estimated remaining time are shown only when the
+/// total amount of work is known in advance.
+#[derive(Debug)]
+pub(crate) struct ProgressReporter {
+    args: ProgressArgs,
+    total_entries: Option<u64>,
+    total_bytes: Option<u64>,
+    terminal: bool,
+    start: Instant,
+    entries: AtomicU64,
+    bytes: AtomicU64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    current: String,
+    last_report: Instant,
+    drawn: bool,
+}
+
+impl ProgressReporter {
+    /// Returns a reporter for `args`, or `None` if neither `--progress` nor `--totals` is
+    /// given.
+    #[inline]
+    pub(crate) fn new(
+        args: ProgressArgs,
+        total_entries: Option<u64>,
+        total_bytes: Option<u64>,
+    ) -> Option<Arc<Self>> {
+        (args.progress || args.totals).then(|| {
+            let start = Instant::now();
+            Arc::new(Self {
+                args,
+                total_entries,
+                total_bytes,
+                terminal: io::stderr().is_terminal(),
+                start,
+                entries: AtomicU64::new(0),
+                bytes: AtomicU64::new(0),
+                state: Mutex::new(State {
+                    current: String::new(),
+                    last_report: start,
+                    drawn: false,
+                }),
+            })
+        })
+    }
+
+    /// Clears the progress line and prints the totals if requested, describing the bytes
+    /// as `processed`, such as "written" or "read".
+    pub(crate) fn finish(&self, processed: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.drawn {
+            let _ = write!(io::stderr(), "\r\x1b[2K");
+            state.drawn = false;
+        }
+        if self.args.totals {
+            let elapsed = self.start.elapsed();
+            let bytes = self.bytes.load(Ordering::Relaxed);
+            eprintln!(
+                "Total bytes {processed}: {bytes} ({}, {}/s) in {:#}",
+                ByteSize(bytes),
+                ByteSize(rate(bytes, elapsed)),
+                DurationDisplay(elapsed)
+            );
+        }
+    }
+
+    fn report(&self) {
+        if !self.args.progress {
+            return;
+        }
+        // Skip reporting rather than waiting for another thread that is reporting.
+        let Ok(mut state) = self.state.try_lock() else {
+            return;
+        };
+        let interval = if self.terminal {
+            DRAW_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        if state.last_report.elapsed() < interval {
+            return;
+        }
+        state.last_report = Instant::now();
+        let line = self.line(self.start.elapsed());
+        if self.terminal {
+            let _ = write!(
+                io::stderr(),
+                "\r\x1b[2K{line} {}",
+                truncate_start(&state.current, NAME_WIDTH)
+            );
+            state.drawn = true;
+        } else {
+            log::info!("{line}");
+        }
+    }
+
+    fn line(&self, elapsed: Duration) -> String {
+        let entries = self.entries.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let fraction = match (self.total_bytes, self.total_entries) {
+            (Some(total), _) if total > 0 => Some(bytes as f64 / total as f64),
+            (_, Some(total)) if total > 0 => Some(entries as f64 / total as f64),
+            _ => None,
+        }
+        .map(|it| it.min(1.0));
+        let mut line = String::new();
+        if let Some(fraction) = fraction {
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            let _ = write!(
+                line,
+                "[{}{}] {:3.0}% ",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                fraction * 100.0
+            );
+        }
+        match self.total_entries {
+            Some(total) => {
+                let _ = write!(line, "{entries}/{total} entries, ");
+            }
+            None if entries > 0 => {
+                let _ = write!(line, "{entries} entries, ");
+            }
+            None => {}
+        }
+        let _ = write!(
+            line,
+            "{}, {}/s",
+            ByteSize(bytes),
+            ByteSize(rate(bytes, elapsed))
+        );
+        if let Some(fraction) = fraction.filter(|it| *it > 0.0) {
+            let remaining = elapsed.mul_f64((1.0 - fraction) / fraction);
+            let _ = write!(line, ", ETA {:#}", DurationDisplay(remaining));
+        }
+        line
+    }
+}
+
+impl ProgressObserver for ProgressReporter {
+    #[inline]
+    fn entry_started(&self, name: &EntryName) {
+        if let Ok(mut state) = self.state.try_lock() {
+            state.current.clear();
+            state.current.push_str(name.as_str());
+        }
+    }
+
+    #[inline]
+    fn entry_finished(&self, _name: &EntryName) {
+        self.entries.fetch_add(1, Ordering::Relaxed);
+        self.report();
+    }
+
+    #[inline]
+    fn bytes_read(&self, len: u64) {
+        self.bytes.fetch_add(len, Ordering::Relaxed);
+        self.report();
+    }
+
+    #[inline]
+    fn bytes_written(&self, len: u64) {
+        self.bytes.fetch_add(len, Ordering::Relaxed);
+        self.report();
+    }
+}
+
+/// Returns `progress` as an observer to give to archives and read options.
+#[inline]
+pub(crate) fn observer(
+    progress: &Option<Arc<ProgressReporter>>,
+) -> Option<Arc<dyn ProgressObserver>> {
+    progress.clone().map(|it| it as _)
+}
+
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    (bytes as f64 / elapsed.as_secs_f64().max(0.001)) as u64
+}
+
+/// Keeps the last `width` characters of `s`, where the most specific part of a path is.
+fn truncate_start(s: &str, width: usize) -> String {
+    let len = s.chars().count();
+    if len <= width {
+        return s.into();
+    }
+    let tail = s.chars().skip(len - width + 3).collect::<String>();
+    format!("...{tail}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reporter(total_entries: Option<u64>, total_bytes: Option<u64>) -> Arc<ProgressReporter> {
+        let args = ProgressArgs {
+            progress: true,
+            totals: false,
+        };
+        ProgressReporter::new(args, total_entries, total_bytes).unwrap()
+    }
+
+    #[test]
+    fn progress_line_with_total() {
+        let progress = reporter(Some(4), None);
+        progress.entry_finished(&"a".into());
+        progress.bytes_written(2048);
+        assert_eq!(
+            progress.line(Duration::from_secs(2)),
+            format!(
+                "[{}{}]  25% 1/4 entries, {}, {}/s, ETA 6s",
+                "#".repeat(7),
+                "-".repeat(23),
+                ByteSize(2048),
+                ByteSize(1024)
+            )
+        );
+    }
+
+    #[test]
+    fn progress_line_without_total() {
+        let progress = reporter(None, None);
+        progress.bytes_read(1024);
+        assert_eq!(
+            progress.line(Duration::from_secs(1)),
+            format!("{}, {}/s", ByteSize(1024), ByteSize(1024))
+        );
+    }
+
+    #[test]
+    fn truncate_name() {
+        assert_eq!(truncate_start("abc", 5), "abc");
+        assert_eq!(truncate_start("abcdefgh", 5), "...gh");
+    }
+
+    #[test]
+    fn no_reporter_without_flags() {
+        assert!(ProgressReporter::new(ProgressArgs::default(), None, None).is_none());
+    }
+}
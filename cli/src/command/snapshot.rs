@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// State of the files archived by the last incremental backup, stored in the snapshot file
+/// given to `pna create --incremental`.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    version: u32,
+    files: BTreeMap<String, FileState>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+struct FileState {
+    dev: u64,
+    ino: u64,
+    mtime: u64,
+    mtime_nsec: u32,
+    size: u64,
+}
+
+impl FileState {
+    fn new(meta: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (dev, ino) = {
+            use std::os::unix::fs::MetadataExt;
+            (meta.dev(), meta.ino())
+        };
+        #[cfg(not(unix))]
+        let (dev, ino) = (0, 0);
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Self {
+            dev,
+            ino,
+            mtime: mtime.as_secs(),
+            mtime_nsec: mtime.subsec_nanos(),
+            size: meta.len(),
+        }
+    }
+}
+
+impl Snapshot {
+    const VERSION: u32 = 1;
+
+    /// Loads a snapshot file. A missing file is treated as an empty snapshot, so the
+    /// first incremental backup archives everything.
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let snapshot = serde_json::from_slice::<Self>(&bytes)?;
+                if snapshot.version != Self::VERSION {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Unsupported snapshot version {} in {}",
+                            snapshot.version,
+                            path.display()
+                        ),
+                    ));
+                }
+                Ok(snapshot)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self {
+                version: Self::VERSION,
+                ..Default::default()
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Records the current state of the given items.
+    pub(crate) fn capture(items: &[PathBuf], follow_links: bool) -> io::Result<Self> {
+        let files = items
+            .iter()
+            .map(|path| {
+                let meta = if follow_links {
+                    fs::metadata(path)
+                } else {
+                    fs::symlink_metadata(path)
+                }?;
+                Ok((path.to_string_lossy().into_owned(), FileState::new(&meta)))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            version: Self::VERSION,
+            files,
+        })
+    }
+
+    /// Writes the snapshot through a temporary file so that an interrupted run keeps the
+    /// previous snapshot intact.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Returns `true` if `path` is new or changed since `self` was captured.
+    pub(crate) fn is_changed(&self, path: &Path, current: &Self) -> bool {
+        let key = path.to_string_lossy();
+        self.files.get(key.as_ref()) != current.files.get(key.as_ref())
+    }
+
+    /// Returns the paths recorded in `self` that no longer exist in `current`.
+    pub(crate) fn removed(&self, current: &Self) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .filter(|it| !current.files.contains_key(*it))
+            .map(PathBuf::from)
+            .collect()
+    }
+}
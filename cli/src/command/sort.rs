@@ -8,7 +8,7 @@ use crate::{
     utils::{env::NamedTempFile, PathPartExt},
 };
 use clap::{Parser, ValueHint};
-use pna::{Archive, NormalEntry};
+use pna::{Archive, NormalEntry, ReadOptions};
 use std::{
     fmt::{self, Display, Formatter},
     path::PathBuf,
@@ -151,7 +151,7 @@ fn sort_archive(args: SortCommand) -> anyhow::Result<()> {
     let mut entries = Vec::<NormalEntry<_>>::new();
     run_entries(
         archives,
-        || password.as_deref(),
+        &ReadOptions::with_password(password.as_deref()),
         |entry| {
             entries.push(entry?);
             Ok(())
@@ -1,5 +1,10 @@
 use crate::{
-    command::{commons::write_split_archive, Command},
+    cli::ProgressArgs,
+    command::{
+        commons::write_split_archive,
+        progress::{observer, ProgressReporter},
+        Command,
+    },
     utils::PathPartExt,
 };
 use bytesize::ByteSize;
@@ -21,6 +26,8 @@ pub(crate) struct SplitCommand {
         help = "Maximum size in bytes of split archive"
     )]
     pub(crate) max_size: Option<ByteSize>,
+    #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
 }
 
 impl Command for SplitCommand {
@@ -32,6 +39,7 @@ impl Command for SplitCommand {
 
 fn split_archive(args: SplitCommand) -> anyhow::Result<()> {
     let read_file = fs::File::open(&args.archive)?;
+    let progress = ProgressReporter::new(args.progress, None, Some(read_file.metadata()?.len()));
     #[cfg(not(feature = "memmap"))]
     let mut read_archive = Archive::read_header(read_file)?;
     #[cfg(not(feature = "memmap"))]
@@ -59,5 +67,15 @@ fn split_archive(args: SplitCommand) -> anyhow::Result<()> {
     }
     let max_file_size = args.max_size.unwrap_or_else(|| ByteSize::gb(1)).as_u64() as usize;
 
-    write_split_archive(base_out_file_name, entries, max_file_size, args.overwrite)
+    write_split_archive(
+        base_out_file_name,
+        entries,
+        max_file_size,
+        args.overwrite,
+        observer(&progress),
+    )?;
+    if let Some(progress) = progress {
+        progress.finish("written");
+    }
+    Ok(())
 }
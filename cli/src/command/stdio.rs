@@ -1,12 +1,15 @@
 use crate::{
     cli::{
-        CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, HashAlgorithmArgs, PasswordArgs,
+        AtimePreserve, CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, ExclusionArgs,
+        HashAlgorithmArgs, OverwriteArgs, PasswordArgs,
     },
     command::{
         append::{open_archive_then_seek_to_end, run_append_archive},
-        ask_password, check_password,
+        ask_password, ask_passwords,
+        cat::run_cat_archive,
+        check_password,
         commons::{
-            collect_items, collect_split_archives, entry_option, CreateOptions, Exclude,
+            collect_items, collect_split_archives, entry_option, CreateOptions, Exclude, HardLinks,
             KeepOptions, OwnerOptions, PathTransformers, TimeOptions,
         },
         create::{create_archive_file, CreationContext},
@@ -16,6 +19,7 @@ use crate::{
     },
     utils::{
         self,
+        fs::IdMap,
         re::{bsd::SubstitutionRule, gnu::TransformRule},
         GlobPatterns,
     },
@@ -73,6 +77,15 @@ pub(crate) struct StdioCommand {
     no_recursive: bool,
     #[arg(long, help = "Overwrite file")]
     overwrite: bool,
+    #[command(flatten)]
+    overwrite_policy: OverwriteArgs,
+    #[arg(
+        long,
+        help = "Write each file to a temporary file next to it and rename it into place once it is completely extracted"
+    )]
+    atomic: bool,
+    #[arg(long, help = "Flush each extracted file to the disk before moving on")]
+    fsync: bool,
     #[arg(long, help = "Archiving the directories")]
     keep_dir: bool,
     #[arg(
@@ -120,10 +133,32 @@ pub(crate) struct StdioCommand {
     exclude_from: Option<String>,
     #[arg(long, help = "Ignore files from .gitignore (unstable)")]
     pub(crate) gitignore: bool,
+    #[command(flatten)]
+    pub(crate) exclusion: ExclusionArgs,
     #[arg(long, help = "Follow symbolic links")]
     pub(crate) follow_links: bool,
+    #[arg(
+        long,
+        help = "Archive hard-linked files as separate copies instead of hard link entries"
+    )]
+    pub(crate) hard_dereference: bool,
+    #[arg(
+        long,
+        value_name = "METHOD",
+        require_equals = true,
+        help = "Preserve the access times of the files being archived, by restoring them after reading (replace) or by reading without updating them where the system supports it (system) [default: replace]"
+    )]
+    pub(crate) atime_preserve: Option<Option<AtimePreserve>>,
     #[arg(long, help = "Output directory of extracted files", value_hint = ValueHint::DirPath)]
     pub(crate) out_dir: Option<PathBuf>,
+    #[arg(
+        short = 'O',
+        long,
+        requires = "extract",
+        conflicts_with = "out_dir",
+        help = "Write the contents of extracted files to the standard output instead of creating them"
+    )]
+    to_stdout: bool,
     #[arg(
         long,
         help = "Remove the specified number of leading path elements. Path names with fewer elements will be silently skipped"
@@ -154,6 +189,20 @@ pub(crate) struct StdioCommand {
         help = "This is equivalent to --uname \"\" --gname \"\". On create, it causes user and group names to not be stored in the archive. On extract, it causes user and group names in the archive to be ignored in favor of the numeric user and group ids."
     )]
     pub(crate) numeric_owner: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map user names and ids with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) owner_map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map group names and ids with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) group_map: Option<PathBuf>,
     #[arg(long, help = "Overrides the creation time")]
     ctime: Option<DateTime>,
     #[arg(
@@ -259,6 +308,7 @@ fn run_create_archive(args: StdioCommand) -> anyhow::Result<()> {
         args.gitignore,
         args.follow_links,
         exclude,
+        &args.exclusion,
     )?;
 
     let password = password.as_deref();
@@ -275,6 +325,8 @@ fn run_create_archive(args: StdioCommand) -> anyhow::Result<()> {
         args.uid,
         args.gid,
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
     let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
     let time_options = TimeOptions {
@@ -284,6 +336,7 @@ fn run_create_archive(args: StdioCommand) -> anyhow::Result<()> {
         clamp_ctime: args.clamp_ctime,
         atime: args.atime.map(|it| it.to_system_time()),
         clamp_atime: args.clamp_atime,
+        mtime_only: false,
     };
     let creation_context = CreationContext {
         write_option: cli_option,
@@ -292,7 +345,11 @@ fn run_create_archive(args: StdioCommand) -> anyhow::Result<()> {
         time_options,
         solid: args.solid,
         follow_links: args.follow_links,
+        hard_links: HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?,
+        atime_preserve: args.atime_preserve.map(Option::unwrap_or_default),
         path_transformers,
+        whiteouts: Vec::new(),
+        progress: None,
     };
     if let Some(file) = args.file {
         create_archive_file(
@@ -306,8 +363,7 @@ fn run_create_archive(args: StdioCommand) -> anyhow::Result<()> {
 }
 
 fn run_extract_archive(args: StdioCommand) -> anyhow::Result<()> {
-    let password = ask_password(args.password)?;
-
+    let passwords = ask_passwords(args.password)?;
     let exclude = {
         let mut exclude = args.exclude.unwrap_or_default();
         if let Some(p) = args.exclude_from {
@@ -318,13 +374,43 @@ fn run_extract_archive(args: StdioCommand) -> anyhow::Result<()> {
             exclude: exclude.into(),
         }
     };
+    if args.to_stdout {
+        return if let Some(path) = args.file {
+            let archives = collect_split_archives(&path)?;
+            run_cat_archive(
+                archives
+                    .into_iter()
+                    .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+                args.files,
+                &passwords,
+                &exclude,
+                None,
+                None,
+            )
+        } else {
+            run_cat_archive(
+                std::iter::repeat_with(|| io::stdin().lock()),
+                args.files,
+                &passwords,
+                &exclude,
+                None,
+                None,
+            )
+        };
+    }
 
     let out_option = OutputOption {
-        overwrite: args.overwrite,
+        overwrite: args.overwrite_policy.strategy(args.overwrite),
+        unlink_first: args.overwrite_policy.unlink_first,
+        atomic: args.atomic,
+        fsync: args.fsync,
+        dry_run: false,
         allow_unsafe_links: args.allow_unsafe_links,
+        keep_directory_symlink: false,
         strip_components: args.strip_components,
         out_dir: args.out_dir,
         exclude,
+        filter: None,
         keep_options: KeepOptions {
             keep_timestamp: args.keep_timestamp,
             keep_permission: args.keep_permission,
@@ -337,9 +423,12 @@ fn run_extract_archive(args: StdioCommand) -> anyhow::Result<()> {
             args.uid,
             args.gid,
             args.numeric_owner,
+            IdMap::load_users(args.owner_map)?,
+            IdMap::load_groups(args.group_map)?,
         ),
         same_owner: !args.no_same_owner,
         path_transformers: PathTransformers::new(args.substitutions, args.transforms),
+        progress: None,
     };
     if let Some(path) = args.file {
         let archives = collect_split_archives(&path)?;
@@ -348,21 +437,23 @@ fn run_extract_archive(args: StdioCommand) -> anyhow::Result<()> {
                 .into_iter()
                 .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
             args.files,
-            || password.as_deref(),
+            &passwords,
+            None,
             out_option,
         )
     } else {
         run_extract_archive_reader(
             std::iter::repeat_with(|| io::stdin().lock()),
             args.files,
-            || password.as_deref(),
+            &passwords,
+            None,
             out_option,
         )
     }
 }
 
 fn run_list_archive(args: StdioCommand) -> anyhow::Result<()> {
-    let password = ask_password(args.password)?;
+    let passwords = ask_passwords(args.password)?;
     let list_options = ListOptions {
         long: false,
         header: false,
@@ -375,7 +466,9 @@ fn run_list_archive(args: StdioCommand) -> anyhow::Result<()> {
         numeric_owner: args.numeric_owner,
         hide_control_chars: false,
         classify: false,
+        versions: false,
         format: None,
+        filter: None,
     };
     let files_globs = GlobPatterns::new(&args.files)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
@@ -397,7 +490,7 @@ fn run_list_archive(args: StdioCommand) -> anyhow::Result<()> {
             archives
                 .into_iter()
                 .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
-            password.as_deref(),
+            &passwords,
             files_globs,
             exclude,
             list_options,
@@ -405,7 +498,7 @@ fn run_list_archive(args: StdioCommand) -> anyhow::Result<()> {
     } else {
         crate::command::list::run_list_archive(
             std::iter::repeat_with(|| io::stdin().lock()),
-            password.as_deref(),
+            &passwords,
             files_globs,
             exclude,
             list_options,
@@ -430,6 +523,8 @@ fn run_append(args: StdioCommand) -> anyhow::Result<()> {
         args.uid,
         args.gid,
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
     let time_options = TimeOptions {
         mtime: args.mtime.map(|it| it.to_system_time()),
@@ -438,13 +533,16 @@ fn run_append(args: StdioCommand) -> anyhow::Result<()> {
         clamp_ctime: args.clamp_ctime,
         atime: args.atime.map(|it| it.to_system_time()),
         clamp_atime: args.clamp_atime,
+        mtime_only: false,
     };
-    let create_options = CreateOptions {
+    let mut create_options = CreateOptions {
         option,
         keep_options,
         owner_options,
         time_options,
         follow_links: args.follow_links,
+        hard_links: HardLinks::default(),
+        atime_preserve: args.atime_preserve.map(Option::unwrap_or_default),
     };
     let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
 
@@ -464,7 +562,7 @@ fn run_append(args: StdioCommand) -> anyhow::Result<()> {
     };
 
     if let Some(file) = &args.file {
-        let archive = open_archive_then_seek_to_end(file)?;
+        let (archive, rollback) = open_archive_then_seek_to_end(file)?;
         let target_items = collect_items(
             &files,
             args.recursive,
@@ -472,8 +570,16 @@ fn run_append(args: StdioCommand) -> anyhow::Result<()> {
             args.gitignore,
             args.follow_links,
             exclude,
+            &args.exclusion,
         )?;
-        run_append_archive(&create_options, &path_transformers, archive, target_items)
+        create_options.hard_links =
+            HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?;
+        rollback.complete(run_append_archive(
+            &create_options,
+            &path_transformers,
+            archive,
+            target_items,
+        ))
     } else {
         let target_items = collect_items(
             &files,
@@ -482,7 +588,10 @@ fn run_append(args: StdioCommand) -> anyhow::Result<()> {
             args.gitignore,
             args.follow_links,
             exclude,
+            &args.exclusion,
         )?;
+        create_options.hard_links =
+            HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?;
         let mut output_archive = Archive::write_header(io::stdout().lock())?;
         {
             let mut input_archive = Archive::read_header(io::stdin().lock())?;
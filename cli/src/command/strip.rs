@@ -1,20 +1,20 @@
 use crate::{
     cli::{
-        FileArgs, PasswordArgs, PrivateChunkType, SolidEntriesTransformStrategy,
+        EntryFilter, FileArgs, PasswordArgs, PrivateChunkType, SolidEntriesTransformStrategy,
         SolidEntriesTransformStrategyArgs,
     },
     command::{
         ask_password,
         commons::{
-            collect_split_archives, run_transform_entry, TransformStrategyKeepSolid,
-            TransformStrategyUnSolid,
+            collect_split_archives, run_entries, run_transform_entry, PlannedAction,
+            TransformStrategyKeepSolid, TransformStrategyUnSolid,
         },
         Command,
     },
     utils::{env::NamedTempFile, PathPartExt},
 };
-use clap::{Args, Parser, ValueHint};
-use pna::{prelude::*, Metadata, NormalEntry, RawChunk};
+use clap::{ArgGroup, Args, Parser, ValueHint};
+use pna::{prelude::*, ChunkType, Metadata, NormalEntry, RawChunk, ReadOptions};
 use std::path::PathBuf;
 
 #[derive(Args, Clone, Eq, PartialEq, Hash, Debug)]
@@ -48,6 +48,10 @@ pub(crate) struct StripOptions {
 }
 
 #[derive(Parser, Clone, Eq, PartialEq, Hash, Debug)]
+#[command(
+    group(ArgGroup::new("unstable-where").args(["filter"]).requires("unstable")),
+    group(ArgGroup::new("unstable-dry-run").args(["dry_run"]).requires("unstable")),
+)]
 pub(crate) struct StripCommand {
     #[command(flatten)]
     pub(crate) strip_options: StripOptions,
@@ -55,6 +59,18 @@ pub(crate) struct StripCommand {
     transform_strategy: SolidEntriesTransformStrategyArgs,
     #[arg(long, help = "Output file path", value_hint = ValueHint::AnyPath)]
     pub(crate) output: Option<PathBuf>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Process only entries matching the expression, such as `kind == file && size > 10M && mtime < 2024-01-01 && path ~ \"*.log\" && uname == \"www\"` (unstable)"
+    )]
+    pub(crate) filter: Option<EntryFilter>,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive (unstable)"
+    )]
+    pub(crate) dry_run: bool,
     #[command(flatten)]
     pub(crate) password: PasswordArgs,
     #[command(flatten)]
@@ -80,6 +96,23 @@ fn strip_metadata(args: StripCommand) -> anyhow::Result<()> {
     #[cfg(feature = "memmap")]
     let archives = mmaps.iter().map(|m| m.as_ref());
 
+    if args.dry_run {
+        run_entries(
+            archives,
+            &ReadOptions::with_password(password.as_deref()),
+            |entry| {
+                let entry = entry?;
+                if args.filter.as_ref().is_none_or(|it| it.matches(&entry))
+                    && strips_entry_metadata(&entry, &args.strip_options)
+                {
+                    PlannedAction::Replace.print(entry.header().path());
+                }
+                Ok(())
+            },
+        )?;
+        return Ok(());
+    }
+
     let output_path = args
         .output
         .unwrap_or_else(|| args.file.archive.remove_part().unwrap());
@@ -91,14 +124,26 @@ fn strip_metadata(args: StripCommand) -> anyhow::Result<()> {
             temp_file.as_file_mut(),
             archives,
             || password.as_deref(),
-            |entry| Ok(Some(strip_entry_metadata(entry?, &args.strip_options))),
+            |entry| {
+                let entry = entry?;
+                if args.filter.as_ref().is_some_and(|it| !it.matches(&entry)) {
+                    return Ok(Some(entry));
+                }
+                Ok(Some(strip_entry_metadata(entry, &args.strip_options)))
+            },
             TransformStrategyUnSolid,
         ),
         SolidEntriesTransformStrategy::KeepSolid => run_transform_entry(
             temp_file.as_file_mut(),
             archives,
             || password.as_deref(),
-            |entry| Ok(Some(strip_entry_metadata(entry?, &args.strip_options))),
+            |entry| {
+                let entry = entry?;
+                if args.filter.as_ref().is_some_and(|it| !it.matches(&entry)) {
+                    return Ok(Some(entry));
+                }
+                Ok(Some(strip_entry_metadata(entry, &args.strip_options)))
+            },
             TransformStrategyKeepSolid,
         ),
     }?;
@@ -111,41 +156,62 @@ fn strip_metadata(args: StripCommand) -> anyhow::Result<()> {
 }
 
 #[inline]
-fn strip_entry_metadata<T>(mut entry: NormalEntry<T>, options: &StripOptions) -> NormalEntry<T>
+fn strip_entry_metadata<T>(entry: NormalEntry<T>, options: &StripOptions) -> NormalEntry<T>
 where
     T: Clone,
     RawChunk<T>: Chunk,
 {
-    let mut metadata = Metadata::new();
-    if options.keep_permission {
-        metadata = metadata.with_permission(entry.metadata().permission().cloned());
-    }
-    if options.keep_timestamp {
-        metadata = metadata.with_accessed(entry.metadata().accessed());
-        metadata = metadata.with_created(entry.metadata().created());
-        metadata = metadata.with_modified(entry.metadata().modified());
-    }
-    entry = entry.with_metadata(metadata);
+    let metadata = stripped_metadata(entry.metadata(), options);
+    let mut entry = entry.with_metadata(metadata);
     if !options.keep_xattr {
         entry = entry.with_xattrs(&[]);
     }
-    let keep_private_all = options
-        .keep_private
-        .as_ref()
-        .is_some_and(|it| it.is_empty());
-    let mut keep_private_chunks = Vec::new();
-    if options.keep_acl {
-        keep_private_chunks.push(crate::chunk::faCl);
-        keep_private_chunks.push(crate::chunk::faCe);
-    }
-    if let Some(chunks) = &options.keep_private {
-        keep_private_chunks.extend(chunks.iter().map(|it| it.0))
-    }
     let filtered = entry
         .extra_chunks()
         .iter()
-        .filter(|it| keep_private_all || keep_private_chunks.contains(&it.ty()))
+        .filter(|it| keeps_chunk(it.ty(), options))
         .cloned()
         .collect::<Vec<_>>();
     entry.with_extra_chunks(filtered)
 }
+
+/// Returns whether [strip_entry_metadata] would change `entry`.
+#[inline]
+fn strips_entry_metadata<T>(entry: &NormalEntry<T>, options: &StripOptions) -> bool
+where
+    RawChunk<T>: Chunk,
+{
+    stripped_metadata(entry.metadata(), options) != *entry.metadata()
+        || (!options.keep_xattr && !entry.xattrs().is_empty())
+        || entry
+            .extra_chunks()
+            .iter()
+            .any(|it| !keeps_chunk(it.ty(), options))
+}
+
+#[inline]
+fn stripped_metadata(metadata: &Metadata, options: &StripOptions) -> Metadata {
+    let mut metadata = metadata.clone();
+    if !options.keep_permission {
+        metadata = metadata.with_permission(None);
+    }
+    if !options.keep_timestamp {
+        metadata = metadata
+            .with_accessed(None)
+            .with_created(None)
+            .with_modified(None);
+    }
+    metadata
+}
+
+/// Returns whether extra chunks of type `ty` are kept.
+#[inline]
+fn keeps_chunk(ty: ChunkType, options: &StripOptions) -> bool {
+    match &options.keep_private {
+        Some(chunks) if chunks.is_empty() => true,
+        keep_private => {
+            (options.keep_acl && (ty == crate::chunk::faCl || ty == crate::chunk::faCe))
+                || keep_private.iter().flatten().any(|it| it.0 == ty)
+        }
+    }
+}
@@ -4,21 +4,25 @@ use crate::command::commons::run_read_entries;
 use crate::command::commons::run_read_entries_mem as run_read_entries;
 use crate::{
     cli::{
-        CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, FileArgs, HashAlgorithmArgs,
-        PasswordArgs, SolidEntriesTransformStrategy, SolidEntriesTransformStrategyArgs,
+        AtimePreserve, CipherAlgorithmArgs, CompressionAlgorithmArgs, DateTime, ExclusionArgs,
+        FileArgs, HashAlgorithmArgs, PasswordArgs, ProgressArgs, SolidEntriesTransformStrategy,
+        SolidEntriesTransformStrategyArgs,
     },
     command::{
         ask_password, check_password,
         commons::{
-            collect_items, collect_split_archives, create_entry, entry_option, CreateOptions,
-            Exclude, KeepOptions, OwnerOptions, PathTransformers, TimeOptions, TransformStrategy,
+            collect_items, collect_split_archives, create_entry, entry_option, output_writer,
+            to_entry_name, CreateOptions, Exclude, HardLinks, KeepOptions, OwnerOptions,
+            PathTransformers, PlannedAction, TimeOptions, TransformStrategy,
             TransformStrategyKeepSolid, TransformStrategyUnSolid,
         },
+        progress::{observer, ProgressReporter},
         Command,
     },
     utils::{
         self,
         env::NamedTempFile,
+        fs::IdMap,
         re::{bsd::SubstitutionRule, gnu::TransformRule},
         PathPartExt,
     },
@@ -113,6 +117,20 @@ pub(crate) struct UpdateCommand {
         help = "This is equivalent to --uname \"\" --gname \"\". It causes user and group names to not be stored in the archive"
     )]
     pub(crate) numeric_owner: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map user names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) owner_map: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map group names and ids read from disk with the `OLD NEW[:NEWID]` lines of FILE, where OLD and NEW are a name or `+` followed by an id",
+        value_hint = ValueHint::FilePath
+    )]
+    pub(crate) group_map: Option<PathBuf>,
     #[arg(long, help = "Overrides the creation time read from disk")]
     ctime: Option<DateTime>,
     #[arg(
@@ -199,12 +217,34 @@ pub(crate) struct UpdateCommand {
     pub(crate) hash: HashAlgorithmArgs,
     #[command(flatten)]
     pub(crate) transform_strategy: SolidEntriesTransformStrategyArgs,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Print what would be done without writing the archive"
+    )]
+    pub(crate) dry_run: bool,
+    #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
     #[command(flatten)]
     pub(crate) file: FileArgs,
     #[arg(long, help = "Ignore files from .gitignore (unstable)")]
     pub(crate) gitignore: bool,
+    #[command(flatten)]
+    pub(crate) exclusion: ExclusionArgs,
     #[arg(long, help = "Follow symbolic links")]
     pub(crate) follow_links: bool,
+    #[arg(
+        long,
+        help = "Archive hard-linked files as separate copies instead of hard link entries"
+    )]
+    pub(crate) hard_dereference: bool,
+    #[arg(
+        long,
+        value_name = "METHOD",
+        require_equals = true,
+        help = "Preserve the access times of the files being archived, by restoring them after reading (replace) or by reading without updating them where the system supports it (system) [default: replace]"
+    )]
+    pub(crate) atime_preserve: Option<Option<AtimePreserve>>,
 }
 
 impl Command for UpdateCommand {
@@ -247,6 +287,8 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
         args.uid,
         args.gid,
         args.numeric_owner,
+        IdMap::load_users(args.owner_map)?,
+        IdMap::load_groups(args.group_map)?,
     );
     let time_options = TimeOptions {
         mtime: args.mtime.map(|it| it.to_system_time()),
@@ -255,6 +297,7 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
         clamp_ctime: args.clamp_ctime,
         atime: args.atime.map(|it| it.to_system_time()),
         clamp_atime: args.clamp_atime,
+        mtime_only: false,
     };
     let time_filters = TimeFilters {
         ctime: TimeFilter {
@@ -266,12 +309,14 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
             older_than: args.older_mtime.map(|it| it.to_system_time()),
         },
     };
-    let create_options = CreateOptions {
+    let mut create_options = CreateOptions {
         option,
         keep_options,
         owner_options,
         time_options,
         follow_links: args.follow_links,
+        hard_links: HardLinks::default(),
+        atime_preserve: args.atime_preserve.map(Option::unwrap_or_default),
     };
     let path_transformers = PathTransformers::new(args.substitutions, args.transforms);
 
@@ -306,13 +351,20 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
         args.gitignore,
         args.follow_links,
         exclude,
+        &args.exclusion,
     )?;
+    create_options.hard_links =
+        HardLinks::new(&target_items, args.follow_links, args.hard_dereference)?;
 
     let (tx, rx) = std::sync::mpsc::channel();
 
-    let mut temp_file =
-        NamedTempFile::new(|| archive_path.parent().unwrap_or_else(|| ".".as_ref()))?;
-    let mut out_archive = Archive::write_header(temp_file.as_file_mut())?;
+    let dry_run = args.dry_run;
+    let mut temp_file = (!dry_run)
+        .then(|| NamedTempFile::new(|| archive_path.parent().unwrap_or_else(|| ".".as_ref())))
+        .transpose()?;
+    let mut out_archive = Archive::write_header(output_writer(&mut temp_file))?;
+    let progress = ProgressReporter::new(args.progress, None, None);
+    out_archive.set_progress_observer(observer(&progress));
 
     let mut target_files_mapping = target_items
         .into_iter()
@@ -329,14 +381,18 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
 
     rayon::scope_fifo(|s| -> anyhow::Result<()> {
         run_read_entries(archives, |entry| {
-            Strategy::transform(&mut out_archive, password, entry, |entry| {
+            Strategy::transform(&mut out_archive, password.as_slice(), entry, |entry| {
                 let entry = entry?;
                 if let Some(target_path) = target_files_mapping.swap_remove(entry.header().path()) {
                     let fs_meta = fs::symlink_metadata(&target_path)?;
                     let need_update = is_newer_than_archive(&fs_meta, entry.metadata())
                         .unwrap_or(true)
                         && time_filters.is_retain(&fs_meta);
-                    if need_update {
+                    if need_update && dry_run {
+                        PlannedAction::Replace
+                            .print(to_entry_name(&target_path, &path_transformers));
+                        Ok(Some(entry))
+                    } else if need_update {
                         let tx = tx.clone();
                         let create_options = create_options.clone();
                         let path_transformers = path_transformers.clone();
@@ -351,6 +407,9 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
                         });
                         Ok(None)
                     } else {
+                        if dry_run {
+                            PlannedAction::Skip.print(entry.header().path());
+                        }
                         Ok(Some(entry))
                     }
                 } else {
@@ -361,6 +420,10 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
 
         // NOTE: Add new entries
         for (_, file) in target_files_mapping {
+            if dry_run {
+                PlannedAction::Add.print(to_entry_name(&file, &path_transformers));
+                continue;
+            }
             let tx = tx.clone();
             let create_options = create_options.clone();
             let path_transformers = path_transformers.clone();
@@ -375,16 +438,24 @@ fn update_archive<Strategy: TransformStrategy>(args: UpdateCommand) -> anyhow::R
     })?;
 
     for entry in rx.into_iter() {
-        Strategy::transform(&mut out_archive, password, entry.map(Into::into), |entry| {
-            entry.map(Some)
-        })?;
+        Strategy::transform(
+            &mut out_archive,
+            password.as_slice(),
+            entry.map(Into::into),
+            |entry| entry.map(Some),
+        )?;
     }
     out_archive.finalize()?;
 
     #[cfg(feature = "memmap")]
     drop(mmaps);
 
-    temp_file.persist(archive_path.remove_part().unwrap())?;
+    if let Some(temp_file) = temp_file {
+        temp_file.persist(archive_path.remove_part().unwrap())?;
+    }
+    if let Some(progress) = progress {
+        progress.finish("written");
+    }
 
     Ok(())
 }
@@ -0,0 +1,63 @@
+use crate::{
+    cli::{FileArgs, PasswordArgs},
+    command::{
+        ask_passwords,
+        commons::{collect_split_archives, run_across_archive},
+        Command,
+    },
+    utils::fmt::DurationDisplay,
+};
+use clap::Parser;
+use pna::ReadOptions;
+use std::{io, time::Instant};
+
+#[derive(Parser, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub(crate) struct VerifyCommand {
+    #[command(flatten)]
+    pub(crate) password: PasswordArgs,
+    #[command(flatten)]
+    pub(crate) file: FileArgs,
+}
+
+impl Command for VerifyCommand {
+    #[inline]
+    fn execute(self) -> anyhow::Result<()> {
+        verify_archive(self)
+    }
+}
+
+fn verify_archive(args: VerifyCommand) -> anyhow::Result<()> {
+    let passwords = ask_passwords(args.password)?;
+    let read_options = ReadOptions::with_passwords(passwords);
+    let start = Instant::now();
+    log::info!("Verify archive {}", args.file.archive.display());
+
+    let archives = collect_split_archives(&args.file.archive)?;
+    let (mut entries, mut problems) = (0, 0);
+    run_across_archive(
+        archives
+            .into_iter()
+            .map(|it| io::BufReader::with_capacity(64 * 1024, it)),
+        |archive| {
+            let report = archive.verify(&read_options)?;
+            for problem in report.problems() {
+                log::error!("{problem}");
+            }
+            entries += report.entries();
+            problems += report.problems().len();
+            Ok(())
+        },
+    )?;
+    if problems != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{problems} problems found in {entries} entries"),
+        )
+        .into());
+    }
+    log::info!(
+        "Successfully verified {entries} entries in {}",
+        DurationDisplay(start.elapsed())
+    );
+    Ok(())
+}
@@ -14,7 +14,7 @@ use base64::Engine;
 use bstr::{io::BufReadExt, ByteSlice};
 use clap::{ArgGroup, Parser, ValueEnum, ValueHint};
 use indexmap::IndexMap;
-use pna::NormalEntry;
+use pna::{NormalEntry, ReadOptions};
 use regex::Regex;
 use std::{
     collections::HashMap,
@@ -205,7 +205,7 @@ fn archive_get_xattr(args: GetXattrCommand) -> anyhow::Result<()> {
 
     run_entries(
         archives,
-        || password.as_deref(),
+        &ReadOptions::with_password(password.as_deref()),
         |entry| {
             let entry = entry?;
             let name = entry.header().path();
@@ -10,6 +10,7 @@ pub(crate) mod mmap;
 pub(crate) mod os;
 mod path;
 pub(crate) mod re;
+pub(crate) mod signal;
 pub(crate) mod str;
 
 pub(crate) use {globs::*, path::*};
@@ -1,13 +1,17 @@
+mod beneath;
+mod id_map;
 mod owner;
 
 #[cfg(windows)]
 use crate::utils::os::windows::{self, fs::*};
+pub(crate) use beneath::*;
+pub(crate) use id_map::*;
 pub(crate) use owner::*;
-pub(crate) use pna::fs::*;
 use std::{
     fs,
     io::{self, prelude::*},
     path::Path,
+    time::SystemTime,
 };
 
 pub(crate) fn is_pna<P: AsRef<Path>>(path: P) -> io::Result<bool> {
@@ -54,24 +58,15 @@ pub(crate) fn read_to_lines<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>>
     inner(path.as_ref())
 }
 
-#[cfg(any(windows, unix))]
+#[cfg(windows)]
 pub(crate) fn chown<P: AsRef<Path>>(
     path: P,
     owner: Option<User>,
     group: Option<Group>,
 ) -> io::Result<()> {
-    #[cfg(windows)]
     fn inner(path: &Path, owner: Option<User>, group: Option<Group>) -> io::Result<()> {
         windows::fs::chown(path.as_ref(), owner.map(|it| it.0), group.map(|it| it.0))
     }
-    #[cfg(unix)]
-    fn inner(path: &Path, owner: Option<User>, group: Option<Group>) -> io::Result<()> {
-        std::os::unix::fs::chown(
-            path,
-            owner.map(|it| it.0.as_raw()),
-            group.map(|it| it.0.as_raw()),
-        )
-    }
     inner(path.as_ref(), owner, group)
 }
 
@@ -83,3 +78,167 @@ pub(crate) fn file_create(path: impl AsRef<Path>, overwrite: bool) -> io::Result
         fs::File::create_new(path)
     }
 }
+
+/// Opens `path` for reading without updating its access time where the system supports it,
+/// and normally otherwise.
+pub(crate) fn open_noatime(path: impl AsRef<Path>) -> io::Result<fs::File> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn inner(path: &Path) -> io::Result<fs::File> {
+        use std::os::unix::fs::OpenOptionsExt;
+        match fs::File::options()
+            .read(true)
+            .custom_flags(nix::fcntl::OFlag::O_NOATIME.bits())
+            .open(path)
+        {
+            // Only the owner of a file may open it with O_NOATIME.
+            Err(e) if e.raw_os_error() == Some(nix::errno::Errno::EPERM as i32) => {
+                fs::File::open(path)
+            }
+            result => result,
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn inner(path: &Path) -> io::Result<fs::File> {
+        fs::File::open(path)
+    }
+    inner(path.as_ref())
+}
+
+/// Timestamps to set with [set_path_times]. The ones that are `None` are left unchanged.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct PathTimes {
+    pub(crate) accessed: Option<SystemTime>,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) created: Option<SystemTime>,
+}
+
+/// Sets the timestamps of a file or a directory without opening it, so that it also works on
+/// entries without read permission. A symbolic link at `path` is not followed.
+pub(crate) fn set_path_times(path: impl AsRef<Path>, times: PathTimes) -> io::Result<()> {
+    #[cfg(windows)]
+    fn inner(path: &Path, times: PathTimes) -> io::Result<()> {
+        use std::os::windows::fs::{FileTimesExt, OpenOptionsExt};
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+        let mut file_times = fs::FileTimes::new();
+        if let Some(accessed) = times.accessed {
+            file_times = file_times.set_accessed(accessed);
+        }
+        if let Some(modified) = times.modified {
+            file_times = file_times.set_modified(modified);
+        }
+        if let Some(created) = times.created {
+            file_times = file_times.set_created(created);
+        }
+        fs::File::options()
+            .access_mode(FILE_WRITE_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+            .open(path)?
+            .set_times(file_times)
+    }
+    #[cfg(all(unix, not(target_os = "redox")))]
+    fn inner(path: &Path, times: PathTimes) -> io::Result<()> {
+        use nix::{
+            fcntl::AT_FDCWD,
+            sys::stat::{utimensat, UtimensatFlags},
+        };
+        utimensat(
+            AT_FDCWD,
+            path,
+            &timespec(times.accessed),
+            &timespec(times.modified),
+            UtimensatFlags::NoFollowSymlink,
+        )?;
+        #[cfg(target_os = "macos")]
+        if let Some(created) = times.created {
+            use std::os::macos::fs::FileTimesExt;
+            fs::File::open(path)?.set_times(fs::FileTimes::new().set_created(created))?;
+        }
+        Ok(())
+    }
+    #[cfg(not(any(windows, all(unix, not(target_os = "redox")))))]
+    fn inner(path: &Path, times: PathTimes) -> io::Result<()> {
+        let mut file_times = fs::FileTimes::new();
+        if let Some(accessed) = times.accessed {
+            file_times = file_times.set_accessed(accessed);
+        }
+        if let Some(modified) = times.modified {
+            file_times = file_times.set_modified(modified);
+        }
+        fs::File::open(path)?.set_times(file_times)
+    }
+    inner(path.as_ref(), times)
+}
+
+/// Converts a time to set with `utimensat`, leaving the time unchanged for `None`.
+#[cfg(all(unix, not(target_os = "redox")))]
+fn timespec(time: Option<SystemTime>) -> nix::sys::time::TimeSpec {
+    use nix::sys::time::TimeSpec;
+    match time.map(|it| it.duration_since(std::time::UNIX_EPOCH)) {
+        None => TimeSpec::UTIME_OMIT,
+        Some(Ok(since)) => TimeSpec::from_duration(since),
+        Some(Err(e)) => -TimeSpec::from_duration(e.duration()),
+    }
+}
+
+/// Exclusive lock on a file opened with [open_locked], released when dropped.
+pub(crate) struct FileLock {
+    #[cfg(unix)]
+    _lock: nix::fcntl::Flock<fs::File>,
+    #[cfg(windows)]
+    _file: fs::File,
+}
+
+/// Opens `path` for reading and writing and locks it, so that another process calling this
+/// function for the same file fails until the returned [FileLock] is dropped.
+pub(crate) fn open_locked(path: impl AsRef<Path>) -> io::Result<(fs::File, FileLock)> {
+    #[cfg(any(unix, windows))]
+    fn locked_error(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!("{} is locked by another process", path.display()),
+        )
+    }
+    #[cfg(unix)]
+    fn inner(path: &Path) -> io::Result<(fs::File, FileLock)> {
+        use nix::{
+            errno::Errno,
+            fcntl::{Flock, FlockArg},
+        };
+        let file = fs::File::options().read(true).write(true).open(path)?;
+        let lock =
+            Flock::lock(file.try_clone()?, FlockArg::LockExclusiveNonblock).map_err(|(_, e)| {
+                match e {
+                    Errno::EWOULDBLOCK => locked_error(path),
+                    e => e.into(),
+                }
+            })?;
+        Ok((file, FileLock { _lock: lock }))
+    }
+    #[cfg(windows)]
+    fn inner(path: &Path) -> io::Result<(fs::File, FileLock)> {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x1;
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        let file = fs::File::options()
+            .read(true)
+            .write(true)
+            .share_mode(FILE_SHARE_READ)
+            .open(path)
+            .map_err(|e| match e.raw_os_error() {
+                Some(ERROR_SHARING_VIOLATION) => locked_error(path),
+                _ => e,
+            })?;
+        let lock = FileLock {
+            _file: file.try_clone()?,
+        };
+        Ok((file, lock))
+    }
+    #[cfg(not(any(unix, windows)))]
+    fn inner(path: &Path) -> io::Result<(fs::File, FileLock)> {
+        let file = fs::File::options().read(true).write(true).open(path)?;
+        Ok((file, FileLock {}))
+    }
+    inner(path.as_ref())
+}
@@ -0,0 +1,655 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A directory that extracted entries are written into.
+///
+/// On Unix, every path is resolved one component at a time relative to a descriptor of the
+/// directory with `O_NOFOLLOW`, so that a symbolic link created by an earlier entry, or by
+/// another process while extracting, cannot redirect a write outside of it. Elsewhere, paths
+/// are joined to the directory as is.
+pub(crate) struct Beneath {
+    root: PathBuf,
+    #[cfg(unix)]
+    fd: std::os::fd::OwnedFd,
+    /// Whether a symbolic link to a directory that already existed is followed, like the one
+    /// of a system directory such as `bin -> usr/bin`.
+    #[cfg(unix)]
+    keep_directory_symlink: bool,
+    /// The symbolic links created by [Beneath::symlink], which are never followed.
+    #[cfg(unix)]
+    created_symlinks: std::sync::Mutex<std::collections::HashSet<PathBuf>>,
+}
+
+fn unsafe_path(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} points outside of the output directory", path.display()),
+    )
+}
+
+/// Splits `path` into its components, rejecting paths that are not relative to the output
+/// directory. An empty path is the output directory itself.
+fn components(path: &Path) -> io::Result<Vec<&std::ffi::OsStr>> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_path(path))
+            }
+        }
+    }
+    Ok(components)
+}
+
+/// Splits `path` into its parent directories and its file name, rejecting paths that are
+/// not relative to the output directory.
+fn split(path: &Path) -> io::Result<(Vec<&std::ffi::OsStr>, &std::ffi::OsStr)> {
+    let mut components = components(path)?;
+    let name = components.pop().ok_or_else(|| unsafe_path(path))?;
+    Ok((components, name))
+}
+
+/// Returns a hidden, randomly suffixed name to write `name` to before renaming it into place,
+/// such as `.name.pna-extract-0123abcd`, so that one left behind is recognized as such.
+fn temp_name(name: &std::ffi::OsStr) -> std::ffi::OsString {
+    let mut temp = std::ffi::OsString::from(".");
+    temp.push(name);
+    temp.push(format!(".pna-extract-{:08x}", rand::random::<u32>()));
+    temp
+}
+
+#[cfg(unix)]
+impl Beneath {
+    /// Opens `root`, creating it if it does not exist. With `keep_directory_symlink`, a
+    /// symbolic link to a directory that was not created by the extraction is followed.
+    pub(crate) fn open(root: impl Into<PathBuf>, keep_directory_symlink: bool) -> io::Result<Self> {
+        use nix::{fcntl::OFlag, sys::stat::Mode};
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        let fd = nix::fcntl::open(
+            &root,
+            OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )?;
+        Ok(Self {
+            root,
+            fd,
+            keep_directory_symlink,
+            created_symlinks: Default::default(),
+        })
+    }
+
+    /// Returns whether the directory `components` below the root may be reached through the
+    /// symbolic link in its place.
+    fn follows_symlink(
+        &self,
+        dir: &std::os::fd::OwnedFd,
+        components: &[&std::ffi::OsStr],
+    ) -> io::Result<bool> {
+        use nix::{
+            fcntl::AtFlags,
+            sys::stat::{fstatat, SFlag},
+        };
+        let Some(name) = components.last() else {
+            return Ok(false);
+        };
+        if !self.keep_directory_symlink {
+            return Ok(false);
+        }
+        let stat = fstatat(dir, *name, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+        if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFLNK {
+            return Ok(false);
+        }
+        let created = self
+            .created_symlinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        Ok(!created.contains(&components.iter().collect::<PathBuf>()))
+    }
+
+    /// Opens the directory `components` below the root, creating missing directories if
+    /// `create` is set, and refusing to traverse symbolic links other than the existing ones
+    /// kept by `keep_directory_symlink`.
+    fn open_dir(
+        &self,
+        path: &Path,
+        components: &[&std::ffi::OsStr],
+        create: bool,
+    ) -> io::Result<std::os::fd::OwnedFd> {
+        use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+        let mut fd = self.fd.try_clone()?;
+        for (i, name) in components.iter().enumerate() {
+            if create {
+                match nix::sys::stat::mkdirat(&fd, *name, Mode::from_bits_truncate(0o777)) {
+                    Ok(()) | Err(Errno::EEXIST) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let flags = OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+            fd = match nix::fcntl::openat(&fd, *name, flags | OFlag::O_NOFOLLOW, Mode::empty()) {
+                Err(Errno::ELOOP | Errno::ENOTDIR)
+                    if self.follows_symlink(&fd, &components[..=i])? =>
+                {
+                    nix::fcntl::openat(&fd, *name, flags, Mode::empty())
+                }
+                result => result,
+            }
+            .map_err(|e| match e {
+                Errno::ELOOP | Errno::ENOTDIR => io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{}: refusing to extract through a symbolic link or a non-directory",
+                        path.display()
+                    ),
+                ),
+                e => e.into(),
+            })?;
+        }
+        Ok(fd)
+    }
+
+    /// Creates the directory `path` and all of its missing parents.
+    pub(crate) fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let (mut components, name) = split(path)?;
+        components.push(name);
+        self.open_dir(path, &components, true).map(drop)
+    }
+
+    /// Creates the file `path` for writing. With `overwrite`, an existing file is truncated
+    /// and an existing symbolic link is replaced rather than followed.
+    pub(crate) fn create_file(&self, path: &Path, overwrite: bool) -> io::Result<fs::File> {
+        use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode, unistd::UnlinkatFlags};
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, true)?;
+        let flags = OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC;
+        let mode = Mode::from_bits_truncate(0o666);
+        let open = |flags| nix::fcntl::openat(&dir, name, flags, mode);
+        let fd = if overwrite {
+            match open(flags | OFlag::O_TRUNC) {
+                Err(Errno::ELOOP) => {
+                    nix::unistd::unlinkat(&dir, name, UnlinkatFlags::NoRemoveDir)?;
+                    open(flags | OFlag::O_EXCL)
+                }
+                result => result,
+            }
+        } else {
+            open(flags | OFlag::O_EXCL)
+        }?;
+        Ok(fs::File::from(fd))
+    }
+
+    /// Creates a new temporary file in the directory of `path`, returning it with its path.
+    pub(crate) fn create_temp_file(&self, path: &Path) -> io::Result<(fs::File, PathBuf)> {
+        use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, true)?;
+        loop {
+            let temp = temp_name(name);
+            match nix::fcntl::openat(
+                &dir,
+                temp.as_os_str(),
+                OFlag::O_WRONLY
+                    | OFlag::O_CREAT
+                    | OFlag::O_EXCL
+                    | OFlag::O_NOFOLLOW
+                    | OFlag::O_CLOEXEC,
+                Mode::from_bits_truncate(0o666),
+            ) {
+                Ok(fd) => return Ok((fs::File::from(fd), path.with_file_name(temp))),
+                Err(Errno::EEXIST) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Renames `from` to `to`, replacing `to` if it is a file or a symbolic link.
+    pub(crate) fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from_components, from_name) = split(from)?;
+        let from_dir = self.open_dir(from, &from_components, false)?;
+        let (components, name) = split(to)?;
+        let dir = self.open_dir(to, &components, true)?;
+        Ok(nix::fcntl::renameat(&from_dir, from_name, &dir, name)?)
+    }
+
+    /// Flushes the directory that contains `path` to the disk, so that a file renamed into it
+    /// survives a crash.
+    pub(crate) fn sync_parent(&self, path: &Path) -> io::Result<()> {
+        let (components, _) = split(path)?;
+        let dir = self.open_dir(path, &components, false)?;
+        Ok(nix::unistd::fsync(&dir)?)
+    }
+
+    /// Creates a symbolic link at `path` that points to `original`.
+    pub(crate) fn symlink(&self, original: &Path, path: &Path) -> io::Result<()> {
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, true)?;
+        // Recorded beforehand, so that an entry extracted meanwhile does not follow it.
+        self.created_symlinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(components.iter().chain([&name]).collect());
+        Ok(nix::unistd::symlinkat(original, &dir, name)?)
+    }
+
+    /// Creates a hard link at `path` to the file `original`, both relative to the root.
+    pub(crate) fn hard_link(&self, original: &Path, path: &Path) -> io::Result<()> {
+        use nix::fcntl::AtFlags;
+        let (original_components, original_name) = split(original)?;
+        let original_dir = self.open_dir(original, &original_components, false)?;
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, true)?;
+        Ok(nix::unistd::linkat(
+            &original_dir,
+            original_name,
+            &dir,
+            name,
+            AtFlags::empty(),
+        )?)
+    }
+
+    /// Creates a hard link at `path` to the file `original`, which is resolved from the
+    /// directory of `path` and may point outside of the root.
+    pub(crate) fn hard_link_outside(&self, original: &Path, path: &Path) -> io::Result<()> {
+        use nix::fcntl::AtFlags;
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, true)?;
+        Ok(nix::unistd::linkat(
+            &dir,
+            original,
+            &dir,
+            name,
+            AtFlags::empty(),
+        )?)
+    }
+
+    /// Returns the names of the entries in the directory `path`.
+    pub(crate) fn read_dir(&self, path: &Path) -> io::Result<Vec<std::ffi::OsString>> {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = self.open_dir(path, &components(path)?, false)?;
+        let mut names = Vec::new();
+        for entry in nix::dir::Dir::from_fd(dir)?.into_iter() {
+            let name = entry?.file_name().to_bytes().to_owned();
+            if !matches!(name.as_slice(), b"." | b"..") {
+                names.push(std::ffi::OsStr::from_bytes(&name).to_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Removes the file, symbolic link or directory tree at `path`.
+    pub(crate) fn remove_all(&self, path: &Path) -> io::Result<()> {
+        use nix::unistd::UnlinkatFlags;
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, false)?;
+        if is_dir_at(&dir, name)? {
+            let tree = open_dir_at(&dir, name)?;
+            remove_dir_contents(&tree)?;
+            Ok(nix::unistd::unlinkat(&dir, name, UnlinkatFlags::RemoveDir)?)
+        } else {
+            Ok(nix::unistd::unlinkat(
+                &dir,
+                name,
+                UnlinkatFlags::NoRemoveDir,
+            )?)
+        }
+    }
+
+    /// Opens the entry at `path` to restore its metadata, without following a symbolic link
+    /// at any component of the path.
+    pub(crate) fn entry(&self, path: &Path) -> io::Result<BeneathEntry> {
+        use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, false)?;
+        let file = match nix::fcntl::openat(
+            &dir,
+            name,
+            OFlag::O_RDONLY
+                | OFlag::O_NOFOLLOW
+                | OFlag::O_NONBLOCK
+                | OFlag::O_NOCTTY
+                | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        ) {
+            Ok(fd) => Some(fs::File::from(fd)),
+            // A symbolic link, or a file that may not be read, which is changed through its
+            // directory instead.
+            Err(Errno::ELOOP | Errno::EACCES) => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(BeneathEntry {
+            path: self.path(path),
+            dir,
+            name: name.to_owned(),
+            file,
+        })
+    }
+
+    /// Returns the entry at `path` to restore its metadata through `file`, which was just
+    /// created there.
+    pub(crate) fn entry_of(&self, path: &Path, file: fs::File) -> io::Result<BeneathEntry> {
+        let (components, name) = split(path)?;
+        let dir = self.open_dir(path, &components, false)?;
+        Ok(BeneathEntry {
+            path: self.path(path),
+            dir,
+            name: name.to_owned(),
+            file: Some(file),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn is_dir_at(dir: &std::os::fd::OwnedFd, name: &std::ffi::OsStr) -> io::Result<bool> {
+    use nix::{
+        fcntl::AtFlags,
+        sys::stat::{fstatat, SFlag},
+    };
+    let stat = fstatat(dir, name, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    Ok(SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFDIR)
+}
+
+#[cfg(unix)]
+fn open_dir_at(
+    dir: &std::os::fd::OwnedFd,
+    name: &(impl nix::NixPath + ?Sized),
+) -> io::Result<std::os::fd::OwnedFd> {
+    use nix::{fcntl::OFlag, sys::stat::Mode};
+    Ok(nix::fcntl::openat(
+        dir,
+        name,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    )?)
+}
+
+/// Removes everything inside of the directory `dir` without following symbolic links.
+#[cfg(unix)]
+fn remove_dir_contents(dir: &std::os::fd::OwnedFd) -> io::Result<()> {
+    use nix::unistd::UnlinkatFlags;
+    use std::os::unix::ffi::OsStrExt;
+    let names = nix::dir::Dir::from_fd(open_dir_at(dir, ".")?)?
+        .into_iter()
+        .map(|entry| entry.map(|it| it.file_name().to_owned()))
+        .collect::<Result<Vec<_>, _>>()?;
+    for name in names {
+        if matches!(name.as_bytes(), b"." | b"..") {
+            continue;
+        }
+        let name = std::ffi::OsStr::from_bytes(name.as_bytes());
+        if is_dir_at(dir, name)? {
+            remove_dir_contents(&open_dir_at(dir, name)?)?;
+            nix::unistd::unlinkat(dir, name, UnlinkatFlags::RemoveDir)?;
+        } else {
+            nix::unistd::unlinkat(dir, name, UnlinkatFlags::NoRemoveDir)?;
+        }
+    }
+    Ok(())
+}
+
+/// An entry below a [Beneath] directory whose metadata is being restored.
+///
+/// On Unix, the entry is changed through a descriptor of it, or through its directory with
+/// calls that do not follow a symbolic link in its place.
+pub(crate) struct BeneathEntry {
+    path: PathBuf,
+    #[cfg(unix)]
+    dir: std::os::fd::OwnedFd,
+    #[cfg(unix)]
+    name: std::ffi::OsString,
+    #[cfg(unix)]
+    file: Option<fs::File>,
+}
+
+#[cfg(unix)]
+impl BeneathEntry {
+    /// Changes the owner and the group of the entry, the ones that are `None` are unchanged.
+    pub(crate) fn chown(
+        &self,
+        owner: Option<super::User>,
+        group: Option<super::Group>,
+    ) -> io::Result<()> {
+        use nix::{
+            fcntl::AtFlags,
+            unistd::{Gid, Uid},
+        };
+        Ok(nix::unistd::fchownat(
+            &self.dir,
+            self.name.as_os_str(),
+            owner.map(|it| Uid::from_raw(it.0.as_raw())),
+            group.map(|it| Gid::from_raw(it.0.as_raw())),
+            AtFlags::AT_SYMLINK_NOFOLLOW,
+        )?)
+    }
+
+    /// Changes the mode of the entry. The mode of a symbolic link is left unchanged on
+    /// systems that do not support it.
+    pub(crate) fn chmod(&self, mode: u32) -> io::Result<()> {
+        use nix::{
+            errno::Errno,
+            sys::stat::{fchmodat, FchmodatFlags, Mode},
+        };
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(file) = &self.file {
+            return file.set_permissions(fs::Permissions::from_mode(mode));
+        }
+        match fchmodat(
+            &self.dir,
+            self.name.as_os_str(),
+            Mode::from_bits_truncate(mode as _),
+            FchmodatFlags::NoFollowSymlink,
+        ) {
+            Err(Errno::EOPNOTSUPP) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Sets the timestamps of the entry, the ones that are `None` are unchanged.
+    pub(crate) fn set_times(&self, times: super::PathTimes) -> io::Result<()> {
+        use nix::sys::stat::{utimensat, UtimensatFlags};
+        utimensat(
+            &self.dir,
+            self.name.as_os_str(),
+            &super::timespec(times.accessed),
+            &super::timespec(times.modified),
+            UtimensatFlags::NoFollowSymlink,
+        )?;
+        #[cfg(target_os = "macos")]
+        if let (Some(file), Some(created)) = (&self.file, times.created) {
+            use std::os::macos::fs::FileTimesExt;
+            file.set_times(fs::FileTimes::new().set_created(created))?;
+        }
+        Ok(())
+    }
+
+    /// Sets the extended attributes of the entry.
+    pub(crate) fn set_xattrs(&self, xattrs: &[pna::ExtendedAttribute]) -> io::Result<()> {
+        use xattr::FileExt;
+        if !xattr::SUPPORTED_PLATFORM {
+            log::warn!("Currently extended attribute is not supported on this platform.");
+            return Ok(());
+        }
+        if let Some(file) = &self.file {
+            for x in xattrs {
+                file.set_xattr(x.name(), x.value())?;
+            }
+            return Ok(());
+        }
+        if xattrs.is_empty() {
+            return Ok(());
+        }
+        // Resolving the path again could follow a symbolic link swapped into one of its
+        // parents, so the entry is reached through the descriptor of its directory.
+        let Some(path) = self.dir_fd_path() else {
+            log::warn!(
+                "{}: skipped restoring extended attributes, which can not be set safely on this platform",
+                self.path.display()
+            );
+            return Ok(());
+        };
+        for x in xattrs {
+            // Does not follow a symbolic link at the entry itself.
+            xattr::set(&path, x.name(), x.value())?;
+        }
+        Ok(())
+    }
+
+    /// Returns a path that refers to the entry through the descriptor of its directory, or
+    /// `None` on systems that do not provide one.
+    fn dir_fd_path(&self) -> Option<PathBuf> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use std::os::fd::AsRawFd;
+            Some(PathBuf::from(format!("/proc/self/fd/{}", self.dir.as_raw_fd())).join(&self.name))
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            None
+        }
+    }
+
+    /// Returns a path that refers to the opened entry itself, or `None` if it is a symbolic
+    /// link or could not be opened.
+    #[cfg(feature = "acl")]
+    pub(crate) fn fd_path(&self) -> Option<PathBuf> {
+        let file = self.file.as_ref()?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use std::os::fd::AsRawFd;
+            Some(PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd())))
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = file;
+            Some(self.path.clone())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl Beneath {
+    /// Opens `root`, creating it if it does not exist. Symbolic links are always followed.
+    pub(crate) fn open(
+        root: impl Into<PathBuf>,
+        _keep_directory_symlink: bool,
+    ) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    fn create_parent(&self, path: &Path) -> io::Result<PathBuf> {
+        split(path)?;
+        let path = self.path(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /// Creates the directory `path` and all of its missing parents.
+    pub(crate) fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(self.create_parent(path)?)
+    }
+
+    /// Creates the file `path` for writing, truncating an existing file with `overwrite`.
+    pub(crate) fn create_file(&self, path: &Path, overwrite: bool) -> io::Result<fs::File> {
+        super::file_create(self.create_parent(path)?, overwrite)
+    }
+
+    /// Creates a new temporary file in the directory of `path`, returning it with its path.
+    pub(crate) fn create_temp_file(&self, path: &Path) -> io::Result<(fs::File, PathBuf)> {
+        let (_, name) = split(path)?;
+        self.create_parent(path)?;
+        loop {
+            let temp = path.with_file_name(temp_name(name));
+            match fs::File::create_new(self.path(&temp)) {
+                Ok(file) => return Ok((file, temp)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Renames `from` to `to`, replacing `to` if it is a file or a symbolic link.
+    pub(crate) fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        split(from)?;
+        fs::rename(self.path(from), self.create_parent(to)?)
+    }
+
+    /// Flushes the directory that contains `path` to the disk where the system supports it.
+    pub(crate) fn sync_parent(&self, path: &Path) -> io::Result<()> {
+        split(path).map(drop)
+    }
+
+    /// Creates a symbolic link at `path` that points to `original`.
+    pub(crate) fn symlink(&self, original: &Path, path: &Path) -> io::Result<()> {
+        pna::fs::symlink(original, self.create_parent(path)?)
+    }
+
+    /// Creates a hard link at `path` to the file `original`, both relative to the root.
+    pub(crate) fn hard_link(&self, original: &Path, path: &Path) -> io::Result<()> {
+        split(original)?;
+        fs::hard_link(self.path(original), self.create_parent(path)?)
+    }
+
+    /// Creates a hard link at `path` to the file `original`, which is resolved from the
+    /// directory of `path` and may point outside of the root.
+    pub(crate) fn hard_link_outside(&self, original: &Path, path: &Path) -> io::Result<()> {
+        let path = self.create_parent(path)?;
+        let parent = path.parent().unwrap_or_else(|| "".as_ref());
+        fs::hard_link(parent.join(original), path)
+    }
+
+    /// Returns the names of the entries in the directory `path`.
+    pub(crate) fn read_dir(&self, path: &Path) -> io::Result<Vec<std::ffi::OsString>> {
+        components(path)?;
+        fs::read_dir(self.path(path))?
+            .map(|entry| entry.map(|it| it.file_name()))
+            .collect()
+    }
+
+    /// Removes the file, symbolic link or directory tree at `path`.
+    pub(crate) fn remove_all(&self, path: &Path) -> io::Result<()> {
+        split(path)?;
+        pna::fs::remove_path_all(self.path(path))
+    }
+
+    /// Returns the entry at `path` to restore its metadata.
+    pub(crate) fn entry(&self, path: &Path) -> io::Result<BeneathEntry> {
+        split(path)?;
+        Ok(BeneathEntry {
+            path: self.path(path),
+        })
+    }
+
+    /// Returns the entry at `path` to restore its metadata, which was just created there.
+    pub(crate) fn entry_of(&self, path: &Path, _file: fs::File) -> io::Result<BeneathEntry> {
+        self.entry(path)
+    }
+}
+
+#[cfg(not(unix))]
+impl BeneathEntry {
+    /// Sets the timestamps of the entry, the ones that are `None` are unchanged.
+    pub(crate) fn set_times(&self, times: super::PathTimes) -> io::Result<()> {
+        super::set_path_times(&self.path, times)
+    }
+}
+
+impl BeneathEntry {
+    /// Returns the path of the entry.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Beneath {
+    /// Returns `path` joined to the root.
+    pub(crate) fn path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
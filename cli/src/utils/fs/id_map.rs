@@ -0,0 +1,204 @@
+use super::{Group, User};
+use std::{collections::BTreeMap, fs, io, path::Path, str::FromStr, sync::Arc};
+
+/// The owner an entry is mapped to by an [IdMap].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub(crate) struct MappedId {
+    /// New name, or `None` to keep the name of the entry.
+    pub(crate) name: Option<String>,
+    /// New id, or `None` to keep the id of the entry.
+    pub(crate) id: Option<u64>,
+}
+
+/// Mapping of user or group names and ids, read from a GNU tar style `--owner-map` or
+/// `--group-map` file.
+///
+/// Each line of the file has the form `OLD NEW[:NEWID]`, where `OLD` is either a name or
+/// `+` followed by an id, and `NEW` is either a name or `+` followed by an id. Without
+/// `NEWID`, the id of `NEW` is looked up on the system, which fails if it does not exist.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub(crate) struct IdMap(Arc<IdMapInner>);
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+struct IdMapInner {
+    names: BTreeMap<String, MappedId>,
+    ids: BTreeMap<u64, MappedId>,
+}
+
+fn invalid_line(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid owner map line: {line:?}"),
+    )
+}
+
+impl IdMap {
+    /// Reads the map file at `path`, looking up the ids of the new names without one with
+    /// `lookup`, which returns `None` on systems without ids.
+    fn from_path(
+        path: &Path,
+        lookup: impl Fn(&str) -> io::Result<Option<u64>>,
+    ) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse::<Self>()
+            .and_then(|it| it.resolve(lookup))
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+    }
+
+    /// Reads the user map file at `path` if given, or returns an empty map.
+    #[inline]
+    pub(crate) fn load_users(path: Option<impl AsRef<Path>>) -> io::Result<Self> {
+        path.map_or_else(
+            || Ok(Self::default()),
+            |path| Self::from_path(path.as_ref(), |name| Ok(User::from_name(name)?.uid())),
+        )
+    }
+
+    /// Reads the group map file at `path` if given, or returns an empty map.
+    #[inline]
+    pub(crate) fn load_groups(path: Option<impl AsRef<Path>>) -> io::Result<Self> {
+        path.map_or_else(
+            || Ok(Self::default()),
+            |path| Self::from_path(path.as_ref(), |name| Ok(Group::from_name(name)?.gid())),
+        )
+    }
+
+    /// Fills in the ids of the new names given without one, as `NEW` is then expected to
+    /// exist on the system.
+    fn resolve(self, lookup: impl Fn(&str) -> io::Result<Option<u64>>) -> io::Result<Self> {
+        let mut inner = Arc::unwrap_or_clone(self.0);
+        for mapped in inner.names.values_mut().chain(inner.ids.values_mut()) {
+            if let (Some(name), None) = (&mapped.name, mapped.id) {
+                mapped.id = lookup(name).map_err(|e| {
+                    io::Error::new(e.kind(), format!("{name}: {e}, give its id as {name}:ID"))
+                })?;
+            }
+        }
+        Ok(Self(Arc::new(inner)))
+    }
+
+    /// Returns the owner that `name` and `id` are mapped to, matching the name first.
+    #[inline]
+    pub(crate) fn get(&self, name: &str, id: u64) -> Option<&MappedId> {
+        self.0.names.get(name).or_else(|| self.0.ids.get(&id))
+    }
+
+    /// Maps `name` and `id`, keeping the ones the map does not replace.
+    #[inline]
+    pub(crate) fn map<'a>(&'a self, name: &'a str, id: u64) -> (&'a str, u64) {
+        match self.get(name, id) {
+            Some(mapped) => (
+                mapped.name.as_deref().unwrap_or(name),
+                mapped.id.unwrap_or(id),
+            ),
+            None => (name, id),
+        }
+    }
+
+    /// Maps an ACL qualifier, which is either a name or an id.
+    #[inline]
+    #[cfg_attr(not(feature = "acl"), allow(dead_code))]
+    pub(crate) fn map_qualifier(&self, qualifier: &str) -> Option<String> {
+        let mapped = match qualifier.parse::<u64>() {
+            Ok(id) => self.0.ids.get(&id),
+            Err(_) => self.0.names.get(qualifier),
+        }?;
+        match (&mapped.name, mapped.id) {
+            (Some(name), _) => Some(name.clone()),
+            (None, Some(id)) => Some(id.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+impl FromStr for IdMap {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut inner = IdMapInner::default();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (Some(old), Some(new), None) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(invalid_line(line));
+            };
+            let (new, new_id) = match new.split_once(':') {
+                Some((name, id)) => (name, Some(id.parse().map_err(|_| invalid_line(line))?)),
+                None => (new, None),
+            };
+            let mapped = match new.strip_prefix('+') {
+                Some(_) if new_id.is_some() => return Err(invalid_line(line)),
+                Some(id) => MappedId {
+                    name: None,
+                    id: Some(id.parse().map_err(|_| invalid_line(line))?),
+                },
+                None => MappedId {
+                    name: Some(new.into()),
+                    id: new_id,
+                },
+            };
+            match old.strip_prefix('+') {
+                Some(id) => {
+                    let id = id.parse().map_err(|_| invalid_line(line))?;
+                    inner.ids.insert(id, mapped);
+                }
+                None => {
+                    inner.names.insert(old.into(), mapped);
+                }
+            }
+        }
+        Ok(Self(Arc::new(inner)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_id_map() {
+        let map = "# comment\n\nalice bob:1001\n+1000 carol\n+2000 +3000\n"
+            .parse::<IdMap>()
+            .unwrap();
+        assert_eq!(map.map("alice", 1), ("bob", 1001));
+        assert_eq!(map.map("dave", 1000), ("carol", 1000));
+        assert_eq!(map.map("", 2000), ("", 3000));
+        assert_eq!(map.map("dave", 1), ("dave", 1));
+        assert_eq!(map.map_qualifier("alice").as_deref(), Some("bob"));
+        assert_eq!(map.map_qualifier("2000").as_deref(), Some("3000"));
+        assert_eq!(map.map_qualifier("dave"), None);
+    }
+
+    #[test]
+    fn resolve_id_map() {
+        let map = "alice bob\n+1000 carol:1003\n+2000 +3000\n"
+            .parse::<IdMap>()
+            .unwrap()
+            .resolve(|name| match name {
+                "bob" => Ok(Some(1002)),
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+            })
+            .unwrap();
+        assert_eq!(map.map("alice", 1), ("bob", 1002));
+        assert_eq!(map.map("dave", 1000), ("carol", 1003));
+        assert_eq!(map.map("", 2000), ("", 3000));
+
+        assert!("alice dave\n"
+            .parse::<IdMap>()
+            .unwrap()
+            .resolve(|_| Err(io::Error::new(io::ErrorKind::NotFound, "not found")))
+            .is_err());
+    }
+
+    #[test]
+    fn parse_invalid_id_map() {
+        assert!("alice".parse::<IdMap>().is_err());
+        assert!("alice bob:x".parse::<IdMap>().is_err());
+        assert!("+x bob".parse::<IdMap>().is_err());
+        assert!("+1000 +3000:5".parse::<IdMap>().is_err());
+    }
+}
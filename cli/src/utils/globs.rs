@@ -41,7 +41,7 @@ impl TryFrom<Vec<globset::Glob>> for GlobPatterns {
 }
 
 /// BSD tar command like globs.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub(crate) struct BsdGlobPatterns(Vec<BsdGlobPattern>);
 
 impl BsdGlobPatterns {
@@ -1,21 +1,10 @@
-use std::{fs, io, ops::Deref, path::Path};
+use std::{fs, io, ops::Deref};
 
 pub(crate) struct Mmap {
     _file: fs::File,
     inner: memmap2::Mmap,
 }
 
-impl Mmap {
-    #[inline]
-    pub(crate) fn open<P: AsRef<Path>>(path: P) -> io::Result<Mmap> {
-        fn inner(path: &Path) -> io::Result<Mmap> {
-            let file = fs::File::open(path)?;
-            Mmap::try_from(file)
-        }
-        inner(path.as_ref())
-    }
-}
-
 impl AsRef<[u8]> for Mmap {
     #[inline]
     fn as_ref(&self) -> &[u8] {
@@ -18,18 +18,3 @@ pub(crate) fn get_xattrs<P: AsRef<Path>>(path: P) -> io::Result<Vec<ExtendedAttr
         Ok(Vec::new())
     }
 }
-
-pub(crate) fn set_xattrs<P: AsRef<Path>>(path: P, xattrs: &[ExtendedAttribute]) -> io::Result<()> {
-    fn inner(path: &Path, xattrs: &[ExtendedAttribute]) -> io::Result<()> {
-        for x in xattrs {
-            xattr::set(path, x.name(), x.value())?;
-        }
-        Ok(())
-    }
-    if xattr::SUPPORTED_PLATFORM {
-        inner(path.as_ref(), xattrs)
-    } else {
-        log::warn!("Currently extended attribute is not supported on this platform.");
-        Ok(())
-    }
-}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 pub(crate) trait PathPartExt {
     fn with_part(&self, n: usize) -> Option<PathBuf>;
@@ -73,6 +73,43 @@ fn remove_part_n<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
     inner(path.as_ref())
 }
 
+/// Returns the reference to `target` from the directory of `link`, where both are relative to
+/// the same root, as hard link entries store it.
+pub(crate) fn reference_from_parent(link: &Path, target: &Path) -> PathBuf {
+    let mut parent = link
+        .parent()
+        .unwrap_or_else(|| "".as_ref())
+        .components()
+        .peekable();
+    let mut target = target.components().peekable();
+    while parent.peek().is_some() && parent.peek() == target.peek() {
+        parent.next();
+        target.next();
+    }
+    parent.map(|_| Component::ParentDir).chain(target).collect()
+}
+
+/// Resolves `reference`, as stored in the hard link entry `link`, into a path relative to the
+/// root, the inverse of [`reference_from_parent`].
+pub(crate) fn resolve_from_parent(link: &Path, reference: &Path) -> PathBuf {
+    let mut resolved = link.parent().map(Path::to_path_buf).unwrap_or_default();
+    for component in reference.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    resolved.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +181,48 @@ mod tests {
             PathBuf::from("dir/foo")
         );
     }
+
+    #[test]
+    fn reference_from_parent_dir() {
+        assert_eq!(
+            reference_from_parent("a.txt".as_ref(), "b.txt".as_ref()),
+            PathBuf::from("b.txt")
+        );
+        assert_eq!(
+            reference_from_parent("dir/a.txt".as_ref(), "dir/b.txt".as_ref()),
+            PathBuf::from("b.txt")
+        );
+        assert_eq!(
+            reference_from_parent("dir/a.txt".as_ref(), "dir/sub/b.txt".as_ref()),
+            PathBuf::from("sub/b.txt")
+        );
+        assert_eq!(
+            reference_from_parent("dir/sub/a.txt".as_ref(), "other/b.txt".as_ref()),
+            PathBuf::from("../../other/b.txt")
+        );
+        assert_eq!(
+            reference_from_parent("dir/a.txt".as_ref(), "b.txt".as_ref()),
+            PathBuf::from("../b.txt")
+        );
+    }
+
+    #[test]
+    fn resolve_from_parent_dir() {
+        assert_eq!(
+            resolve_from_parent("a.txt".as_ref(), "b.txt".as_ref()),
+            PathBuf::from("b.txt")
+        );
+        assert_eq!(
+            resolve_from_parent("dir/a.txt".as_ref(), "sub/b.txt".as_ref()),
+            PathBuf::from("dir/sub/b.txt")
+        );
+        assert_eq!(
+            resolve_from_parent("dir/sub/a.txt".as_ref(), "../../other/./b.txt".as_ref()),
+            PathBuf::from("other/b.txt")
+        );
+        assert_eq!(
+            resolve_from_parent("dir/a.txt".as_ref(), "../../b.txt".as_ref()),
+            PathBuf::from("../b.txt")
+        );
+    }
 }
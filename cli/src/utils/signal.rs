@@ -0,0 +1,61 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches the next interrupt or termination request instead of terminating the process, so
+/// that the running command can stop where it is able to undo its changes.
+///
+/// Only the first request is caught, a second one terminates the process as usual.
+pub(crate) fn catch_interrupt() -> io::Result<()> {
+    #[cfg(unix)]
+    fn inner() -> io::Result<()> {
+        use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+        extern "C" fn handler(_: nix::libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+        let action = SigAction::new(
+            SigHandler::Handler(handler),
+            SaFlags::SA_RESETHAND | SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        for signal in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP] {
+            // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+            unsafe { sigaction(signal, &action) }?;
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    fn inner() -> io::Result<()> {
+        // The C runtime resets the handler to the default before calling it.
+        extern "C" fn handler(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: the handler only stores to an atomic.
+            if unsafe { libc::signal(signal, handler as libc::sighandler_t) } == libc::SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+    #[cfg(not(any(unix, windows)))]
+    fn inner() -> io::Result<()> {
+        Ok(())
+    }
+    inner()
+}
+
+/// Returns an error if an interrupt or termination request was caught by [catch_interrupt].
+pub(crate) fn check_interrupt() -> io::Result<()> {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "Interrupted by a signal",
+        ))
+    } else {
+        Ok(())
+    }
+}
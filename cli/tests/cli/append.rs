@@ -1,5 +1,6 @@
 mod atime;
 mod ctime;
+mod dry_run;
 mod exclude;
 mod mtime;
 
@@ -0,0 +1,59 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn append_dry_run() {
+    setup();
+    let _ = fs::remove_dir_all("append_dry_run");
+    fs::create_dir_all("append_dry_run/in").unwrap();
+    fs::write("append_dry_run/in/a.txt", "a").unwrap();
+    fs::write("append_dry_run/in/b.txt", "b").unwrap();
+    fs::write("append_dry_run/not_archive.pna", "not an archive").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "append_dry_run/dry_run.pna",
+        "--overwrite",
+        "append_dry_run/in/a.txt",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let before = fs::read("append_dry_run/dry_run.pna").unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "a",
+        "append_dry_run/dry_run.pna",
+        "append_dry_run/in/b.txt",
+        "--dry-run",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert_eq!(fs::read("append_dry_run/dry_run.pna").unwrap(), before);
+
+    for archive in [
+        "append_dry_run/missing.pna",
+        "append_dry_run/not_archive.pna",
+    ] {
+        let result = cli::Cli::try_parse_from([
+            "pna",
+            "--quiet",
+            "a",
+            archive,
+            "append_dry_run/in/b.txt",
+            "--dry-run",
+            "--unstable",
+        ])
+        .unwrap()
+        .execute();
+        assert!(result.is_err());
+    }
+    assert!(!fs::exists("append_dry_run/missing.pna").unwrap());
+}
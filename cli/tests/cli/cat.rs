@@ -0,0 +1,161 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn cat_entries() {
+    setup();
+    fs::create_dir_all("cat_entries/in").unwrap();
+    fs::write("cat_entries/in/a.txt", "aaa\n").unwrap();
+    fs::write("cat_entries/in/b.txt", "bbb\n").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "cat_entries/cat.pna",
+        "--overwrite",
+        "--solid",
+        "--password",
+        "password",
+        "cat_entries/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "cat_entries/split.pna",
+        "--overwrite",
+        "--password",
+        "password",
+        "cat_entries/in/",
+        "--unstable",
+        "--split",
+        "150",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "experimental",
+        "cat",
+        "cat_entries/split.part1.pna",
+        "--password",
+        "password",
+        "cat_entries/in/b.txt",
+    ])
+    .assert()
+    .success()
+    .stdout("bbb\n");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "x",
+        "cat_entries/cat.pna",
+        "-O",
+        "--password",
+        "password",
+        "cat_entries/in/a.txt",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("aaa\n");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "experimental",
+        "stdio",
+        "-x",
+        "-O",
+        "--password",
+        "password",
+        "-f",
+        "cat_entries/cat.pna",
+        "cat_entries/in/a.txt",
+    ])
+    .assert()
+    .success()
+    .stdout("aaa\n");
+}
+
+#[test]
+fn extract_to_stdout_selection() {
+    setup();
+    fs::create_dir_all("extract_to_stdout_selection/in").unwrap();
+    fs::write("extract_to_stdout_selection/in/a.txt", "aaa\n").unwrap();
+    fs::write("extract_to_stdout_selection/in/b.log", "bbbbbb\n").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "extract_to_stdout_selection/cat.pna",
+        "--overwrite",
+        "extract_to_stdout_selection/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    fs::write("extract_to_stdout_selection/in/a.txt", "AAA\n").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "a",
+        "extract_to_stdout_selection/cat.pna",
+        "extract_to_stdout_selection/in/a.txt",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "x",
+        "extract_to_stdout_selection/cat.pna",
+        "-O",
+        "--exclude",
+        "*.log",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("aaa\nAAA\n");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "x",
+        "extract_to_stdout_selection/cat.pna",
+        "-O",
+        "--where",
+        "size > 5",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("bbbbbb\n");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "x",
+        "extract_to_stdout_selection/cat.pna",
+        "-O",
+        "--version",
+        "1",
+        "extract_to_stdout_selection/in/a.txt",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("aaa\n");
+}
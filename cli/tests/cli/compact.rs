@@ -0,0 +1,155 @@
+use crate::utils::{archive::for_each_entry, setup};
+use clap::Parser;
+use pna::{
+    Archive, CipherMode, DataKind, Encryption, EntryBuilder, HashAlgorithm, ReadEntry, ReadOptions,
+    SolidEntryBuilder, WriteOptions,
+};
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io, io::prelude::*};
+
+fn write_archive(path: &str) {
+    let file = fs::File::create(path).unwrap();
+    let mut archive = Archive::write_header(file).unwrap();
+    for (name, content) in [
+        ("a.txt", "a1"),
+        ("b.txt", "b1"),
+        ("c.txt", "c1"),
+        ("a.txt", "a2"),
+    ] {
+        let mut builder = EntryBuilder::new_file(name.into(), WriteOptions::store()).unwrap();
+        builder.write_all(content.as_bytes()).unwrap();
+        archive.add_entry(builder.build().unwrap()).unwrap();
+    }
+    archive
+        .add_entry(EntryBuilder::new_whiteout("c.txt".into()).build().unwrap())
+        .unwrap();
+    archive.finalize().unwrap();
+}
+
+fn entries(path: &str) -> Vec<(String, DataKind, String)> {
+    let mut entries = Vec::new();
+    for_each_entry(path, |entry| {
+        let content = entry
+            .reader(ReadOptions::builder().build())
+            .and_then(io::read_to_string)
+            .unwrap();
+        entries.push((
+            entry.header().path().to_string(),
+            entry.header().data_kind(),
+            content,
+        ));
+    })
+    .unwrap();
+    entries
+}
+
+#[test]
+fn compact_archive() {
+    setup();
+    fs::create_dir_all("compact_archive").unwrap();
+    write_archive("compact_archive/src.pna");
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "compact",
+        "compact_archive/src.pna",
+        "--output",
+        "compact_archive/compacted.pna",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert_eq!(
+        entries("compact_archive/compacted.pna"),
+        [
+            ("b.txt".into(), DataKind::File, "b1".into()),
+            ("a.txt".into(), DataKind::File, "a2".into()),
+            ("c.txt".into(), DataKind::Whiteout, String::new()),
+        ]
+    );
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "compact",
+        "compact_archive/src.pna",
+        "--drop-deleted",
+        "--output",
+        "compact_archive/dropped.pna",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert_eq!(
+        entries("compact_archive/dropped.pna"),
+        [
+            ("b.txt".into(), DataKind::File, "b1".into()),
+            ("a.txt".into(), DataKind::File, "a2".into()),
+        ]
+    );
+}
+
+#[test]
+fn compact_solid_archive_with_multiple_password_files() {
+    setup();
+    let base = "compact_solid_archive_with_multiple_password_files";
+    fs::create_dir_all(base).unwrap();
+    let file = fs::File::create(format!("{base}/src.pna")).unwrap();
+    let mut archive = Archive::write_header(file).unwrap();
+    for (password, content) in [("first password", "a1"), ("second password", "a2")] {
+        fs::write(format!("{base}/{content}_password"), password).unwrap();
+        let mut solid = SolidEntryBuilder::new(
+            WriteOptions::builder()
+                .encryption(Encryption::Aes)
+                .cipher_mode(CipherMode::CBC)
+                .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+                .password(Some(password))
+                .build(),
+        )
+        .unwrap();
+        let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(content.as_bytes()).unwrap();
+        solid.add_entry(builder.build().unwrap()).unwrap();
+        archive.add_entry(solid.build().unwrap()).unwrap();
+    }
+    archive.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "compact",
+        &format!("{base}/src.pna"),
+        "--output",
+        &format!("{base}/compacted.pna"),
+        "--password-file",
+        &format!("{base}/a1_password"),
+        "--password-file",
+        &format!("{base}/a2_password"),
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let read_options = ReadOptions::with_passwords(["first password", "second password"]);
+    let file = fs::File::open(format!("{base}/compacted.pna")).unwrap();
+    let mut archive = Archive::read_header(file).unwrap();
+    let mut entries = Vec::new();
+    for entry in archive.entries() {
+        let ReadEntry::Solid(solid) = entry.unwrap() else {
+            panic!("expected a solid entry");
+        };
+        for entry in solid.entries_with_options(&read_options).unwrap() {
+            let entry = entry.unwrap();
+            let content = entry
+                .reader(ReadOptions::builder().build())
+                .and_then(io::read_to_string)
+                .unwrap();
+            entries.push((entry.header().path().to_string(), content));
+        }
+    }
+    assert_eq!(entries, [("a.txt".into(), "a2".into())]);
+}
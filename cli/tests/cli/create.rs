@@ -1,16 +1,25 @@
 mod atime;
+mod atime_preserve;
 mod ctime;
+mod dry_run;
 mod exclude;
 mod exclude_from;
+mod exclusion;
 mod files_from;
 mod files_from_stdin;
 mod gitignore;
+mod hard_link;
 mod include;
+mod incremental;
 mod mtime;
 mod no_recursive;
 mod numeric_owner;
+mod owner_map;
 mod password_from_file;
 mod password_hash;
+mod progress;
+mod reproducible;
+mod resume;
 mod substitution;
 mod symlink;
 mod transform;
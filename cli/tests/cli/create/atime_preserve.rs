@@ -0,0 +1,57 @@
+use crate::utils::{archive, setup};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{
+    fs,
+    time::{Duration, UNIX_EPOCH},
+};
+
+fn create_with_atime_preserve(dir: &str, option: &str) {
+    setup();
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(format!("{dir}/in")).unwrap();
+    let path = format!("{dir}/in/a.txt");
+    fs::write(&path, "a").unwrap();
+    // Older than the modification time, so that reading it updates it even with relatime.
+    let accessed = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(accessed))
+        .unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{dir}/atime_preserve.pna"),
+        "--overwrite",
+        &path,
+        "--keep-timestamp",
+        option,
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(fs::metadata(&path).unwrap().accessed().unwrap(), accessed);
+    archive::for_each_entry(format!("{dir}/atime_preserve.pna"), |entry| {
+        assert_eq!(
+            entry.metadata().accessed(),
+            Some(Duration::from_secs(1_000_000_000))
+        );
+    })
+    .unwrap();
+}
+
+#[test]
+fn create_atime_preserve_replace() {
+    create_with_atime_preserve("create_atime_preserve_replace", "--atime-preserve");
+}
+
+#[test]
+fn create_atime_preserve_system() {
+    create_with_atime_preserve("create_atime_preserve_system", "--atime-preserve=system");
+}
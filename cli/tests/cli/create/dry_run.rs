@@ -0,0 +1,25 @@
+use crate::utils::{setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn create_dry_run() {
+    setup();
+    TestResources::extract_in("raw/", "create_dry_run/in/").unwrap();
+    let _ = fs::remove_file("create_dry_run/dry_run.pna");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_dry_run/dry_run.pna",
+        "create_dry_run/in/",
+        "--dry-run",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert!(!fs::exists("create_dry_run/dry_run.pna").unwrap());
+}
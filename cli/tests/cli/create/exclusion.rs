@@ -0,0 +1,85 @@
+use crate::utils::{archive, setup};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{collections::BTreeSet, fs};
+
+fn init(dir: &str) {
+    setup();
+    let _ = fs::remove_dir_all(dir);
+    for sub in ["keep", "cache", "not_cache", ".git", "tagged", "ignored"] {
+        fs::create_dir_all(format!("{dir}/in/{sub}")).unwrap();
+        fs::write(format!("{dir}/in/{sub}/a.txt"), "a").unwrap();
+    }
+    fs::write(
+        format!("{dir}/in/cache/CACHEDIR.TAG"),
+        "Signature: 8a477f597d28d172789f06886806bc55\n# cache directory tag\n",
+    )
+    .unwrap();
+    fs::write(format!("{dir}/in/not_cache/CACHEDIR.TAG"), "not a tag").unwrap();
+    fs::write(format!("{dir}/in/tagged/.nobackup"), "").unwrap();
+    fs::write(format!("{dir}/in/.pnaignore"), "ignored/\n*.log\n").unwrap();
+    fs::write(format!("{dir}/in/keep/b.log"), "b").unwrap();
+}
+
+fn entries(archive: &str) -> BTreeSet<String> {
+    let mut entries = BTreeSet::new();
+    archive::for_each_entry(archive, |entry| {
+        entries.insert(entry.header().path().to_string());
+    })
+    .unwrap();
+    entries
+}
+
+#[test]
+fn create_with_exclusion_rules() {
+    init("create_with_exclusion_rules");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_with_exclusion_rules/exclusion.pna",
+        "--overwrite",
+        "create_with_exclusion_rules/in/",
+        "--exclude-caches",
+        "--exclude-vcs",
+        "--exclude-if-present",
+        ".nobackup",
+        "--pnaignore",
+        "--one-file-system",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert_eq!(
+        entries("create_with_exclusion_rules/exclusion.pna"),
+        BTreeSet::from(
+            [
+                "create_with_exclusion_rules/in/.pnaignore",
+                "create_with_exclusion_rules/in/keep/a.txt",
+                "create_with_exclusion_rules/in/not_cache/CACHEDIR.TAG",
+                "create_with_exclusion_rules/in/not_cache/a.txt",
+            ]
+            .map(String::from)
+        )
+    );
+}
+
+#[test]
+fn create_without_exclusion_rules() {
+    init("create_without_exclusion_rules");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_without_exclusion_rules/exclusion.pna",
+        "--overwrite",
+        "create_without_exclusion_rules/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let entries = entries("create_without_exclusion_rules/exclusion.pna");
+    assert_eq!(entries.len(), 11);
+    assert!(entries.contains("create_without_exclusion_rules/in/.git/a.txt"));
+}
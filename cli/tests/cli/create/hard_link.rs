@@ -0,0 +1,109 @@
+use crate::utils::{archive::for_each_entry, diff::diff, setup};
+use clap::Parser;
+use pna::DataKind;
+use portable_network_archive::{cli, command::Command};
+use std::{collections::HashMap, fs};
+
+fn archived_kinds(archive: &str) -> HashMap<String, DataKind> {
+    let mut entries = HashMap::new();
+    for_each_entry(archive, |entry| {
+        entries.insert(
+            entry.header().path().to_string(),
+            entry.header().data_kind(),
+        );
+    })
+    .unwrap();
+    entries
+}
+
+#[cfg(unix)]
+#[test]
+fn create_hard_link() {
+    use std::os::unix::fs::MetadataExt;
+    setup();
+    let _ = fs::remove_dir_all("create_hard_link");
+    fs::create_dir_all("create_hard_link/in/dir").unwrap();
+    fs::create_dir_all("create_hard_link/in/x").unwrap();
+    fs::create_dir_all("create_hard_link/in/y").unwrap();
+    fs::write("create_hard_link/in/a.txt", "hard linked").unwrap();
+    fs::hard_link("create_hard_link/in/a.txt", "create_hard_link/in/b.txt").unwrap();
+    fs::hard_link("create_hard_link/in/a.txt", "create_hard_link/in/dir/c.txt").unwrap();
+    // Sibling directories can only be linked with `..`, so these are archived as copies.
+    fs::write("create_hard_link/in/x/p.txt", "sibling").unwrap();
+    fs::hard_link("create_hard_link/in/x/p.txt", "create_hard_link/in/y/q.txt").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_hard_link/hard_link.pna",
+        "--overwrite",
+        "create_hard_link/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let kinds = archived_kinds("create_hard_link/hard_link.pna");
+    let count = |kind| kinds.values().filter(|it| **it == kind).count();
+    assert_eq!(count(DataKind::File), 3);
+    assert_eq!(count(DataKind::HardLink), 2);
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "create_hard_link/hard_link.pna",
+        "--overwrite",
+        "--out-dir",
+        "create_hard_link/out/",
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    diff("create_hard_link/in/", "create_hard_link/out/").unwrap();
+    let ino = |path| fs::metadata(path).unwrap().ino();
+    assert_eq!(
+        ino("create_hard_link/out/a.txt"),
+        ino("create_hard_link/out/b.txt")
+    );
+    assert_eq!(
+        ino("create_hard_link/out/a.txt"),
+        ino("create_hard_link/out/dir/c.txt")
+    );
+}
+
+#[test]
+fn create_hard_dereference() {
+    setup();
+    let _ = fs::remove_dir_all("create_hard_dereference");
+    fs::create_dir_all("create_hard_dereference/in").unwrap();
+    fs::write("create_hard_dereference/in/a.txt", "hard linked").unwrap();
+    fs::hard_link(
+        "create_hard_dereference/in/a.txt",
+        "create_hard_dereference/in/b.txt",
+    )
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_hard_dereference/hard_link.pna",
+        "--overwrite",
+        "create_hard_dereference/in/",
+        "--hard-dereference",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        archived_kinds("create_hard_dereference/hard_link.pna"),
+        HashMap::from([
+            ("create_hard_dereference/in/a.txt".into(), DataKind::File),
+            ("create_hard_dereference/in/b.txt".into(), DataKind::File),
+        ])
+    );
+}
@@ -0,0 +1,85 @@
+use crate::utils::{archive::for_each_entry, diff::diff, setup};
+use clap::Parser;
+use pna::DataKind;
+use portable_network_archive::{cli, command::Command};
+use std::{collections::HashMap, fs};
+
+#[test]
+fn create_incremental() {
+    setup();
+    let _ = fs::remove_dir_all("create_incremental");
+    fs::create_dir_all("create_incremental/in").unwrap();
+    fs::write("create_incremental/in/a.txt", "a").unwrap();
+    fs::write("create_incremental/in/b.txt", "b").unwrap();
+    fs::write("create_incremental/in/c.txt", "c").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_incremental/full.pna",
+        "--overwrite",
+        "create_incremental/in/",
+        "--incremental",
+        "create_incremental/snapshot.json",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    fs::write("create_incremental/in/b.txt", "bb").unwrap();
+    fs::remove_file("create_incremental/in/c.txt").unwrap();
+    fs::write("create_incremental/in/d.txt", "d").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_incremental/level1.pna",
+        "--overwrite",
+        "create_incremental/in/",
+        "--incremental",
+        "create_incremental/snapshot.json",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut entries = HashMap::new();
+    for_each_entry("create_incremental/level1.pna", |entry| {
+        entries.insert(
+            entry.header().path().to_string(),
+            entry.header().data_kind(),
+        );
+    })
+    .unwrap();
+    assert_eq!(
+        entries,
+        HashMap::from([
+            ("create_incremental/in/b.txt".into(), DataKind::File),
+            ("create_incremental/in/c.txt".into(), DataKind::Whiteout),
+            ("create_incremental/in/d.txt".into(), DataKind::File),
+        ])
+    );
+
+    for archive in [
+        "create_incremental/full.pna",
+        "create_incremental/level1.pna",
+    ] {
+        cli::Cli::try_parse_from([
+            "pna",
+            "--quiet",
+            "x",
+            archive,
+            "--overwrite",
+            "--out-dir",
+            "create_incremental/out/",
+            "--strip-components",
+            "2",
+        ])
+        .unwrap()
+        .execute()
+        .unwrap();
+    }
+    diff("create_incremental/in/", "create_incremental/out/").unwrap();
+}
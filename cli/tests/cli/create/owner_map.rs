@@ -0,0 +1,147 @@
+#![cfg(unix)]
+use crate::utils::{archive, setup};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{fs, os::unix::fs::MetadataExt};
+
+#[test]
+fn archive_create_owner_map() {
+    setup();
+    let _ = fs::remove_dir_all("archive_create_owner_map");
+    fs::create_dir_all("archive_create_owner_map/in").unwrap();
+    fs::write("archive_create_owner_map/in/a.txt", "a").unwrap();
+    let meta = fs::metadata("archive_create_owner_map/in/a.txt").unwrap();
+    fs::write(
+        "archive_create_owner_map/owner_map",
+        format!("# local owner\n+{} mapped_user:1001\n", meta.uid()),
+    )
+    .unwrap();
+    fs::write(
+        "archive_create_owner_map/group_map",
+        format!("+{} +1002\n", meta.gid()),
+    )
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "archive_create_owner_map/owner_map.pna",
+        "--overwrite",
+        "archive_create_owner_map/in/a.txt",
+        "--keep-permission",
+        "--unstable",
+        "--owner-map",
+        "archive_create_owner_map/owner_map",
+        "--group-map",
+        "archive_create_owner_map/group_map",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    archive::for_each_entry("archive_create_owner_map/owner_map.pna", |entry| {
+        let permission = entry.metadata().permission().unwrap();
+        assert_eq!(permission.uname(), "mapped_user");
+        assert_eq!(permission.uid(), 1001);
+        assert_eq!(permission.gid(), 1002);
+    })
+    .unwrap();
+}
+
+#[test]
+fn archive_create_owner_map_with_uname() {
+    setup();
+    let _ = fs::remove_dir_all("archive_create_owner_map_with_uname");
+    fs::create_dir_all("archive_create_owner_map_with_uname/in").unwrap();
+    fs::write("archive_create_owner_map_with_uname/in/a.txt", "a").unwrap();
+    let meta = fs::metadata("archive_create_owner_map_with_uname/in/a.txt").unwrap();
+    fs::write(
+        "archive_create_owner_map_with_uname/owner_map",
+        format!("+{} mapped_user:1001\n", meta.uid()),
+    )
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "archive_create_owner_map_with_uname/owner_map.pna",
+        "--overwrite",
+        "archive_create_owner_map_with_uname/in/a.txt",
+        "--keep-permission",
+        "--unstable",
+        "--owner-map",
+        "archive_create_owner_map_with_uname/owner_map",
+        "--uname",
+        "forced_user",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    archive::for_each_entry(
+        "archive_create_owner_map_with_uname/owner_map.pna",
+        |entry| {
+            let permission = entry.metadata().permission().unwrap();
+            assert_eq!(permission.uname(), "forced_user");
+            assert_eq!(permission.uid(), 1001);
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn archive_create_owner_map_resolve_name() {
+    setup();
+    let _ = fs::remove_dir_all("archive_create_owner_map_resolve_name");
+    fs::create_dir_all("archive_create_owner_map_resolve_name/in").unwrap();
+    fs::write("archive_create_owner_map_resolve_name/in/a.txt", "a").unwrap();
+    let meta = fs::metadata("archive_create_owner_map_resolve_name/in/a.txt").unwrap();
+    fs::write(
+        "archive_create_owner_map_resolve_name/owner_map",
+        format!("+{} root\n", meta.uid()),
+    )
+    .unwrap();
+    fs::write(
+        "archive_create_owner_map_resolve_name/unknown_map",
+        format!("+{} no_such_user_for_pna\n", meta.uid()),
+    )
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "archive_create_owner_map_resolve_name/owner_map.pna",
+        "--overwrite",
+        "archive_create_owner_map_resolve_name/in/a.txt",
+        "--keep-permission",
+        "--unstable",
+        "--owner-map",
+        "archive_create_owner_map_resolve_name/owner_map",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    archive::for_each_entry(
+        "archive_create_owner_map_resolve_name/owner_map.pna",
+        |entry| {
+            let permission = entry.metadata().permission().unwrap();
+            assert_eq!(permission.uname(), "root");
+            assert_eq!(permission.uid(), 0);
+        },
+    )
+    .unwrap();
+
+    assert!(cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "archive_create_owner_map_resolve_name/unknown_map.pna",
+        "--overwrite",
+        "archive_create_owner_map_resolve_name/in/a.txt",
+        "--keep-permission",
+        "--unstable",
+        "--owner-map",
+        "archive_create_owner_map_resolve_name/unknown_map",
+    ])
+    .unwrap()
+    .execute()
+    .is_err());
+}
@@ -173,3 +173,45 @@ fn aes_ctr_pbkdf2_with_params_archive() {
     )
     .unwrap();
 }
+
+#[test]
+fn aes_ctr_pbkdf2_archive_key() {
+    setup();
+    TestResources::extract_in("raw/", "aes_pbkdf2_archive_key/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "aes_pbkdf2_archive_key/zstd_aes_pbkdf2_archive_key.pna",
+        "--overwrite",
+        "aes_pbkdf2_archive_key/in/",
+        "--password",
+        "password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "--archive-key",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "aes_pbkdf2_archive_key/zstd_aes_pbkdf2_archive_key.pna",
+        "--overwrite",
+        "--out-dir",
+        "aes_pbkdf2_archive_key/out/",
+        "--password",
+        "password",
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    diff("aes_pbkdf2_archive_key/in/", "aes_pbkdf2_archive_key/out/").unwrap();
+}
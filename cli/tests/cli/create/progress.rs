@@ -0,0 +1,83 @@
+use crate::utils::{diff::diff, setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+
+#[test]
+fn create_extract_with_progress() {
+    setup();
+    TestResources::extract_in("raw/", "create_with_progress/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_with_progress/progress.pna",
+        "--overwrite",
+        "create_with_progress/in/",
+        "--progress",
+        "--totals",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "create_with_progress/progress.pna",
+        "--overwrite",
+        "--out-dir",
+        "create_with_progress/out/",
+        "--strip-components",
+        "2",
+        "--progress",
+        "--totals",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    diff("create_with_progress/in/", "create_with_progress/out/").unwrap();
+}
+
+#[test]
+fn create_split_with_progress() {
+    setup();
+    TestResources::extract_in("raw/", "create_split_with_progress/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_split_with_progress/progress.pna",
+        "--overwrite",
+        "create_split_with_progress/in/",
+        "--split",
+        "30kb",
+        "--unstable",
+        "--progress",
+        "--totals",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "create_split_with_progress/progress.part1.pna",
+        "--overwrite",
+        "--out-dir",
+        "create_split_with_progress/out/",
+        "--strip-components",
+        "2",
+        "--progress",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    diff(
+        "create_split_with_progress/in/",
+        "create_split_with_progress/out/",
+    )
+    .unwrap();
+}
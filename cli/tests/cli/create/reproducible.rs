@@ -0,0 +1,136 @@
+#![cfg(unix)]
+use crate::utils::{archive, setup};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{
+    fs,
+    time::{Duration, UNIX_EPOCH},
+};
+
+fn init(dir: &str) {
+    setup();
+    let _ = fs::remove_dir_all(dir);
+    for name in ["c", "a", "b"] {
+        fs::create_dir_all(format!("{dir}/in/{name}")).unwrap();
+        fs::write(format!("{dir}/in/{name}/{name}.txt"), name).unwrap();
+    }
+}
+
+fn create(dir: &str, archive: &str, options: &[&str]) -> anyhow::Result<()> {
+    cli::Cli::try_parse_from(
+        [
+            "pna",
+            "--quiet",
+            "c",
+            &format!("{dir}/{archive}"),
+            "--overwrite",
+            &format!("{dir}/in/"),
+            "--keep-dir",
+            "--keep-timestamp",
+            "--keep-permission",
+            "--reproducible",
+            "--mtime",
+            "2024-01-01T00:00:00Z",
+            "--clamp-mtime",
+            "--unstable",
+        ]
+        .into_iter()
+        .chain(options.iter().copied()),
+    )
+    .unwrap()
+    .execute()
+}
+
+fn touch(dir: &str) {
+    let accessed = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::open(format!("{dir}/in/a/a.txt"))
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(accessed))
+        .unwrap();
+}
+
+#[test]
+fn create_reproducible() {
+    init("create_reproducible");
+    create("create_reproducible", "1.pna", &[]).unwrap();
+    touch("create_reproducible");
+    create("create_reproducible", "2.pna", &[]).unwrap();
+    assert_eq!(
+        fs::read("create_reproducible/1.pna").unwrap(),
+        fs::read("create_reproducible/2.pna").unwrap()
+    );
+
+    let mut names = Vec::new();
+    archive::for_each_entry("create_reproducible/1.pna", |entry| {
+        let metadata = entry.metadata();
+        assert_eq!(metadata.accessed(), None);
+        assert_eq!(metadata.created(), None);
+        assert!(metadata.modified().unwrap() <= Duration::from_secs(1_704_067_200));
+        let permission = metadata.permission().unwrap();
+        assert_eq!((permission.uname(), permission.uid()), ("root", 0));
+        assert_eq!((permission.gname(), permission.gid()), ("root", 0));
+        names.push(entry.header().path().to_string());
+    })
+    .unwrap();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+}
+
+#[test]
+fn create_reproducible_encrypted() {
+    init("create_reproducible_encrypted");
+    let options = [
+        "--password",
+        "password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "r=1",
+        "--random-seed",
+        "seed",
+    ];
+    create("create_reproducible_encrypted", "1.pna", &options).unwrap();
+    touch("create_reproducible_encrypted");
+    create("create_reproducible_encrypted", "2.pna", &options).unwrap();
+    assert_eq!(
+        fs::read("create_reproducible_encrypted/1.pna").unwrap(),
+        fs::read("create_reproducible_encrypted/2.pna").unwrap()
+    );
+}
+
+#[test]
+fn create_reproducible_encrypted_without_seed() {
+    init("create_reproducible_encrypted_without_seed");
+    assert!(create(
+        "create_reproducible_encrypted_without_seed",
+        "1.pna",
+        &["--password", "password"],
+    )
+    .is_err());
+}
+
+#[test]
+fn create_reproducible_whiteout() {
+    init("create_reproducible_whiteout");
+    let options = [
+        "--incremental",
+        "create_reproducible_whiteout/snapshot.json",
+    ];
+    create("create_reproducible_whiteout", "full.pna", &options).unwrap();
+    fs::remove_file("create_reproducible_whiteout/in/a/a.txt").unwrap();
+    create("create_reproducible_whiteout", "level1.pna", &options).unwrap();
+
+    let mut whiteouts = 0;
+    archive::for_each_entry("create_reproducible_whiteout/level1.pna", |entry| {
+        if entry.header().data_kind() == pna::DataKind::Whiteout {
+            assert_eq!(
+                entry.metadata().modified(),
+                Some(Duration::from_secs(1_704_067_200))
+            );
+            whiteouts += 1;
+        }
+    })
+    .unwrap();
+    assert_eq!(whiteouts, 1);
+}
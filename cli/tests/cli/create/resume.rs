@@ -0,0 +1,152 @@
+use crate::utils::{diff::diff, setup};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{fs, path::Path};
+
+/// Replaces the end marker of `part` with the bytes of a partially written entry, and
+/// records a checkpoint just before them as an interrupted run would have.
+fn interrupt(part: &str, checkpoint: &str, part_num: usize, split: bool, done: &[&str]) {
+    let mut bytes = fs::read(part).unwrap();
+    // AEND chunk
+    let offset = bytes.len() - 12;
+    bytes.truncate(offset);
+    bytes.extend_from_slice(b"\x00\x00\x01\x00FHED partially written");
+    fs::write(part, bytes).unwrap();
+    fs::write(
+        checkpoint,
+        serde_json::json!({
+            "version": 1,
+            "split": split,
+            "part": part_num,
+            "offset": offset,
+            "done": done,
+        })
+        .to_string(),
+    )
+    .unwrap();
+}
+
+fn extract(archive: &str, out_dir: &str) {
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        archive,
+        "--overwrite",
+        "--out-dir",
+        out_dir,
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+}
+
+#[test]
+fn create_resume() {
+    setup();
+    let _ = fs::remove_dir_all("create_resume");
+    fs::create_dir_all("create_resume/in").unwrap();
+    fs::write("create_resume/in/a.txt", "a").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_resume/resume.pna",
+        "--overwrite",
+        "create_resume/in/",
+        "--checkpoint=0",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert!(!Path::new("create_resume/resume.pna.checkpoint").exists());
+
+    interrupt(
+        "create_resume/resume.pna",
+        "create_resume/resume.pna.checkpoint",
+        1,
+        false,
+        &["create_resume/in/a.txt"],
+    );
+    fs::write("create_resume/in/b.txt", "b").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_resume/resume.pna",
+        "create_resume/in/",
+        "--resume",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert!(!Path::new("create_resume/resume.pna.checkpoint").exists());
+
+    extract("create_resume/resume.pna", "create_resume/out/");
+    diff("create_resume/in/", "create_resume/out/").unwrap();
+}
+
+#[test]
+fn create_resume_split() {
+    setup();
+    let _ = fs::remove_dir_all("create_resume_split");
+    fs::create_dir_all("create_resume_split/in").unwrap();
+    fs::write("create_resume_split/in/a.txt", "a".repeat(2500)).unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_resume_split/resume.pna",
+        "--overwrite",
+        "create_resume_split/in/",
+        "--store",
+        "--split",
+        "1kb",
+        "--checkpoint=0",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let part1 = fs::read("create_resume_split/resume.part1.pna").unwrap();
+    assert!(!Path::new("create_resume_split/resume.part4.pna").exists());
+
+    interrupt(
+        "create_resume_split/resume.part3.pna",
+        "create_resume_split/resume.pna.checkpoint",
+        3,
+        true,
+        &["create_resume_split/in/a.txt"],
+    );
+    fs::write("create_resume_split/in/b.txt", "b".repeat(1500)).unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "create_resume_split/resume.pna",
+        "create_resume_split/in/",
+        "--store",
+        "--split",
+        "1kb",
+        "--resume",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert!(!Path::new("create_resume_split/resume.pna.checkpoint").exists());
+    assert_eq!(
+        fs::read("create_resume_split/resume.part1.pna").unwrap(),
+        part1
+    );
+
+    extract(
+        "create_resume_split/resume.part1.pna",
+        "create_resume_split/out/",
+    );
+    diff("create_resume_split/in/", "create_resume_split/out/").unwrap();
+}
@@ -1,3 +1,4 @@
+mod dry_run;
 mod exclude;
 mod exclude_from;
 mod files_from;
@@ -5,6 +6,7 @@ mod files_from_stdin;
 mod include;
 mod password;
 mod password_file;
+mod where_filter;
 
 use crate::utils::{diff::diff, setup, TestResources};
 use clap::Parser;
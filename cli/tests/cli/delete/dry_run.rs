@@ -0,0 +1,37 @@
+use crate::utils::{setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn delete_dry_run() {
+    setup();
+    TestResources::extract_in("raw/", "delete_dry_run/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "delete_dry_run/dry_run.pna",
+        "--overwrite",
+        "delete_dry_run/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let before = fs::read("delete_dry_run/dry_run.pna").unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "delete",
+        "delete_dry_run/dry_run.pna",
+        "**/*.txt",
+        "--dry-run",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(fs::read("delete_dry_run/dry_run.pna").unwrap(), before);
+}
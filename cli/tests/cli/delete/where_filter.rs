@@ -0,0 +1,41 @@
+use crate::utils::{archive, setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+
+#[test]
+fn delete_where_filter() {
+    setup();
+    TestResources::extract_in("raw/", "delete_where_filter/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "delete_where_filter/where.pna",
+        "--overwrite",
+        "delete_where_filter/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "delete",
+        "delete_where_filter/where.pna",
+        "--unstable",
+        "--where",
+        "path !~ \"*.txt\"",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut remaining = 0;
+    archive::for_each_entry("delete_where_filter/where.pna", |entry| {
+        assert!(entry.header().path().as_str().ends_with(".txt"));
+        remaining += 1;
+    })
+    .unwrap();
+    assert!(remaining > 0);
+}
@@ -0,0 +1,153 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn diff_archive() {
+    setup();
+    let _ = fs::remove_dir_all("diff_archive");
+    fs::create_dir_all("diff_archive/in/dir").unwrap();
+    fs::write("diff_archive/in/dir/a.txt", "a").unwrap();
+    fs::write("diff_archive/in/dir/b.txt", "b").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "diff_archive/diff.pna",
+        "--overwrite",
+        "diff_archive/in/",
+        "--keep-timestamp",
+        "--keep-permission",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "diff",
+        "diff_archive/diff.pna",
+        "--content",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    fs::write("diff_archive/in/dir/a.txt", "A").unwrap();
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    let assert = cmd
+        .args([
+            "--quiet",
+            "experimental",
+            "diff",
+            "diff_archive/diff.pna",
+            "--content",
+        ])
+        .assert()
+        .failure();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout
+        .lines()
+        .any(|it| it == "diff_archive/in/dir/a.txt: Contents differ"));
+    assert!(stdout
+        .lines()
+        .all(|it| it.starts_with("diff_archive/in/dir/a.txt: ")));
+
+    fs::remove_file("diff_archive/in/dir/b.txt").unwrap();
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "experimental",
+        "diff",
+        "diff_archive/diff.pna",
+        "diff_archive/in/dir/b.txt",
+    ])
+    .assert()
+    .failure()
+    .stdout("diff_archive/in/dir/b.txt: Warning: Cannot stat: No such file or directory\n");
+}
+
+#[test]
+fn diff_archives() {
+    setup();
+    let _ = fs::remove_dir_all("diff_archives");
+    fs::create_dir_all("diff_archives/in").unwrap();
+    fs::write("diff_archives/in/a.txt", "a").unwrap();
+    fs::write("diff_archives/in/b.txt", "b").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "diff_archives/old.pna",
+        "--overwrite",
+        "diff_archives/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "diff_archives/same.pna",
+        "--overwrite",
+        "--solid",
+        "diff_archives/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "diff",
+        "diff_archives/old.pna",
+        "--against",
+        "diff_archives/same.pna",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    fs::rename("diff_archives/in/b.txt", "diff_archives/in/c.txt").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "diff_archives/new.pna",
+        "--overwrite",
+        "diff_archives/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    let assert = cmd
+        .args([
+            "--quiet",
+            "experimental",
+            "diff",
+            "diff_archives/old.pna",
+            "--against",
+            "diff_archives/new.pna",
+            "--format",
+            "jsonl",
+        ])
+        .assert()
+        .failure();
+    let records = String::from_utf8_lossy(&assert.get_output().stdout)
+        .lines()
+        .map(|it| serde_json::from_str::<serde_json::Value>(it).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        records,
+        [serde_json::json!({
+            "status": "renamed",
+            "from": "diff_archives/in/b.txt",
+            "path": "diff_archives/in/c.txt",
+        })]
+    );
+}
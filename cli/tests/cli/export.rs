@@ -0,0 +1,228 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io::Read};
+
+fn create_archive(dir: &str) {
+    fs::create_dir_all(format!("{dir}/in/dir")).unwrap();
+    fs::write(format!("{dir}/in/dir/a.txt"), "aaa").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{dir}/archive.pna"),
+        "--overwrite",
+        "--keep-dir",
+        "--keep-permission",
+        "--keep-timestamp",
+        &format!("{dir}/in/"),
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "xattr",
+        "set",
+        &format!("{dir}/archive.pna"),
+        "--name",
+        "user.comment",
+        "--value",
+        "hello",
+        &format!("{dir}/in/dir/a.txt"),
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+}
+
+#[test]
+fn export_pax() {
+    setup();
+    create_archive("export_pax");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "export",
+        "export_pax/archive.pna",
+        "--format",
+        "pax",
+        "--output",
+        "export_pax/archive.tar",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut archive = tar::Archive::new(fs::File::open("export_pax/archive.tar").unwrap());
+    let mut found = false;
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap().to_str() != Some("export_pax/in/dir/a.txt") {
+            continue;
+        }
+        found = true;
+        let xattr = entry
+            .pax_extensions()
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|it| it.key() == Ok("SCHILY.xattr.user.comment"))
+            .map(|it| it.value_bytes().to_vec());
+        assert_eq!(xattr.as_deref(), Some(&b"hello"[..]));
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "aaa");
+    }
+    assert!(found);
+}
+
+#[test]
+fn export_tar() {
+    setup();
+    create_archive("export_tar");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "export",
+        "export_tar/archive.pna",
+        "--format",
+        "tar",
+        "--output",
+        "export_tar/archive.tar",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut archive = tar::Archive::new(fs::File::open("export_tar/archive.tar").unwrap());
+    let paths = archive
+        .entries()
+        .unwrap()
+        .map(|it| it.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&"export_tar/in/dir/".into()));
+    assert!(paths.contains(&"export_tar/in/dir/a.txt".into()));
+}
+
+#[test]
+fn export_zip() {
+    setup();
+    create_archive("export_zip");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "export",
+        "export_zip/archive.pna",
+        "--format",
+        "zip",
+        "--output",
+        "export_zip/archive.zip",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut archive =
+        zip::ZipArchive::new(fs::File::open("export_zip/archive.zip").unwrap()).unwrap();
+    let mut file = archive.by_name("export_zip/in/dir/a.txt").unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "aaa");
+}
+
+#[test]
+fn export_imported_nested_hard_link() {
+    setup();
+    let _ = fs::remove_dir_all("export_imported_nested_hard_link");
+    fs::create_dir_all("export_imported_nested_hard_link").unwrap();
+    let file = fs::File::create("export_imported_nested_hard_link/source.tar").unwrap();
+    let mut builder = tar::Builder::new(file);
+    let mut header = tar::Header::new_ustar();
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(1_600_000_000);
+    header.set_size(3);
+    builder
+        .append_data(&mut header, "in/a.txt", &b"aaa"[..])
+        .unwrap();
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::Link);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(1_600_000_000);
+    header.set_size(0);
+    builder
+        .append_link(&mut header, "in/dir/hard", "in/a.txt")
+        .unwrap();
+    builder.into_inner().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "import",
+        "export_imported_nested_hard_link/archive.pna",
+        "export_imported_nested_hard_link/source.tar",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "export",
+        "export_imported_nested_hard_link/archive.pna",
+        "--format",
+        "pax",
+        "--output",
+        "export_imported_nested_hard_link/archive.tar",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut archive =
+        tar::Archive::new(fs::File::open("export_imported_nested_hard_link/archive.tar").unwrap());
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let link_name = entry
+            .link_name()
+            .unwrap()
+            .map(|it| it.to_string_lossy().into_owned());
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        entries.push((path, entry.header().entry_type(), link_name, contents));
+    }
+    assert_eq!(
+        entries,
+        [
+            (
+                "in/a.txt".into(),
+                tar::EntryType::Regular,
+                None,
+                "aaa".into()
+            ),
+            (
+                "in/dir/hard".into(),
+                tar::EntryType::Link,
+                Some("in/a.txt".into()),
+                String::new()
+            ),
+        ]
+    );
+}
@@ -1,5 +1,14 @@
+mod atomic;
 mod chroot;
+mod directory_metadata;
+mod dry_run;
 mod exclude;
+mod multiple_password_files;
+mod overwrite_policy;
 mod password_from_file;
 mod substitution;
+mod symlink_race;
 mod transform;
+mod version;
+mod where_filter;
+mod whiteout;
@@ -0,0 +1,126 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+fn init(dir: &str) {
+    setup();
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(format!("{dir}/in")).unwrap();
+    fs::create_dir_all(format!("{dir}/out")).unwrap();
+    fs::write(format!("{dir}/in/a.txt"), "new").unwrap();
+    fs::write(format!("{dir}/out/a.txt"), "old").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{dir}/atomic.pna"),
+        "--overwrite",
+        &format!("{dir}/in/a.txt"),
+        "--password",
+        "password",
+        "--aes",
+        "cbc",
+        "--argon2",
+        "t=1,m=50",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+}
+
+fn extract(dir: &str, password: &str) -> anyhow::Result<()> {
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        &format!("{dir}/atomic.pna"),
+        "--overwrite",
+        "--atomic",
+        "--fsync",
+        "--unstable",
+        "--out-dir",
+        &format!("{dir}/out"),
+        "--strip-components",
+        "2",
+        "--password",
+        password,
+    ])
+    .unwrap()
+    .execute()
+}
+
+fn out_files(dir: &str) -> Vec<String> {
+    let mut files = fs::read_dir(format!("{dir}/out"))
+        .unwrap()
+        .map(|it| it.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn extract_atomic_replaces_file() {
+    init("extract_atomic_replaces_file");
+    fs::hard_link(
+        "extract_atomic_replaces_file/out/a.txt",
+        "extract_atomic_replaces_file/out/b.txt",
+    )
+    .unwrap();
+    extract("extract_atomic_replaces_file", "password").unwrap();
+    assert_eq!(
+        fs::read_to_string("extract_atomic_replaces_file/out/a.txt").unwrap(),
+        "new"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_atomic_replaces_file/out/b.txt").unwrap(),
+        "old"
+    );
+    assert_eq!(
+        out_files("extract_atomic_replaces_file"),
+        ["a.txt", "b.txt"]
+    );
+}
+
+#[test]
+fn extract_atomic_keeps_file_on_failure() {
+    init("extract_atomic_keeps_file_on_failure");
+    assert!(extract("extract_atomic_keeps_file_on_failure", "wrong").is_err());
+    assert_eq!(
+        fs::read_to_string("extract_atomic_keeps_file_on_failure/out/a.txt").unwrap(),
+        "old"
+    );
+    assert_eq!(out_files("extract_atomic_keeps_file_on_failure"), ["a.txt"]);
+}
+
+#[test]
+fn stdio_extract_atomic_keeps_file_on_failure() {
+    let dir = "stdio_extract_atomic_keeps_file_on_failure";
+    init(dir);
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "stdio",
+        "-x",
+        "-f",
+        &format!("{dir}/atomic.pna"),
+        "--overwrite",
+        "--atomic",
+        "--fsync",
+        "--out-dir",
+        &format!("{dir}/out"),
+        "--strip-components",
+        "2",
+        "--password",
+        "wrong",
+    ])
+    .unwrap()
+    .execute();
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read_to_string(format!("{dir}/out/a.txt")).unwrap(),
+        "old"
+    );
+    assert_eq!(out_files(dir), ["a.txt"]);
+}
@@ -0,0 +1,193 @@
+#![cfg(unix)]
+use crate::utils::setup;
+use clap::Parser;
+use pna::{Archive, EntryBuilder, Permission, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    time::{Duration, UNIX_EPOCH},
+};
+
+#[test]
+fn extract_directory_metadata_after_contents() {
+    setup();
+    if let Ok(meta) = fs::metadata("extract_directory_metadata/out/dir") {
+        let mut permissions = meta.permissions();
+        permissions.set_mode(0o755);
+        fs::set_permissions("extract_directory_metadata/out/dir", permissions).unwrap();
+    }
+    let _ = fs::remove_dir_all("extract_directory_metadata");
+    fs::create_dir_all("extract_directory_metadata").unwrap();
+    let owner = fs::metadata(".").unwrap();
+    let permission = |mode| {
+        Permission::new(
+            owner.uid().into(),
+            "".into(),
+            owner.gid().into(),
+            "".into(),
+            mode,
+        )
+    };
+
+    let file = fs::File::create("extract_directory_metadata/dir.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut dir = EntryBuilder::new_dir("dir".into());
+    dir.modified(Duration::from_secs(1_000_000_000));
+    dir.permission(permission(0o555));
+    writer.add_entry(dir.build().unwrap()).unwrap();
+    let mut sub = EntryBuilder::new_dir("dir/sub".into());
+    sub.modified(Duration::from_secs(1_100_000_000));
+    sub.permission(permission(0o755));
+    writer.add_entry(sub.build().unwrap()).unwrap();
+    let mut file = EntryBuilder::new_file("dir/sub/a.txt".into(), WriteOptions::store()).unwrap();
+    file.write_all(b"a").unwrap();
+    file.permission(permission(0o644));
+    writer.add_entry(file.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_directory_metadata/dir.pna",
+        "--overwrite",
+        "--keep-timestamp",
+        "--keep-permission",
+        "--out-dir",
+        "extract_directory_metadata/out/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string("extract_directory_metadata/out/dir/sub/a.txt").unwrap(),
+        "a"
+    );
+    let dir = fs::metadata("extract_directory_metadata/out/dir").unwrap();
+    assert_eq!(dir.permissions().mode() & 0o777, 0o555);
+    assert_eq!(
+        dir.modified().unwrap(),
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+    );
+    let sub = fs::metadata("extract_directory_metadata/out/dir/sub").unwrap();
+    assert_eq!(
+        sub.modified().unwrap(),
+        UNIX_EPOCH + Duration::from_secs(1_100_000_000)
+    );
+}
+
+#[test]
+fn extract_directory_metadata_without_access() {
+    setup();
+    for dir in ["", "/dir"] {
+        if let Ok(meta) = fs::metadata(format!("extract_directory_without_access/out{dir}")) {
+            let mut permissions = meta.permissions();
+            permissions.set_mode(0o755);
+            fs::set_permissions(
+                format!("extract_directory_without_access/out{dir}"),
+                permissions,
+            )
+            .unwrap();
+        }
+    }
+    let _ = fs::remove_dir_all("extract_directory_without_access");
+    fs::create_dir_all("extract_directory_without_access").unwrap();
+    let owner = fs::metadata(".").unwrap();
+
+    let file = fs::File::create("extract_directory_without_access/dir.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut dir = EntryBuilder::new_dir("dir".into());
+    dir.modified(Duration::from_secs(1_000_000_000));
+    dir.permission(Permission::new(
+        owner.uid().into(),
+        "".into(),
+        owner.gid().into(),
+        "".into(),
+        0o311,
+    ));
+    writer.add_entry(dir.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_directory_without_access/dir.pna",
+        "--overwrite",
+        "--keep-timestamp",
+        "--keep-permission",
+        "--out-dir",
+        "extract_directory_without_access/out/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let dir = fs::metadata("extract_directory_without_access/out/dir").unwrap();
+    assert_eq!(dir.permissions().mode() & 0o777, 0o311);
+    assert_eq!(
+        dir.modified().unwrap(),
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+    );
+}
+
+#[test]
+fn extract_directory_metadata_replaced_by_symlink() {
+    setup();
+    let _ = fs::remove_dir_all("extract_directory_replaced_by_symlink");
+    fs::create_dir_all("extract_directory_replaced_by_symlink").unwrap();
+    let owner = fs::metadata(".").unwrap();
+    let permission = |mode| {
+        Permission::new(
+            owner.uid().into(),
+            "".into(),
+            owner.gid().into(),
+            "".into(),
+            mode,
+        )
+    };
+
+    let file = fs::File::create("extract_directory_replaced_by_symlink/dir.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut target = EntryBuilder::new_dir("target".into());
+    target.modified(Duration::from_secs(1_000_000_000));
+    target.permission(permission(0o755));
+    writer.add_entry(target.build().unwrap()).unwrap();
+    let mut dir = EntryBuilder::new_dir("link".into());
+    dir.modified(Duration::from_secs(1_100_000_000));
+    dir.permission(permission(0o700));
+    writer.add_entry(dir.build().unwrap()).unwrap();
+    let link = EntryBuilder::new_symbolic_link("link".into(), "target".into()).unwrap();
+    writer.add_entry(link.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_directory_replaced_by_symlink/dir.pna",
+        "--overwrite",
+        "--keep-timestamp",
+        "--keep-permission",
+        "--out-dir",
+        "extract_directory_replaced_by_symlink/out/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert!(
+        fs::symlink_metadata("extract_directory_replaced_by_symlink/out/link")
+            .unwrap()
+            .is_symlink()
+    );
+    let target = fs::metadata("extract_directory_replaced_by_symlink/out/target").unwrap();
+    assert_eq!(target.permissions().mode() & 0o777, 0o755);
+    assert_eq!(
+        target.modified().unwrap(),
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+    );
+}
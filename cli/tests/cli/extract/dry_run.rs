@@ -0,0 +1,111 @@
+use crate::utils::{setup, TestResources};
+use clap::Parser;
+use pna::{Archive, EntryBuilder, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io::Write};
+
+#[test]
+fn extract_dry_run() {
+    setup();
+    TestResources::extract_in("raw/", "extract_dry_run/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "extract_dry_run/dry_run.pna",
+        "--overwrite",
+        "extract_dry_run/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let _ = fs::remove_dir_all("extract_dry_run/out/");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_dry_run/dry_run.pna",
+        "--out-dir",
+        "extract_dry_run/out/",
+        "-n",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert!(!fs::exists("extract_dry_run/out/").unwrap());
+}
+
+#[test]
+fn extract_dry_run_existing_without_overwrite() {
+    setup();
+    TestResources::extract_in("raw/", "extract_dry_run_existing/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "extract_dry_run_existing/dry_run.pna",
+        "--overwrite",
+        "extract_dry_run_existing/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_dry_run_existing/dry_run.pna",
+        "--dry-run",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn extract_dry_run_plan() {
+    setup();
+    let _ = fs::remove_dir_all("extract_dry_run_plan");
+    fs::create_dir_all("extract_dry_run_plan/out").unwrap();
+    fs::write("extract_dry_run_plan/out/a.txt", "old").unwrap();
+
+    let file = fs::File::create("extract_dry_run_plan/plan.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"new").unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    let builder = EntryBuilder::new_symbolic_link("link".into(), "../outside".into()).unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    let builder = EntryBuilder::new_hard_link("hard".into(), "../outside".into()).unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    for (option, action) in [("--backup", "backup"), ("--unlink-first", "replace")] {
+        let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+        cmd.args([
+            "--quiet",
+            "x",
+            "extract_dry_run_plan/plan.pna",
+            "--out-dir",
+            "extract_dry_run_plan/out/",
+            "--dry-run",
+            option,
+            "--unstable",
+        ])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{action} extract_dry_run_plan/out/a.txt\n\
+             skip extract_dry_run_plan/out/link\n\
+             skip extract_dry_run_plan/out/hard\n"
+        ));
+    }
+    assert_eq!(
+        fs::read_to_string("extract_dry_run_plan/out/a.txt").unwrap(),
+        "old"
+    );
+}
@@ -0,0 +1,217 @@
+use crate::utils::setup;
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn extract_with_multiple_password_files() {
+    setup();
+    let base = "extract_with_multiple_password_files";
+    fs::create_dir_all(format!("{base}/in")).unwrap();
+    fs::write(format!("{base}/in/first.txt"), "first").unwrap();
+    fs::write(format!("{base}/in/second.txt"), "second").unwrap();
+    fs::write(format!("{base}/first_password"), "first password").unwrap();
+    fs::write(format!("{base}/second_password"), "second password").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{base}/multiple_passwords.pna"),
+        "--overwrite",
+        &format!("{base}/in/first.txt"),
+        "--password",
+        "first password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "a",
+        &format!("{base}/multiple_passwords.pna"),
+        &format!("{base}/in/second.txt"),
+        "--password",
+        "second password",
+        "--camellia",
+        "cbc",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        &format!("{base}/multiple_passwords.pna"),
+        "--overwrite",
+        "--out-dir",
+        &format!("{base}/missing/"),
+        "--password-file",
+        &format!("{base}/first_password"),
+    ])
+    .unwrap()
+    .execute();
+    assert!(result.is_err());
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        &format!("{base}/multiple_passwords.pna"),
+        "--overwrite",
+        "--out-dir",
+        &format!("{base}/out/"),
+        "--password-file",
+        &format!("{base}/first_password"),
+        "--password-file",
+        &format!("{base}/second_password"),
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(format!("{base}/out/first.txt")).unwrap(),
+        "first"
+    );
+    assert_eq!(
+        fs::read_to_string(format!("{base}/out/second.txt")).unwrap(),
+        "second"
+    );
+}
+
+#[test]
+fn extract_solid_with_multiple_password_files() {
+    setup();
+    let base = "extract_solid_with_multiple_password_files";
+    fs::create_dir_all(format!("{base}/in")).unwrap();
+    fs::write(format!("{base}/in/first.txt"), "first").unwrap();
+    fs::write(format!("{base}/in/second.txt"), "second").unwrap();
+    fs::write(format!("{base}/first_password"), "first password").unwrap();
+    fs::write(format!("{base}/second_password"), "second password").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{base}/multiple_passwords.pna"),
+        "--overwrite",
+        "--solid",
+        "--store",
+        &format!("{base}/in/first.txt"),
+        "--password",
+        "first password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "a",
+        &format!("{base}/multiple_passwords.pna"),
+        &format!("{base}/in/second.txt"),
+        "--password",
+        "second password",
+        "--aes",
+        "cbc",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        &format!("{base}/multiple_passwords.pna"),
+        "--overwrite",
+        "--out-dir",
+        &format!("{base}/out/"),
+        "--password-file",
+        &format!("{base}/second_password"),
+        "--password-file",
+        &format!("{base}/first_password"),
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(format!("{base}/out/first.txt")).unwrap(),
+        "first"
+    );
+    assert_eq!(
+        fs::read_to_string(format!("{base}/out/second.txt")).unwrap(),
+        "second"
+    );
+}
+
+#[test]
+fn extract_store_ctr_with_multiple_password_files() {
+    setup();
+    let base = "extract_store_ctr_with_multiple_password_files";
+    fs::create_dir_all(format!("{base}/in")).unwrap();
+    fs::write(format!("{base}/in/first.txt"), "first").unwrap();
+    fs::write(format!("{base}/first_password"), "first password").unwrap();
+    fs::write(format!("{base}/second_password"), "second password").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        &format!("{base}/store_ctr.pna"),
+        "--overwrite",
+        "--store",
+        &format!("{base}/in/first.txt"),
+        "--password",
+        "first password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        &format!("{base}/store_ctr.pna"),
+        "--overwrite",
+        "--out-dir",
+        &format!("{base}/out/"),
+        "--password-file",
+        &format!("{base}/first_password"),
+        "--password-file",
+        &format!("{base}/second_password"),
+        "--strip-components",
+        "2",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(format!("{base}/out/first.txt")).unwrap(),
+        "first"
+    );
+}
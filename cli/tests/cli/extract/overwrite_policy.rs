@@ -0,0 +1,147 @@
+use crate::utils::setup;
+use clap::Parser;
+use pna::{Archive, EntryBuilder, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{
+    fs,
+    io::Write,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Creates `<dir>/new.pna` containing `a.txt` modified at 1,000,000,000 seconds, and an
+/// existing `<dir>/out/a.txt` to extract it over.
+fn init(dir: &str) {
+    setup();
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(format!("{dir}/out")).unwrap();
+    let file = fs::File::create(format!("{dir}/new.pna")).unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut entry = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+    entry.write_all(b"new").unwrap();
+    entry.modified(Duration::from_secs(1_000_000_000));
+    writer.add_entry(entry.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+    fs::write(format!("{dir}/out/a.txt"), "old").unwrap();
+}
+
+fn extract(dir: &str, options: &[&str]) {
+    let archive = format!("{dir}/new.pna");
+    let out_dir = format!("{dir}/out");
+    let mut args = vec![
+        "pna",
+        "--quiet",
+        "x",
+        &archive,
+        "--keep-timestamp",
+        "--out-dir",
+        &out_dir,
+        "--unstable",
+    ];
+    args.extend_from_slice(options);
+    cli::Cli::try_parse_from(args).unwrap().execute().unwrap();
+}
+
+fn set_modified(path: &str, secs: u64) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap();
+}
+
+#[test]
+fn extract_existing_without_policy() {
+    init("extract_existing_without_policy");
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_existing_without_policy/new.pna",
+        "--out-dir",
+        "extract_existing_without_policy/out",
+    ])
+    .unwrap()
+    .execute();
+    assert!(result.is_err());
+}
+
+#[test]
+fn extract_keep_old_files() {
+    init("extract_keep_old_files");
+    extract("extract_keep_old_files", &["--keep-old-files"]);
+    assert_eq!(
+        fs::read_to_string("extract_keep_old_files/out/a.txt").unwrap(),
+        "old"
+    );
+}
+
+#[test]
+fn extract_keep_newer_files() {
+    init("extract_keep_newer_files");
+    set_modified("extract_keep_newer_files/out/a.txt", 1_100_000_000);
+    extract("extract_keep_newer_files", &["--keep-newer-files"]);
+    assert_eq!(
+        fs::read_to_string("extract_keep_newer_files/out/a.txt").unwrap(),
+        "old"
+    );
+
+    set_modified("extract_keep_newer_files/out/a.txt", 900_000_000);
+    extract("extract_keep_newer_files", &["--keep-newer-files"]);
+    assert_eq!(
+        fs::read_to_string("extract_keep_newer_files/out/a.txt").unwrap(),
+        "new"
+    );
+}
+
+#[test]
+fn extract_backup_simple() {
+    init("extract_backup_simple");
+    extract("extract_backup_simple", &["--backup"]);
+    assert_eq!(
+        fs::read_to_string("extract_backup_simple/out/a.txt").unwrap(),
+        "new"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_backup_simple/out/a.txt~").unwrap(),
+        "old"
+    );
+}
+
+#[test]
+fn extract_backup_numbered() {
+    init("extract_backup_numbered");
+    extract("extract_backup_numbered", &["--backup=numbered"]);
+    extract("extract_backup_numbered", &["--backup=numbered"]);
+    assert_eq!(
+        fs::read_to_string("extract_backup_numbered/out/a.txt.~1~").unwrap(),
+        "old"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_backup_numbered/out/a.txt.~2~").unwrap(),
+        "new"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_backup_numbered/out/a.txt").unwrap(),
+        "new"
+    );
+}
+
+#[test]
+fn extract_unlink_first() {
+    init("extract_unlink_first");
+    fs::hard_link(
+        "extract_unlink_first/out/a.txt",
+        "extract_unlink_first/out/b.txt",
+    )
+    .unwrap();
+    extract("extract_unlink_first", &["--unlink-first"]);
+    assert_eq!(
+        fs::read_to_string("extract_unlink_first/out/a.txt").unwrap(),
+        "new"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_unlink_first/out/b.txt").unwrap(),
+        "old"
+    );
+}
@@ -0,0 +1,185 @@
+#![cfg(unix)]
+use crate::utils::setup;
+use clap::Parser;
+use pna::{Archive, EntryBuilder, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io::Write, path::Path};
+
+#[test]
+fn extract_refuses_to_write_through_symlink() {
+    setup();
+    let _ = fs::remove_dir_all("extract_symlink_race");
+    fs::create_dir_all("extract_symlink_race/victim").unwrap();
+    fs::create_dir_all("extract_symlink_race/out").unwrap();
+    std::os::unix::fs::symlink(
+        fs::canonicalize("extract_symlink_race/victim").unwrap(),
+        "extract_symlink_race/out/link",
+    )
+    .unwrap();
+
+    let file = fs::File::create("extract_symlink_race/race.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut builder =
+        EntryBuilder::new_file("link/pwned.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"pwned").unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_symlink_race/race.pna",
+        "--overwrite",
+        "--out-dir",
+        "extract_symlink_race/out/",
+    ])
+    .unwrap()
+    .execute();
+
+    assert!(result.is_err());
+    assert!(!Path::new("extract_symlink_race/victim/pwned.txt").exists());
+}
+
+#[test]
+fn extract_keeps_permission_of_symlink_target() {
+    use pna::Permission;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    setup();
+    let _ = fs::remove_dir_all("extract_symlink_permission");
+    fs::create_dir_all("extract_symlink_permission").unwrap();
+    fs::write("extract_symlink_permission/victim.txt", "victim").unwrap();
+    fs::set_permissions(
+        "extract_symlink_permission/victim.txt",
+        fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    let victim = fs::canonicalize("extract_symlink_permission/victim.txt").unwrap();
+    let owner = fs::metadata(".").unwrap();
+
+    let file = fs::File::create("extract_symlink_permission/link.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut builder =
+        EntryBuilder::new_symbolic_link("link".into(), victim.to_string_lossy().as_ref().into())
+            .unwrap();
+    builder.permission(Permission::new(
+        owner.uid().into(),
+        "".into(),
+        owner.gid().into(),
+        "".into(),
+        0o777,
+    ));
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_symlink_permission/link.pna",
+        "--overwrite",
+        "--keep-permission",
+        "--allow-unsafe-links",
+        "--out-dir",
+        "extract_symlink_permission/out/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert!(fs::symlink_metadata("extract_symlink_permission/out/link")
+        .unwrap()
+        .is_symlink());
+    assert_eq!(
+        fs::metadata(&victim).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+}
+
+#[test]
+fn extract_through_existing_directory_symlink_with_keep_directory_symlink() {
+    setup();
+    let _ = fs::remove_dir_all("extract_keep_directory_symlink");
+    fs::create_dir_all("extract_keep_directory_symlink/out/usr/bin").unwrap();
+    std::os::unix::fs::symlink("usr/bin", "extract_keep_directory_symlink/out/bin").unwrap();
+
+    let file = fs::File::create("extract_keep_directory_symlink/keep.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut builder = EntryBuilder::new_file("bin/tool".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"tool").unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    let extract = |extra: &[&str]| {
+        cli::Cli::try_parse_from(
+            [
+                "pna",
+                "--quiet",
+                "x",
+                "extract_keep_directory_symlink/keep.pna",
+                "--overwrite",
+                "--out-dir",
+                "extract_keep_directory_symlink/out/",
+            ]
+            .into_iter()
+            .chain(extra.iter().copied()),
+        )
+        .unwrap()
+        .execute()
+    };
+
+    assert!(extract(&[]).is_err());
+    assert!(!Path::new("extract_keep_directory_symlink/out/usr/bin/tool").exists());
+
+    extract(&["--keep-directory-symlink", "--unstable"]).unwrap();
+    assert_eq!(
+        fs::read("extract_keep_directory_symlink/out/usr/bin/tool").unwrap(),
+        b"tool"
+    );
+    assert!(
+        fs::symlink_metadata("extract_keep_directory_symlink/out/bin")
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[test]
+fn extract_keep_directory_symlink_refuses_symlink_from_archive() {
+    setup();
+    let _ = fs::remove_dir_all("extract_keep_directory_symlink_archive");
+    fs::create_dir_all("extract_keep_directory_symlink_archive/victim").unwrap();
+    let victim = fs::canonicalize("extract_keep_directory_symlink_archive/victim").unwrap();
+
+    let file = fs::File::create("extract_keep_directory_symlink_archive/link.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    let mut builder = EntryBuilder::new_file("pwned.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"pwned").unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    let builder =
+        EntryBuilder::new_symbolic_link("link".into(), victim.to_string_lossy().as_ref().into())
+            .unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    let builder =
+        EntryBuilder::new_hard_link("link/pwned.txt".into(), "../pwned.txt".into()).unwrap();
+    writer.add_entry(builder.build().unwrap()).unwrap();
+    writer.finalize().unwrap();
+
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_keep_directory_symlink_archive/link.pna",
+        "--overwrite",
+        "--allow-unsafe-links",
+        "--keep-directory-symlink",
+        "--unstable",
+        "--out-dir",
+        "extract_keep_directory_symlink_archive/out/",
+    ])
+    .unwrap()
+    .execute();
+
+    assert!(result.is_err());
+    assert!(!Path::new("extract_keep_directory_symlink_archive/victim/pwned.txt").exists());
+}
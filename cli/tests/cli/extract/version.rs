@@ -0,0 +1,133 @@
+use crate::utils::setup;
+use clap::Parser;
+use pna::{Archive, EntryBuilder, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io::prelude::*, time::Duration};
+
+fn write_versioned_archive(path: &str) {
+    let file = fs::File::create(path).unwrap();
+    let mut archive = Archive::write_header(file).unwrap();
+    for (name, content, mtime) in [
+        ("a.txt", "a1", 1_000),
+        ("b.txt", "b1", 1_000),
+        ("a.txt", "a2", 2_000),
+    ] {
+        let mut builder = EntryBuilder::new_file(name.into(), WriteOptions::store()).unwrap();
+        builder.write_all(content.as_bytes()).unwrap();
+        builder.modified(Duration::from_secs(mtime));
+        archive.add_entry(builder.build().unwrap()).unwrap();
+    }
+    let mut whiteout = EntryBuilder::new_whiteout("b.txt".into());
+    whiteout.modified(Duration::from_secs(2_000));
+    archive.add_entry(whiteout.build().unwrap()).unwrap();
+    archive.finalize().unwrap();
+}
+
+fn extract(archive: &str, out_dir: &str, selector: &[&str]) {
+    let _ = fs::remove_dir_all(out_dir);
+    cli::Cli::try_parse_from(
+        [
+            "pna",
+            "--quiet",
+            "x",
+            archive,
+            "--overwrite",
+            "--out-dir",
+            out_dir,
+            "--unstable",
+        ]
+        .into_iter()
+        .chain(selector.iter().copied()),
+    )
+    .unwrap()
+    .execute()
+    .unwrap();
+}
+
+#[test]
+fn extract_version() {
+    setup();
+    fs::create_dir_all("extract_version").unwrap();
+    write_versioned_archive("extract_version/versions.pna");
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "ls",
+        "extract_version/versions.pna",
+        "--versions",
+        "--unstable",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    extract(
+        "extract_version/versions.pna",
+        "extract_version/first",
+        &["--version", "1"],
+    );
+    assert_eq!(
+        fs::read_to_string("extract_version/first/a.txt").unwrap(),
+        "a1"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_version/first/b.txt").unwrap(),
+        "b1"
+    );
+
+    extract(
+        "extract_version/versions.pna",
+        "extract_version/at",
+        &["--at", "@1500"],
+    );
+    assert_eq!(
+        fs::read_to_string("extract_version/at/a.txt").unwrap(),
+        "a1"
+    );
+    assert_eq!(
+        fs::read_to_string("extract_version/at/b.txt").unwrap(),
+        "b1"
+    );
+
+    extract(
+        "extract_version/versions.pna",
+        "extract_version/latest",
+        &["--at", "@3000"],
+    );
+    assert_eq!(
+        fs::read_to_string("extract_version/latest/a.txt").unwrap(),
+        "a2"
+    );
+    assert!(!fs::exists("extract_version/latest/b.txt").unwrap());
+}
+
+#[test]
+fn extract_at_without_mtime() {
+    setup();
+    fs::create_dir_all("extract_at_without_mtime").unwrap();
+    let file = fs::File::create("extract_at_without_mtime/versions.pna").unwrap();
+    let mut archive = Archive::write_header(file).unwrap();
+    let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"a1").unwrap();
+    builder.modified(Duration::from_secs(1_000));
+    archive.add_entry(builder.build().unwrap()).unwrap();
+    let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"a2").unwrap();
+    archive.add_entry(builder.build().unwrap()).unwrap();
+    let mut builder = EntryBuilder::new_file("b.txt".into(), WriteOptions::store()).unwrap();
+    builder.write_all(b"b1").unwrap();
+    archive.add_entry(builder.build().unwrap()).unwrap();
+    archive.finalize().unwrap();
+
+    extract(
+        "extract_at_without_mtime/versions.pna",
+        "extract_at_without_mtime/at",
+        &["--at", "@3000"],
+    );
+    assert_eq!(
+        fs::read_to_string("extract_at_without_mtime/at/a.txt").unwrap(),
+        "a1"
+    );
+    assert!(!fs::exists("extract_at_without_mtime/at/b.txt").unwrap());
+}
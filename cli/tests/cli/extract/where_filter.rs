@@ -0,0 +1,48 @@
+use crate::utils::{archive, setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn extract_where_filter() {
+    setup();
+    TestResources::extract_in("raw/", "extract_where_filter/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "extract_where_filter/where.pna",
+        "--overwrite",
+        "extract_where_filter/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let _ = fs::remove_dir_all("extract_where_filter/out/");
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_where_filter/where.pna",
+        "--overwrite",
+        "--out-dir",
+        "extract_where_filter/out/",
+        "--unstable",
+        "--where",
+        "kind == file && path ~ \"*.txt\" && size > 0",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut extracted = 0;
+    archive::for_each_entry("extract_where_filter/where.pna", |entry| {
+        let path = entry.header().path().as_str();
+        let out = format!("extract_where_filter/out/{path}");
+        let expected = path.ends_with(".txt") && entry.metadata().raw_file_size() > Some(0);
+        assert_eq!(fs::exists(&out).unwrap(), expected, "{path}");
+        extracted += usize::from(expected);
+    })
+    .unwrap();
+    assert!(extracted > 0);
+}
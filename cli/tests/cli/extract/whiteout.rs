@@ -0,0 +1,53 @@
+use crate::utils::setup;
+use clap::Parser;
+use pna::{Archive, EntryBuilder, WriteOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{fs, io::Write, path::Path};
+
+fn add_file(archive: &mut Archive<fs::File>, path: &str, content: &[u8]) {
+    let mut builder = EntryBuilder::new_file(path.into(), WriteOptions::store()).unwrap();
+    builder.write_all(content).unwrap();
+    archive.add_entry(builder.build().unwrap()).unwrap();
+}
+
+#[test]
+fn extract_whiteout_in_archive_order() {
+    setup();
+    let _ = fs::remove_dir_all("extract_whiteout_order");
+    fs::create_dir_all("extract_whiteout_order").unwrap();
+
+    let file = fs::File::create("extract_whiteout_order/whiteout.pna").unwrap();
+    let mut archive = Archive::write_header(file).unwrap();
+    add_file(&mut archive, "replaced.txt", &[b'o'; 1024 * 1024]);
+    let whiteout = EntryBuilder::new_whiteout("replaced.txt".into());
+    archive.add_entry(whiteout.build().unwrap()).unwrap();
+    add_file(&mut archive, "replaced.txt", b"new");
+    add_file(&mut archive, "removed.txt", &[b'r'; 1024 * 1024]);
+    let link = EntryBuilder::new_symbolic_link("link".into(), "removed.txt".into()).unwrap();
+    archive.add_entry(link.build().unwrap()).unwrap();
+    for path in ["removed.txt", "link"] {
+        let whiteout = EntryBuilder::new_whiteout(path.into());
+        archive.add_entry(whiteout.build().unwrap()).unwrap();
+    }
+    archive.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "extract_whiteout_order/whiteout.pna",
+        "--overwrite",
+        "--out-dir",
+        "extract_whiteout_order/out/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(
+        fs::read("extract_whiteout_order/out/replaced.txt").unwrap(),
+        b"new"
+    );
+    assert!(!Path::new("extract_whiteout_order/out/removed.txt").exists());
+    assert!(fs::symlink_metadata("extract_whiteout_order/out/link").is_err());
+}
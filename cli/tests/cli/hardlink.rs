@@ -137,3 +137,37 @@ fn hardlink_allow_unsafe_links() {
         fs::read_to_string("hardlink_allow_unsafe_links/dist/linked2.txt",).unwrap()
     );
 }
+
+#[test]
+fn hardlink_outside_of_root() {
+    setup();
+    fs::create_dir_all("hardlink_outside_of_root").unwrap();
+    fs::write("hardlink_outside_of_root/outside.txt", "outside\n").unwrap();
+    let file = fs::File::create("hardlink_outside_of_root/hardlink.pna").unwrap();
+    let mut writer = Archive::write_header(file).unwrap();
+    writer
+        .add_entry(
+            EntryBuilder::new_hard_link("dir/linked.txt".into(), "../../outside.txt".into())
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+    writer.finalize().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "hardlink_outside_of_root/hardlink.pna",
+        "--overwrite",
+        "--out-dir",
+        "hardlink_outside_of_root/dist",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    // Check skipped extract unsafe link
+    assert!(!fs::exists("hardlink_outside_of_root/dist/dir/linked.txt").unwrap());
+}
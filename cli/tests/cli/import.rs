@@ -0,0 +1,228 @@
+use crate::utils::{archive::for_each_entry, setup};
+use clap::Parser;
+use pna::{DataKind, ReadOptions};
+use portable_network_archive::{cli, command::Command};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    time::Duration,
+};
+
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {key}={value}\n");
+    let mut len = body.len() + 1;
+    while len.to_string().len() + body.len() != len {
+        len = len.to_string().len() + body.len();
+    }
+    format!("{len}{body}")
+}
+
+fn tar_header(ty: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(ty);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(1_600_000_000);
+    header
+}
+
+#[test]
+fn import_tar() {
+    setup();
+    fs::create_dir_all("import_tar").unwrap();
+    let file = fs::File::create("import_tar/source.tar.gz").unwrap();
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        file,
+        flate2::Compression::default(),
+    ));
+
+    let pax = [
+        pax_record("SCHILY.xattr.user.comment", "hello"),
+        pax_record("mtime", "1700000000.5"),
+        pax_record("uname", "alice"),
+    ]
+    .concat();
+    let mut header = tar_header(tar::EntryType::XHeader, 0o644, pax.len() as u64);
+    builder
+        .append_data(&mut header, "PaxHeaders/a.txt", pax.as_bytes())
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Regular, 0o644, 1);
+    header.set_uid(1000);
+    builder
+        .append_data(&mut header, "in/a.txt", &b"a"[..])
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Directory, 0o755, 0);
+    builder
+        .append_data(&mut header, "in/dir/", &[][..])
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Symlink, 0o777, 0);
+    builder
+        .append_link(&mut header, "in/link", "a.txt")
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Link, 0o644, 0);
+    builder
+        .append_link(&mut header, "in/hard", "in/a.txt")
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "import",
+        "import_tar/imported.pna",
+        "import_tar/source.tar.gz",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut entries = HashMap::new();
+    for_each_entry("import_tar/imported.pna", |entry| {
+        entries.insert(entry.header().path().to_string(), entry);
+    })
+    .unwrap();
+    assert_eq!(entries.len(), 4);
+
+    let file = &entries["in/a.txt"];
+    assert_eq!(file.header().data_kind(), DataKind::File);
+    let permission = file.metadata().permission().unwrap();
+    assert_eq!(permission.uid(), 1000);
+    assert_eq!(permission.uname(), "alice");
+    assert_eq!(permission.permissions(), 0o644);
+    assert_eq!(
+        file.metadata().modified(),
+        Some(Duration::new(1_700_000_000, 500_000_000))
+    );
+    assert_eq!(file.xattrs().len(), 1);
+    assert_eq!(file.xattrs()[0].name(), "user.comment");
+    assert_eq!(file.xattrs()[0].value(), b"hello");
+
+    assert_eq!(entries["in/dir"].header().data_kind(), DataKind::Directory);
+    assert_eq!(
+        entries["in/link"].header().data_kind(),
+        DataKind::SymbolicLink
+    );
+    assert_eq!(entries["in/hard"].header().data_kind(), DataKind::HardLink);
+}
+
+#[test]
+fn import_tar_nested_hard_link() {
+    setup();
+    let _ = fs::remove_dir_all("import_tar_nested_hard_link");
+    fs::create_dir_all("import_tar_nested_hard_link").unwrap();
+    let file = fs::File::create("import_tar_nested_hard_link/source.tar").unwrap();
+    let mut builder = tar::Builder::new(file);
+    let mut header = tar_header(tar::EntryType::Regular, 0o644, 1);
+    builder
+        .append_data(&mut header, "in/a.txt", &b"a"[..])
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Link, 0o644, 0);
+    builder
+        .append_link(&mut header, "in/dir/hard", "in/a.txt")
+        .unwrap();
+    let mut header = tar_header(tar::EntryType::Link, 0o644, 0);
+    builder
+        .append_link(&mut header, "in/same", "in/a.txt")
+        .unwrap();
+    builder.into_inner().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "import",
+        "import_tar_nested_hard_link/imported.pna",
+        "import_tar_nested_hard_link/source.tar",
+        "--overwrite",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut references = HashMap::new();
+    for_each_entry("import_tar_nested_hard_link/imported.pna", |entry| {
+        if entry.header().data_kind() == DataKind::HardLink {
+            let mut reference = String::new();
+            entry
+                .reader(ReadOptions::builder().build())
+                .unwrap()
+                .read_to_string(&mut reference)
+                .unwrap();
+            references.insert(entry.header().path().to_string(), reference);
+        }
+    })
+    .unwrap();
+    assert_eq!(references["in/dir/hard"], "../a.txt");
+    assert_eq!(references["in/same"], "a.txt");
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "x",
+        "import_tar_nested_hard_link/imported.pna",
+        "--overwrite",
+        "--out-dir",
+        "import_tar_nested_hard_link/out/",
+        "--allow-unsafe-links",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string("import_tar_nested_hard_link/out/in/dir/hard").unwrap(),
+        "a"
+    );
+}
+
+#[test]
+fn import_zip() {
+    setup();
+    fs::create_dir_all("import_zip").unwrap();
+    let file = fs::File::create("import_zip/source.zip").unwrap();
+    let mut writer = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o640);
+    writer.start_file("in/a.txt", options).unwrap();
+    writer.write_all(b"a").unwrap();
+    writer.add_directory("in/dir/", options).unwrap();
+    writer.add_symlink("in/link", "a.txt", options).unwrap();
+    writer.finish().unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "import",
+        "import_zip/imported.pna",
+        "import_zip/source.zip",
+        "--overwrite",
+        "--solid",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut entries = HashMap::new();
+    for_each_entry("import_zip/imported.pna", |entry| {
+        entries.insert(entry.header().path().to_string(), entry);
+    })
+    .unwrap();
+    assert_eq!(entries.len(), 3);
+    let file = &entries["in/a.txt"];
+    assert_eq!(file.header().data_kind(), DataKind::File);
+    assert_eq!(
+        file.metadata().permission().unwrap().permissions() & 0o777,
+        0o640
+    );
+    assert_eq!(entries["in/dir"].header().data_kind(), DataKind::Directory);
+    assert_eq!(
+        entries["in/link"].header().data_kind(),
+        DataKind::SymbolicLink
+    );
+}
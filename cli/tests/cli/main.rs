@@ -2,17 +2,23 @@
 mod acl;
 mod append;
 #[cfg(not(target_family = "wasm"))]
+mod cat;
+#[cfg(not(target_family = "wasm"))]
 mod cd_option;
 mod chmod;
 mod chown;
 #[cfg(not(target_family = "wasm"))]
 mod combination;
+mod compact;
 mod concat;
 mod create;
 mod delete;
+mod diff;
 mod encrypt;
+mod export;
 mod extract;
 mod hardlink;
+mod import;
 mod keep_acl;
 mod keep_all;
 mod list;
@@ -25,4 +31,5 @@ mod split;
 mod strip;
 mod update;
 pub mod utils;
+mod verify;
 mod xattr;
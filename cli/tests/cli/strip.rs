@@ -60,3 +60,48 @@ fn archive_strip_metadata() {
 
     diff("archive_strip_metadata/in/", "archive_strip_metadata/out/").unwrap();
 }
+
+#[test]
+fn strip_dry_run_unchanged() {
+    setup();
+    let _ = std::fs::remove_dir_all("strip_dry_run_unchanged");
+    std::fs::create_dir_all("strip_dry_run_unchanged/in").unwrap();
+    std::fs::write("strip_dry_run_unchanged/in/a.txt", "a").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "strip_dry_run_unchanged/dry_run.pna",
+        "--overwrite",
+        "strip_dry_run_unchanged/in/a.txt",
+        "--keep-timestamp",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "strip",
+        "strip_dry_run_unchanged/dry_run.pna",
+        "--keep-timestamp",
+        "--dry-run",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("pna").unwrap();
+    cmd.args([
+        "--quiet",
+        "strip",
+        "strip_dry_run_unchanged/dry_run.pna",
+        "--dry-run",
+        "--unstable",
+    ])
+    .assert()
+    .success()
+    .stdout("replace strip_dry_run_unchanged/in/a.txt\n");
+}
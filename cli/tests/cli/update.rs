@@ -1,6 +1,7 @@
 mod atime;
 mod ctime;
 mod deletion;
+mod dry_run;
 mod error;
 mod exclude;
 mod files_from_stdin;
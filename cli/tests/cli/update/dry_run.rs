@@ -0,0 +1,38 @@
+use crate::utils::{setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn update_dry_run() {
+    setup();
+    TestResources::extract_in("raw/", "update_dry_run/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "update_dry_run/dry_run.pna",
+        "--overwrite",
+        "update_dry_run/in/",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    let before = fs::read("update_dry_run/dry_run.pna").unwrap();
+    fs::write("update_dry_run/in/raw/new.txt", "new").unwrap();
+
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "update",
+        "update_dry_run/dry_run.pna",
+        "update_dry_run/in/",
+        "-n",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    assert_eq!(fs::read("update_dry_run/dry_run.pna").unwrap(), before);
+}
@@ -0,0 +1,56 @@
+use crate::utils::{setup, TestResources};
+use clap::Parser;
+use portable_network_archive::{cli, command::Command};
+use std::fs;
+
+#[test]
+fn verify_archive() {
+    setup();
+    TestResources::extract_in("raw/", "verify_archive/in/").unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "c",
+        "verify_archive/verify.pna",
+        "--overwrite",
+        "verify_archive/in/",
+        "--password",
+        "password",
+        "--aes",
+        "ctr",
+        "--pbkdf2",
+        "r=1",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+    cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "verify",
+        "verify_archive/verify.pna",
+        "--password",
+        "password",
+    ])
+    .unwrap()
+    .execute()
+    .unwrap();
+
+    let mut archive = fs::read("verify_archive/verify.pna").unwrap();
+    let middle = archive.len() / 2;
+    archive[middle] ^= 0xff;
+    fs::write("verify_archive/broken.pna", archive).unwrap();
+    let result = cli::Cli::try_parse_from([
+        "pna",
+        "--quiet",
+        "experimental",
+        "verify",
+        "verify_archive/broken.pna",
+        "--password",
+        "password",
+    ])
+    .unwrap()
+    .execute();
+    assert!(result.is_err());
+}
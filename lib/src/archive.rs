@@ -1,14 +1,20 @@
 mod header;
 mod read;
+mod verify;
+mod version;
 mod write;
 
 use crate::{
     chunk::{ChunkStreamWriter, RawChunk},
     cipher::CipherWriter,
     compress::CompressionWriter,
+    entry::{SolidHeader, WriteOptions},
+    progress::Progress,
 };
 pub use header::*;
 use std::io::prelude::*;
+pub use verify::*;
+pub use version::*;
 pub(crate) use {read::*, write::*};
 
 /// An object providing access to a PNA file.
@@ -65,6 +71,9 @@ pub(crate) use {read::*, write::*};
 pub struct Archive<T> {
     inner: T,
     header: ArchiveHeader,
+    // the last `AKDF` chunk read or written
+    key_derivation: Option<String>,
+    progress: Progress,
     // following fields are only use in reader mode
     next_archive: bool,
     buf: Vec<RawChunk>,
@@ -79,6 +88,8 @@ impl<T> Archive<T> {
         Self {
             inner,
             header,
+            key_derivation: None,
+            progress: Progress::none(),
             next_archive: false,
             buf,
         }
@@ -131,12 +142,17 @@ impl<T> Archive<T> {
 pub struct SolidArchive<T: Write> {
     archive_header: ArchiveHeader,
     inner: CompressionWriter<CipherWriter<ChunkStreamWriter<T>>>,
+    // header and options of the solid data buffered in plain until finalization
+    seeded: Option<(SolidHeader, WriteOptions)>,
+    // the last `AKDF` chunk written in the solid data stream
+    entries_key_derivation: Option<String>,
+    progress: Progress,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::entry::*;
+    use crate::{chunk::ChunkType, entry::*};
     use std::io::{self, Cursor};
     use std::time::Duration;
     #[cfg(all(target_family = "wasm", target_os = "unknown"))]
@@ -345,6 +361,294 @@ mod tests {
         }
     }
 
+    fn archive_key_option() -> WriteOptions {
+        WriteOptions::builder()
+            .compression(Compression::ZStandard)
+            .encryption(Encryption::Aes)
+            .cipher_mode(CipherMode::CTR)
+            .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+            .password(Some("password"))
+            .archive_key(true)
+            .build()
+    }
+
+    fn count_chunks(archive: &[u8], ty: ChunkType) -> usize {
+        let mut reader = &archive[PNA_HEADER.len()..];
+        let mut count = 0;
+        while !reader.is_empty() {
+            if crate::chunk::read_chunk(&mut reader).unwrap().ty == ty {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn read_all(archive: &[u8], read_options: &ReadOptions) -> Vec<(String, Vec<u8>)> {
+        let mut reader = Archive::read_header(archive).unwrap();
+        reader
+            .entries_with_password(read_options.password())
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let mut body = Vec::new();
+                entry
+                    .reader(read_options)
+                    .unwrap()
+                    .read_to_end(&mut body)
+                    .unwrap();
+                (entry.header().path().to_string(), body)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn archive_key() {
+        let option = archive_key_option();
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for i in 0..3 {
+            writer
+                .add_entry({
+                    let mut builder =
+                        EntryBuilder::new_file(format!("text{i}").into(), &option).unwrap();
+                    builder.write_all(format!("text{i}").as_bytes()).unwrap();
+                    builder.build().unwrap()
+                })
+                .unwrap();
+        }
+        writer
+            .write_file("text3".into(), Metadata::new(), &option, |w| {
+                w.write_all(b"text3")
+            })
+            .unwrap();
+        let archive = writer.finalize().unwrap();
+        assert_eq!(count_chunks(&archive, ChunkType::AKDF), 1);
+
+        let entries = read_all(&archive, &ReadOptions::with_password(Some("password")));
+        assert_eq!(entries.len(), 4);
+        for (i, (name, body)) in entries.into_iter().enumerate() {
+            assert_eq!(name, format!("text{i}"));
+            assert_eq!(body, format!("text{i}").as_bytes());
+        }
+        let mut reader = Archive::read_header(archive.as_slice()).unwrap();
+        let entry = reader.entries_skip_solid().next().unwrap().unwrap();
+        let mut body = Vec::new();
+        let result = entry
+            .reader(ReadOptions::with_password(Some("wrong")))
+            .and_then(|mut r| r.read_to_end(&mut body));
+        assert!(result.is_err() || body != b"text0");
+    }
+
+    #[test]
+    fn candidate_passwords() {
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for password in ["first", "second"] {
+            let option = WriteOptions::builder()
+                .compression(Compression::Deflate)
+                .encryption(Encryption::Camellia)
+                .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+                .password(Some(password))
+                .build();
+            let mut builder = EntryBuilder::new_file(password.into(), &option).unwrap();
+            builder.write_all(password.as_bytes()).unwrap();
+            writer.add_entry(builder.build().unwrap()).unwrap();
+        }
+        let archive = writer.finalize().unwrap();
+
+        let read_options = ReadOptions::with_passwords(["wrong", "second", "first"]);
+        for (name, body) in read_all(&archive, &read_options) {
+            assert_eq!(name.as_bytes(), body);
+        }
+
+        let read_options = ReadOptions::builder()
+            .add_password("wrong")
+            .add_password("first")
+            .build();
+        let mut reader = Archive::read_header(archive.as_slice()).unwrap();
+        let mut entries = reader.entries_skip_solid();
+        assert!(entries
+            .next()
+            .unwrap()
+            .unwrap()
+            .reader(&read_options)
+            .is_ok());
+        let err = entries.next().unwrap().unwrap().reader(&read_options).err();
+        assert_eq!(err.unwrap().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    fn candidate_password_archive(
+        option: impl Fn(&str) -> WriteOptions,
+        archive_key: bool,
+    ) -> Vec<u8> {
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for password in ["first", "second"] {
+            let option = option(password)
+                .into_builder()
+                .archive_key(archive_key)
+                .build();
+            let mut builder = EntryBuilder::new_file(password.into(), &option).unwrap();
+            builder
+                .write_all(&password.repeat(100).into_bytes())
+                .unwrap();
+            writer.add_entry(builder.build().unwrap()).unwrap();
+        }
+        writer.finalize().unwrap()
+    }
+
+    fn store_option(password: &str, cipher_mode: CipherMode) -> WriteOptions {
+        WriteOptions::builder()
+            .compression(Compression::No)
+            .encryption(Encryption::Aes)
+            .cipher_mode(cipher_mode)
+            .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+            .password(Some(password))
+            .build()
+    }
+
+    #[test]
+    fn candidate_passwords_store() {
+        let read_options = ReadOptions::with_passwords(["wrong", "second", "first"]);
+        let archive = candidate_password_archive(|it| store_option(it, CipherMode::CBC), false);
+        for (name, body) in read_all(&archive, &read_options) {
+            assert_eq!(name.repeat(100).as_bytes(), body);
+        }
+        let archive = candidate_password_archive(|it| store_option(it, CipherMode::CTR), true);
+        for (name, body) in read_all(&archive, &read_options) {
+            assert_eq!(name.repeat(100).as_bytes(), body);
+        }
+
+        // Nothing tells a wrong key from the right one, so the first password is used.
+        let read_options = ReadOptions::with_passwords(["first", "second"]);
+        let archive = candidate_password_archive(|it| store_option(it, CipherMode::CTR), false);
+        let entries = read_all(&archive, &read_options);
+        assert_eq!(entries[0].1, b"first".repeat(100));
+        assert_ne!(entries[1].1, b"second".repeat(100));
+    }
+
+    #[test]
+    fn candidate_passwords_large() {
+        let body = (0..200 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for password in ["first", "second"] {
+            let option = WriteOptions::builder()
+                .compression(Compression::ZStandard)
+                .encryption(Encryption::Aes)
+                .cipher_mode(CipherMode::CBC)
+                .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+                .password(Some(password))
+                .build();
+            let mut builder = EntryBuilder::new_file(password.into(), &option).unwrap();
+            builder.write_all(&body).unwrap();
+            writer.add_entry(builder.build().unwrap()).unwrap();
+        }
+        let archive = writer.finalize().unwrap();
+
+        let read_options = ReadOptions::with_passwords(["wrong", "second", "first"]);
+        for (_, data) in read_all(&archive, &read_options) {
+            assert_eq!(data, body);
+        }
+    }
+
+    #[test]
+    fn candidate_passwords_solid() {
+        for (compression, cipher_mode) in [
+            (Compression::No, CipherMode::CBC),
+            (Compression::No, CipherMode::CTR),
+            (Compression::ZStandard, CipherMode::CTR),
+        ] {
+            let mut writer = Archive::write_header(Vec::new()).unwrap();
+            for password in ["first", "second"] {
+                let mut builder = SolidEntryBuilder::new(
+                    WriteOptions::builder()
+                        .compression(compression)
+                        .encryption(Encryption::Aes)
+                        .cipher_mode(cipher_mode)
+                        .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+                        .password(Some(password))
+                        .build(),
+                )
+                .unwrap();
+                let mut entry =
+                    EntryBuilder::new_file(password.into(), WriteOptions::store()).unwrap();
+                entry.write_all(password.as_bytes()).unwrap();
+                builder.add_entry(entry.build().unwrap()).unwrap();
+                writer.add_entry(builder.build().unwrap()).unwrap();
+            }
+            let archive = writer.finalize().unwrap();
+
+            let read_options = ReadOptions::with_passwords(["wrong", "second", "first"]);
+            let mut reader = Archive::read_header(archive.as_slice()).unwrap();
+            for entry in reader.entries() {
+                let ReadEntry::Solid(solid) = entry.unwrap() else {
+                    panic!()
+                };
+                let password = solid
+                    .matching_password(&["wrong", "second", "first"])
+                    .unwrap();
+                for entry in solid.entries_with_options(&read_options).unwrap() {
+                    let entry = entry.unwrap();
+                    assert_eq!(Some(entry.header().path().as_str()), password);
+                    let mut body = Vec::new();
+                    entry
+                        .reader(ReadOptions::builder().build())
+                        .unwrap()
+                        .read_to_end(&mut body)
+                        .unwrap();
+                    assert_eq!(entry.header().path().as_str().as_bytes(), body);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn archive_key_copy_entry() {
+        let archive = create_archive(b"archive text", archive_key_option()).unwrap();
+        let mut reader = Archive::read_header(archive.as_slice()).unwrap();
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for entry in reader.entries_skip_solid() {
+            writer.add_entry(entry.unwrap()).unwrap();
+        }
+        let copied = writer.finalize().unwrap();
+        assert_eq!(count_chunks(&copied, ChunkType::AKDF), 1);
+        assert_eq!(
+            read_all(&copied, &ReadOptions::with_password(Some("password"))),
+            [("test/text".into(), b"archive text".to_vec())]
+        );
+    }
+
+    #[test]
+    fn archive_key_split_archive() {
+        let option = archive_key_option();
+        let mut entry = EntryBuilder::new_file("text".into(), &option).unwrap();
+        entry.write_all(&b"text".repeat(100)).unwrap();
+        let part = EntryPart::from(entry.build().unwrap());
+        let (first, remaining) = part.as_ref().try_split(200).unwrap();
+
+        let (mut part1, mut part2) = (Vec::new(), Vec::new());
+        let mut writer = Archive::write_header(&mut part1).unwrap();
+        writer.add_entry_part(first).unwrap();
+        let mut writer = writer.split_to_next_archive(&mut part2).unwrap();
+        writer.add_entry_part(remaining.unwrap()).unwrap();
+        writer.finalize().unwrap();
+        assert_eq!(count_chunks(&part1, ChunkType::AKDF), 1);
+        assert_eq!(count_chunks(&part2, ChunkType::AKDF), 0);
+
+        let mut reader = Archive::read_header(&part1[..]).unwrap();
+        assert!(reader.entries().next().is_none());
+        let mut reader = reader.read_next_archive(&part2[..]).unwrap();
+        let entry = reader.entries_skip_solid().next().unwrap().unwrap();
+        let mut body = Vec::new();
+        entry
+            .reader(ReadOptions::with_password(Some("password")))
+            .unwrap()
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, b"text".repeat(100));
+    }
+
+    #[test]
+    fn archive_key_solid_archive() {
+        solid_archive(archive_key_option());
+    }
+
     #[test]
     fn solid_store_camellia_cbc() {
         solid_archive(
@@ -3,7 +3,7 @@ mod slice;
 use crate::{
     archive::{Archive, ArchiveHeader, PNA_HEADER},
     chunk::{read_chunk, Chunk, ChunkReader, ChunkType, RawChunk},
-    entry::{Entry, NormalEntry, RawEntry, ReadEntry},
+    entry::{read_entry_with_key_derivation, Entry, NormalEntry, RawEntry, ReadEntry},
 };
 #[cfg(feature = "unstable-async")]
 use futures_util::AsyncReadExt;
@@ -108,7 +108,10 @@ impl<R: Read> Archive<R> {
     fn read_entry(&mut self) -> io::Result<Option<ReadEntry>> {
         let entry = self.next_raw_item()?;
         match entry {
-            Some(entry) => Ok(Some(entry.try_into()?)),
+            Some(entry) => Ok(Some(read_entry_with_key_derivation(
+                entry,
+                &mut self.key_derivation,
+            )?)),
             None => Ok(None),
         }
     }
@@ -189,7 +192,8 @@ impl<R: Read> Archive<R> {
     #[inline]
     pub fn read_next_archive<OR: Read>(self, reader: OR) -> io::Result<Archive<OR>> {
         let current_header = self.header;
-        let next = Archive::<OR>::read_header_with_buffer(reader, self.buf)?;
+        let mut next = Archive::<OR>::read_header_with_buffer(reader, self.buf)?;
+        next.key_derivation = self.key_derivation;
         if current_header.archive_number + 1 != next.header.archive_number {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -293,7 +297,10 @@ impl<R: futures_io::AsyncRead + Unpin> Archive<R> {
     pub async fn read_entry_async(&mut self) -> io::Result<Option<ReadEntry>> {
         let entry = self.next_raw_item_async().await?;
         Ok(match entry {
-            Some(entry) => Some(entry.try_into()?),
+            Some(entry) => Some(read_entry_with_key_derivation(
+                entry,
+                &mut self.key_derivation,
+            )?),
             None => None,
         })
     }
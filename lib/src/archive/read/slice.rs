@@ -1,6 +1,8 @@
 use crate::{
-    archive::ArchiveHeader, chunk::read_chunk_from_slice, entry::RawEntry, Archive, Chunk,
-    ChunkType, Entry, NormalEntry, RawChunk, ReadEntry, PNA_HEADER,
+    archive::ArchiveHeader,
+    chunk::read_chunk_from_slice,
+    entry::{read_entry_with_key_derivation, RawEntry},
+    Archive, Chunk, ChunkType, Entry, NormalEntry, RawChunk, ReadEntry, PNA_HEADER,
 };
 use std::borrow::Cow;
 use std::io;
@@ -92,7 +94,10 @@ impl<'d> Archive<&'d [u8]> {
     fn read_entry_slice(&mut self) -> io::Result<Option<ReadEntry<Cow<'d, [u8]>>>> {
         let entry = self.next_raw_item_slice()?;
         match entry {
-            Some(entry) => Ok(Some(entry.try_into()?)),
+            Some(entry) => Ok(Some(read_entry_with_key_derivation(
+                entry,
+                &mut self.key_derivation,
+            )?)),
             None => Ok(None),
         }
     }
@@ -177,7 +182,8 @@ impl<'d> Archive<&'d [u8]> {
     #[inline]
     pub fn read_next_archive_from_slice(self, bytes: &[u8]) -> io::Result<Archive<&[u8]>> {
         let current_header = self.header;
-        let next = Archive::read_header_from_slice_with_buffer(bytes, self.buf)?;
+        let mut next = Archive::read_header_from_slice_with_buffer(bytes, self.buf)?;
+        next.key_derivation = self.key_derivation;
         if current_header.archive_number + 1 != next.header.archive_number {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -272,7 +278,7 @@ mod tests {
     #[test]
     fn read_header() {
         let result = read_header_from_slice(PNA_HEADER).unwrap();
-        assert_eq!(result, &[]);
+        assert_eq!(result, b"");
     }
 
     #[test]
@@ -0,0 +1,411 @@
+use crate::{
+    archive::Archive,
+    chunk::{read_chunk_unchecked, ChunkType, RawChunk},
+    entry::{
+        read_entry_with_key_derivation, EntryHeader, EntryName, NormalEntry, RawEntry, ReadEntry,
+        ReadOption,
+    },
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, Read},
+    mem,
+};
+
+/// Kind of a problem found by [`Archive::verify`].
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[non_exhaustive]
+pub enum VerifyProblemKind {
+    /// The CRC of the chunk does not match its contents.
+    BrokenChunk(ChunkType),
+    /// The chunk appeared where it is not allowed.
+    UnexpectedChunk(ChunkType),
+    /// The archive ended before the `AEND` chunk.
+    UnexpectedEof,
+    /// The entry could not be parsed.
+    InvalidEntry(String),
+    /// The entry data could not be decrypted or decompressed.
+    InvalidData(String),
+    /// The size of the decoded data differs from the size recorded in the `fSIZ` chunk.
+    SizeMismatch {
+        /// Size recorded in the `fSIZ` chunk.
+        expected: u128,
+        /// Size of the decoded data.
+        actual: u128,
+    },
+}
+
+impl Display for VerifyProblemKind {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BrokenChunk(ty) => write!(f, "broken chunk `{ty}`"),
+            Self::UnexpectedChunk(ty) => write!(f, "unexpected chunk `{ty}`"),
+            Self::UnexpectedEof => f.write_str("unexpected end of archive"),
+            Self::InvalidEntry(e) => write!(f, "invalid entry: {e}"),
+            Self::InvalidData(e) => write!(f, "invalid data: {e}"),
+            Self::SizeMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "size mismatch: expected {expected} bytes, got {actual} bytes"
+                )
+            }
+        }
+    }
+}
+
+/// A problem found by [`Archive::verify`].
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct VerifyProblem {
+    solid_entry: Option<usize>,
+    path: Option<EntryName>,
+    kind: VerifyProblemKind,
+}
+
+impl VerifyProblem {
+    /// Path of the entry that has the problem, if it is known.
+    #[inline]
+    pub fn path(&self) -> Option<&EntryName> {
+        self.path.as_ref()
+    }
+
+    /// Kind of the problem.
+    #[inline]
+    pub fn kind(&self) -> &VerifyProblemKind {
+        &self.kind
+    }
+
+    /// Index of the solid entry that has the problem, or that contains the entry that has it,
+    /// counting the solid entries checked by the same [`Archive::verify`] call from 0.
+    #[inline]
+    pub const fn solid_entry(&self) -> Option<usize> {
+        self.solid_entry
+    }
+}
+
+impl Display for VerifyProblem {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(index) = self.solid_entry {
+            write!(f, "solid entry {index}: ")?;
+        }
+        match &self.path {
+            Some(path) => write!(f, "{path}: {}", self.kind),
+            None => Display::fmt(&self.kind, f),
+        }
+    }
+}
+
+/// Result of [`Archive::verify`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct VerifyReport {
+    entries: usize,
+    problems: Vec<VerifyProblem>,
+    solid_entries: usize,
+    /// Index of the solid entry being checked, which is attached to the problems found.
+    solid_entry: Option<usize>,
+}
+
+impl VerifyReport {
+    /// Number of entries checked, counting the entries in solid entries.
+    #[inline]
+    pub const fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Problems found, in the order they appeared in the archive.
+    #[inline]
+    pub fn problems(&self) -> &[VerifyProblem] {
+        &self.problems
+    }
+
+    /// Returns `true` if no problem was found.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn push(&mut self, path: Option<EntryName>, kind: VerifyProblemKind) {
+        self.problems.push(VerifyProblem {
+            solid_entry: self.solid_entry,
+            path,
+            kind,
+        });
+    }
+}
+
+/// Path of the entry that is being read, if its header is available.
+fn entry_path(chunks: &[RawChunk]) -> Option<EntryName> {
+    let chunk = chunks.iter().find(|it| it.ty == ChunkType::FHED)?;
+    EntryHeader::try_from_bytes(&chunk.data)
+        .ok()
+        .map(|it| it.path().clone())
+}
+
+impl<R: Read> Archive<R> {
+    /// Verifies the rest of the archive up to the `AEND` chunk.
+    ///
+    /// Checks the CRC and the order of every chunk, then decrypts and decompresses the data of
+    /// every entry, including the entries in solid entries, and compares its size with the
+    /// one recorded in the `fSIZ` chunk. Problems in the archive do not stop the verification;
+    /// they are collected into the returned [VerifyReport].
+    ///
+    /// For a split archive, call this method for each part, moving to the next part with
+    /// [`Archive::read_next_archive`] while [`Archive::has_next_archive`] returns `true`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an I/O error other than an unexpected end of the archive occurs
+    /// while reading from the archive.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libpna::{Archive, ReadOptions};
+    /// use std::{fs, io};
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let file = fs::File::open("foo.pna")?;
+    /// let mut archive = Archive::read_header(file)?;
+    /// let report = archive.verify(ReadOptions::with_password(Some("password")))?;
+    /// for problem in report.problems() {
+    ///     eprintln!("{problem}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn verify(&mut self, option: impl ReadOption) -> io::Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let mut chunks = mem::take(&mut self.buf);
+        let mut opened = chunks
+            .iter()
+            .map(|it| it.ty)
+            .find(|ty| matches!(*ty, ChunkType::FHED | ChunkType::SHED));
+        let mut broken = false;
+        loop {
+            let (chunk, crc_matched) = match read_chunk_unchecked(&mut self.inner) {
+                Ok(it) => it,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    report.push(entry_path(&chunks), VerifyProblemKind::UnexpectedEof);
+                    return Ok(report);
+                }
+                Err(e) => return Err(e),
+            };
+            if !crc_matched {
+                broken = true;
+                report.push(
+                    entry_path(&chunks),
+                    VerifyProblemKind::BrokenChunk(chunk.ty),
+                );
+            }
+            match (opened, chunk.ty) {
+                (_, ChunkType::AEND) if self.next_archive => {
+                    self.buf = chunks;
+                    return Ok(report);
+                }
+                (None, ChunkType::AEND) => return Ok(report),
+                (Some(_), ChunkType::AEND) => {
+                    report.push(entry_path(&chunks), VerifyProblemKind::UnexpectedEof);
+                    return Ok(report);
+                }
+                (_, ChunkType::ANXT) => self.next_archive = true,
+                (None, ChunkType::FHED | ChunkType::SHED) => {
+                    opened = Some(chunk.ty);
+                    chunks.push(chunk);
+                }
+                (Some(ChunkType::FHED), ChunkType::FEND)
+                | (Some(ChunkType::SHED), ChunkType::SEND) => {
+                    chunks.push(chunk);
+                    let entry = RawEntry(mem::take(&mut chunks));
+                    if mem::take(&mut broken) {
+                        report.entries += 1;
+                    } else {
+                        self.verify_entry(entry, &option, &mut report);
+                    }
+                    opened = None;
+                }
+                (None, ChunkType::AKDF) => chunks.push(chunk),
+                (
+                    _,
+                    ty @ (ChunkType::FHED
+                    | ChunkType::SHED
+                    | ChunkType::FEND
+                    | ChunkType::SEND
+                    | ChunkType::AKDF
+                    | ChunkType::AHED),
+                ) => {
+                    report.push(entry_path(&chunks), VerifyProblemKind::UnexpectedChunk(ty));
+                    broken |= opened.is_some();
+                }
+                (None, ty) if ty.is_critical() => {
+                    report.push(None, VerifyProblemKind::UnexpectedChunk(ty));
+                }
+                _ => chunks.push(chunk),
+            }
+        }
+    }
+
+    fn verify_entry(
+        &mut self,
+        entry: RawEntry,
+        option: impl ReadOption,
+        report: &mut VerifyReport,
+    ) {
+        let path = entry_path(&entry.0);
+        match read_entry_with_key_derivation(entry, &mut self.key_derivation) {
+            Ok(ReadEntry::Normal(entry)) => verify_normal_entry(&entry, option, report),
+            Ok(ReadEntry::Solid(solid)) => {
+                report.solid_entry = Some(report.solid_entries);
+                report.solid_entries += 1;
+                match solid.entries_with_options(&option) {
+                    Ok(entries) => {
+                        for entry in entries {
+                            match entry {
+                                Ok(entry) => verify_normal_entry(&entry, &option, report),
+                                Err(e) => {
+                                    report
+                                        .push(None, VerifyProblemKind::InvalidData(e.to_string()));
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => report.push(None, VerifyProblemKind::InvalidData(e.to_string())),
+                }
+                report.solid_entry = None;
+            }
+            Err(e) => {
+                report.entries += 1;
+                report.push(path, VerifyProblemKind::InvalidEntry(e.to_string()));
+            }
+        }
+    }
+}
+
+fn verify_normal_entry(entry: &NormalEntry, option: impl ReadOption, report: &mut VerifyReport) {
+    report.entries += 1;
+    let path = entry.header().path();
+    let size = entry
+        .reader(option)
+        .and_then(|mut reader| io::copy(&mut reader, &mut io::sink()));
+    match (size, entry.metadata().raw_file_size()) {
+        (Err(e), _) => report.push(
+            Some(path.clone()),
+            VerifyProblemKind::InvalidData(e.to_string()),
+        ),
+        (Ok(actual), Some(expected)) if u128::from(actual) != expected => report.push(
+            Some(path.clone()),
+            VerifyProblemKind::SizeMismatch {
+                expected,
+                actual: actual.into(),
+            },
+        ),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Compression, Encryption, EntryBuilder, HashAlgorithm, ReadOptions, SolidEntryBuilder,
+        WriteOptions, PNA_HEADER,
+    };
+    use std::io::Write;
+    #[cfg(all(target_family = "wasm", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    fn write_options() -> WriteOptions {
+        WriteOptions::builder()
+            .compression(Compression::ZStandard)
+            .encryption(Encryption::Aes)
+            .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+            .password(Some("password"))
+            .build()
+    }
+
+    fn archive() -> Vec<u8> {
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        for name in ["a.txt", "b.txt"] {
+            let mut builder = EntryBuilder::new_file(name.into(), write_options()).unwrap();
+            builder.write_all(name.as_bytes()).unwrap();
+            writer.add_entry(builder.build().unwrap()).unwrap();
+        }
+        let mut solid = SolidEntryBuilder::new(write_options()).unwrap();
+        let mut builder = EntryBuilder::new_file("c.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"c.txt").unwrap();
+        solid.add_entry(builder.build().unwrap()).unwrap();
+        writer.add_entry(solid.build().unwrap()).unwrap();
+        writer.finalize().unwrap()
+    }
+
+    fn verify(archive: &[u8], password: &str) -> VerifyReport {
+        Archive::read_header(archive)
+            .unwrap()
+            .verify(ReadOptions::with_password(Some(password)))
+            .unwrap()
+    }
+
+    #[test]
+    fn verify_valid_archive() {
+        let report = verify(&archive(), "password");
+        assert!(report.is_ok(), "{:?}", report.problems());
+        assert_eq!(report.entries(), 3);
+    }
+
+    #[test]
+    fn verify_wrong_password() {
+        let report = verify(&archive(), "wrong");
+        assert_eq!(report.entries(), 2);
+        assert!(report
+            .problems()
+            .iter()
+            .all(|it| matches!(it.kind(), VerifyProblemKind::InvalidData(_))));
+        assert_eq!(report.problems()[0].path(), Some(&EntryName::from("a.txt")));
+    }
+
+    #[test]
+    fn verify_solid_entry_with_candidate_passwords() {
+        let report = Archive::read_header(archive().as_slice())
+            .unwrap()
+            .verify(ReadOptions::with_passwords(["wrong", "password"]))
+            .unwrap();
+        assert!(report.is_ok(), "{:?}", report.problems());
+        assert_eq!(report.entries(), 3);
+
+        let report = verify(&archive(), "wrong");
+        let problem = report.problems().last().unwrap();
+        assert_eq!(problem.solid_entry(), Some(0));
+        assert_eq!(problem.path(), None);
+        assert!(problem.to_string().starts_with("solid entry 0: "));
+    }
+
+    #[test]
+    fn verify_broken_chunk() {
+        let mut archive = archive();
+        // The last byte of the CRC of the `FHED` chunk of the first entry.
+        let offset = PNA_HEADER.len() + 12 + 8 + 8 + 6 + "a.txt".len() + 3;
+        archive[offset] ^= 0xff;
+        let report = verify(&archive, "password");
+        assert_eq!(report.entries(), 3);
+        assert_eq!(
+            report.problems(),
+            [VerifyProblem {
+                solid_entry: None,
+                path: None,
+                kind: VerifyProblemKind::BrokenChunk(ChunkType::FHED),
+            }]
+        );
+    }
+
+    #[test]
+    fn verify_truncated_archive() {
+        let archive = archive();
+        let report = verify(&archive[..archive.len() - 20], "password");
+        assert_eq!(
+            report.problems().last().map(VerifyProblem::kind),
+            Some(&VerifyProblemKind::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    archive::Archive,
+    entry::{DataKind, EntryName, NormalEntry},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+    time::Duration,
+};
+
+/// Selects which version of each path [`EntryVersions::select`] resolves.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum VersionSelector {
+    /// The last version of each path in the archive.
+    Latest,
+    /// The last version of each path whose modification time is not later than the given
+    /// time since the Unix epoch.
+    ///
+    /// This compares the modification time recorded for each version, not the time it was
+    /// added to the archive, so a file appended with an older modification time is treated as
+    /// an older version. Versions without a modification time are never selected.
+    AsOf(Duration),
+    /// The n-th version of each path, counting from 1 in archive order. Paths with fewer
+    /// versions are not resolved.
+    Nth(usize),
+}
+
+impl VersionSelector {
+    /// Returns `true` if `entry`, the `n`-th version of its path counting from 1, can be
+    /// selected. The last version that can be selected is the one resolved.
+    #[inline]
+    fn accepts<T>(&self, entry: &NormalEntry<T>, n: usize) -> bool {
+        match *self {
+            Self::Latest => true,
+            Self::AsOf(time) => entry.metadata().modified().is_some_and(|m| m <= time),
+            Self::Nth(nth) => n == nth,
+        }
+    }
+}
+
+/// Every version of the entries in an archive, grouped by path.
+///
+/// An archive that is only ever appended to can contain the same path multiple times. Each
+/// occurrence is a version of that path, and later occurrences supersede earlier ones. A
+/// [`DataKind::Whiteout`] entry records that the path was deleted.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct EntryVersions<T = Vec<u8>> {
+    paths: Vec<(EntryName, Vec<NormalEntry<T>>)>,
+    index: HashMap<EntryName, usize>,
+}
+
+impl<T> EntryVersions<T> {
+    /// Groups the given entries by path.
+    ///
+    /// Paths keep the order of their first version, and the versions of a path keep the
+    /// order in which they appear.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error yielded by `entries`.
+    #[inline]
+    pub fn from_entries<I>(entries: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = io::Result<NormalEntry<T>>>,
+    {
+        let mut versions = Self {
+            paths: Vec::new(),
+            index: HashMap::new(),
+        };
+        for entry in entries {
+            versions.push(entry?);
+        }
+        Ok(versions)
+    }
+
+    fn push(&mut self, entry: NormalEntry<T>) {
+        let path = entry.header().path();
+        match self.index.get(path) {
+            Some(&i) => self.paths[i].1.push(entry),
+            None => {
+                self.index.insert(path.clone(), self.paths.len());
+                self.paths.push((path.clone(), vec![entry]));
+            }
+        }
+    }
+
+    /// Returns the versions of `path`, oldest first.
+    #[inline]
+    pub fn get(&self, path: &EntryName) -> Option<&[NormalEntry<T>]> {
+        self.index.get(path).map(|&i| self.paths[i].1.as_slice())
+    }
+
+    /// Returns an iterator over every path and its versions, oldest first.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&EntryName, &[NormalEntry<T>])> {
+        self.paths.iter().map(|(path, it)| (path, it.as_slice()))
+    }
+
+    /// Returns the number of distinct paths.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns `true` if there are no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Resolves one version of every path.
+    ///
+    /// Paths whose selected version is a [`DataKind::Whiteout`] entry are deleted at that
+    /// point, so they are left out of the result along with paths that have no matching
+    /// version.
+    #[inline]
+    pub fn select(self, selector: VersionSelector) -> Vec<NormalEntry<T>> {
+        self.paths
+            .into_iter()
+            .filter_map(|(_, versions)| {
+                versions
+                    .into_iter()
+                    .enumerate()
+                    .rfind(|(i, it)| selector.accepts(it, i + 1))
+                    .map(|(_, it)| it)
+            })
+            .filter(|it| it.header().data_kind() != DataKind::Whiteout)
+            .collect()
+    }
+}
+
+/// Resolves the versions a [`VersionSelector`] selects while the entries are read one by one,
+/// without keeping them in memory.
+///
+/// Pass every entry of the archive to [`VersionPlan::push`] in order, then read the archive
+/// again and keep only the entries whose position is in [`VersionPlan::selected`].
+///
+/// # Examples
+/// ```no_run
+/// use libpna::{Archive, VersionPlan, VersionSelector};
+/// use std::fs;
+/// # use std::io;
+///
+/// # fn main() -> io::Result<()> {
+/// let mut plan = VersionPlan::new(VersionSelector::Latest);
+/// let mut archive = Archive::read_header(fs::File::open("foo.pna")?)?;
+/// for entry in archive.entries_with_password(None) {
+///     plan.push(&entry?);
+/// }
+/// let selected = plan.selected();
+///
+/// let mut archive = Archive::read_header(fs::File::open("foo.pna")?)?;
+/// for (i, entry) in archive.entries_with_password(None).enumerate() {
+///     let entry = entry?;
+///     if selected.contains(&i) {
+///         println!("{}", entry.header().path());
+///     }
+/// }
+/// #    Ok(())
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct VersionPlan {
+    selector: VersionSelector,
+    paths: HashMap<EntryName, PathVersions>,
+    len: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+struct PathVersions {
+    count: usize,
+    selected: Option<(usize, bool)>,
+}
+
+impl VersionPlan {
+    /// Creates an empty plan for `selector`.
+    #[inline]
+    pub fn new(selector: VersionSelector) -> Self {
+        Self {
+            selector,
+            paths: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    /// Records the next entry of the archive.
+    #[inline]
+    pub fn push<T>(&mut self, entry: &NormalEntry<T>) {
+        let position = self.len;
+        self.len += 1;
+        let path = entry.header().path();
+        let versions = match self.paths.get_mut(path) {
+            Some(versions) => versions,
+            None => self.paths.entry(path.clone()).or_default(),
+        };
+        versions.count += 1;
+        if self.selector.accepts(entry, versions.count) {
+            let whiteout = entry.header().data_kind() == DataKind::Whiteout;
+            versions.selected = Some((position, whiteout));
+        }
+    }
+
+    /// Returns the positions of the selected entries, counting from 0 in the order they were
+    /// pushed.
+    ///
+    /// As with [`EntryVersions::select`], paths whose selected version is a
+    /// [`DataKind::Whiteout`] entry are left out.
+    #[inline]
+    pub fn selected(&self) -> HashSet<usize> {
+        self.paths
+            .values()
+            .filter_map(|it| it.selected)
+            .filter(|(_, whiteout)| !whiteout)
+            .map(|(position, _)| position)
+            .collect()
+    }
+}
+
+impl<R: Read> Archive<R> {
+    /// Reads the rest of the archive, including the entries in solid entries, and groups the
+    /// entries by path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an I/O error occurs while reading from the archive.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use libpna::{Archive, VersionSelector};
+    /// use std::fs;
+    /// # use std::io;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let file = fs::File::open("foo.pna")?;
+    /// let mut archive = Archive::read_header(file)?;
+    /// for entry in archive.entry_versions(None)?.select(VersionSelector::Latest) {
+    ///     println!("{}", entry.header().path());
+    /// }
+    /// #    Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn entry_versions(&mut self, password: Option<&str>) -> io::Result<EntryVersions> {
+        EntryVersions::from_entries(self.entries_with_password(password))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntryBuilder, WriteOptions};
+    use std::io::Write;
+    #[cfg(all(target_family = "wasm", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    fn file(name: &str, content: &[u8], mtime: u64) -> NormalEntry {
+        let mut builder = EntryBuilder::new_file(name.into(), WriteOptions::store()).unwrap();
+        builder.write_all(content).unwrap();
+        builder.modified(Duration::from_secs(mtime));
+        builder.build().unwrap()
+    }
+
+    fn archive() -> Vec<u8> {
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        writer.add_entry(file("a.txt", b"a1", 10)).unwrap();
+        writer.add_entry(file("b.txt", b"b1", 10)).unwrap();
+        writer.add_entry(file("a.txt", b"a2", 20)).unwrap();
+        let mut whiteout = EntryBuilder::new_whiteout("b.txt".into());
+        whiteout.modified(Duration::from_secs(20));
+        writer.add_entry(whiteout.build().unwrap()).unwrap();
+        writer.add_entry(file("a.txt", b"a3", 30)).unwrap();
+        writer.finalize().unwrap()
+    }
+
+    fn contents(entries: Vec<NormalEntry>) -> Vec<(String, Vec<u8>)> {
+        entries
+            .into_iter()
+            .map(|entry| {
+                let mut buf = Vec::new();
+                io::copy(
+                    &mut entry.reader(crate::ReadOptions::builder().build()).unwrap(),
+                    &mut buf,
+                )
+                .unwrap();
+                (entry.header().path().to_string(), buf)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn group_versions() {
+        let bytes = archive();
+        let mut archive = Archive::read_header(bytes.as_slice()).unwrap();
+        let versions = archive.entry_versions(None).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions.get(&"a.txt".into()).unwrap().len(), 3);
+        assert_eq!(versions.get(&"b.txt".into()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn select_versions() {
+        let bytes = archive();
+        let mut archive = Archive::read_header(bytes.as_slice()).unwrap();
+        let versions = archive.entry_versions(None).unwrap();
+        assert_eq!(
+            contents(versions.clone().select(VersionSelector::Latest)),
+            [("a.txt".into(), b"a3".to_vec())]
+        );
+        assert_eq!(
+            contents(
+                versions
+                    .clone()
+                    .select(VersionSelector::AsOf(Duration::from_secs(15)))
+            ),
+            [
+                ("a.txt".into(), b"a1".to_vec()),
+                ("b.txt".into(), b"b1".to_vec())
+            ]
+        );
+        assert_eq!(
+            contents(versions.clone().select(VersionSelector::Nth(2))),
+            [("a.txt".into(), b"a2".to_vec())]
+        );
+        assert!(versions.select(VersionSelector::Nth(0)).is_empty());
+    }
+
+    #[test]
+    fn select_versions_without_mtime() {
+        let mut writer = Archive::write_header(Vec::new()).unwrap();
+        writer.add_entry(file("a.txt", b"a1", 10)).unwrap();
+        let mut builder = EntryBuilder::new_file("a.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"a2").unwrap();
+        writer.add_entry(builder.build().unwrap()).unwrap();
+        let bytes = writer.finalize().unwrap();
+
+        let mut archive = Archive::read_header(bytes.as_slice()).unwrap();
+        let versions = archive.entry_versions(None).unwrap();
+        assert_eq!(
+            contents(versions.select(VersionSelector::AsOf(Duration::from_secs(15)))),
+            [("a.txt".into(), b"a1".to_vec())]
+        );
+    }
+
+    #[test]
+    fn plan_versions() {
+        let bytes = archive();
+        let plan = |selector| {
+            let mut plan = VersionPlan::new(selector);
+            let mut archive = Archive::read_header(bytes.as_slice()).unwrap();
+            for entry in archive.entries_with_password(None) {
+                plan.push(&entry.unwrap());
+            }
+            let mut selected = plan.selected().into_iter().collect::<Vec<_>>();
+            selected.sort_unstable();
+            selected
+        };
+        assert_eq!(plan(VersionSelector::Latest), [4]);
+        assert_eq!(plan(VersionSelector::AsOf(Duration::from_secs(15))), [0, 1]);
+        assert_eq!(plan(VersionSelector::Nth(2)), [2]);
+        assert!(plan(VersionSelector::Nth(0)).is_empty());
+    }
+}
@@ -4,16 +4,21 @@ use crate::{
     cipher::CipherWriter,
     compress::CompressionWriter,
     entry::{
-        get_writer, get_writer_context, Entry, EntryHeader, EntryName, EntryPart, Metadata,
-        NormalEntry, SealedEntryExt, SolidHeader, WriteCipher, WriteOption, WriteOptions,
+        get_seeded_writer_context, get_writer, get_writer_context, is_seeded, plain_writer_context,
+        write_key_derivation, Entry, EntryHeader, EntryName, EntryPart, EntryWriterContext,
+        Metadata, NormalEntry, SealedEntryExt, SolidHeader, WriteCipher, WriteOption, WriteOptions,
     },
     io::TryIntoInner,
+    progress::{Progress, ProgressObserver},
 };
 #[cfg(feature = "unstable-async")]
 use futures_io::AsyncWrite;
 #[cfg(feature = "unstable-async")]
 use futures_util::AsyncWriteExt;
-use std::io::{self, Write};
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
 
 /// Internal Writer type alias.
 pub(crate) type InternalDataWriter<W> = CompressionWriter<CipherWriter<W>>;
@@ -22,12 +27,14 @@ pub(crate) type InternalDataWriter<W> = CompressionWriter<CipherWriter<W>>;
 pub(crate) type InternalArchiveDataWriter<W> = InternalDataWriter<ChunkStreamWriter<W>>;
 
 /// Writer that compresses and encrypts according to the given options.
-pub struct EntryDataWriter<W: Write>(InternalArchiveDataWriter<W>);
+pub struct EntryDataWriter<W: Write>(InternalArchiveDataWriter<W>, Progress);
 
 impl<W: Write> Write for EntryDataWriter<W> {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        let len = self.0.write(buf)?;
+        self.1.bytes_written(len);
+        Ok(len)
     }
 
     #[inline]
@@ -38,12 +45,15 @@ impl<W: Write> Write for EntryDataWriter<W> {
 
 pub struct SolidArchiveEntryDataWriter<'w, W: Write>(
     InternalArchiveDataWriter<&'w mut InternalArchiveDataWriter<W>>,
+    Progress,
 );
 
 impl<W: Write> Write for SolidArchiveEntryDataWriter<'_, W> {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        let len = self.0.write(buf)?;
+        self.1.bytes_written(len);
+        Ok(len)
     }
 
     #[inline]
@@ -94,6 +104,33 @@ impl<W: Write> Archive<W> {
         Ok(Self::new(write, header))
     }
 
+    /// Sets the observer to report the progress of writing entries to, or removes it with
+    /// `None`.
+    ///
+    /// The observer is kept by the archive returned by [`Archive::split_to_next_archive`].
+    ///
+    /// # Examples
+    /// ```
+    /// use libpna::{Archive, ProgressObserver};
+    /// use std::sync::Arc;
+    /// # use std::io;
+    ///
+    /// struct Observer;
+    ///
+    /// impl ProgressObserver for Observer {}
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut archive = Archive::write_header(Vec::new())?;
+    /// archive.set_progress_observer(Some(Arc::new(Observer)));
+    /// archive.finalize()?;
+    /// #    Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn set_progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>) {
+        self.progress = Progress::new(observer);
+    }
+
     /// Write a regular file as normal entry into archive.
     ///
     /// # Errors
@@ -131,11 +168,20 @@ impl<W: Write> Archive<W> {
     where
         F: FnMut(&mut EntryDataWriter<&mut W>) -> io::Result<()>,
     {
-        write_file_entry(&mut self.inner, name, metadata, option, |w| {
-            let mut w = EntryDataWriter(w);
-            f(&mut w)?;
-            Ok(w.0)
-        })
+        let progress = &self.progress;
+        write_file_entry(
+            &mut self.inner,
+            &mut self.key_derivation,
+            name,
+            metadata,
+            option,
+            progress,
+            |w| {
+                let mut w = EntryDataWriter(w, progress.clone());
+                f(&mut w)?;
+                Ok(w.0)
+            },
+        )
     }
 
     /// Adds a new entry to the archive.
@@ -167,7 +213,20 @@ impl<W: Write> Archive<W> {
     /// ```
     #[inline]
     pub fn add_entry(&mut self, entry: impl Entry) -> io::Result<usize> {
-        entry.write_in(&mut self.inner)
+        if let Some(name) = entry.name() {
+            self.progress.entry_started(name);
+        }
+        let len = write_key_derivation(
+            &mut self.inner,
+            &mut self.key_derivation,
+            entry.key_derivation(),
+        )?;
+        let len = len + entry.write_in(&mut self.inner)?;
+        self.progress.bytes_written(len);
+        if let Some(name) = entry.name() {
+            self.progress.entry_finished(name);
+        }
+        Ok(len)
     }
 
     /// Adds a part of an entry to the archive.
@@ -207,8 +266,19 @@ impl<W: Write> Archive<W> {
     {
         let mut written_len = 0;
         for chunk in entry_part.0 {
+            if chunk.ty() == ChunkType::AKDF {
+                let key_derivation = String::from_utf8(chunk.data().into())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                written_len += write_key_derivation(
+                    &mut self.inner,
+                    &mut self.key_derivation,
+                    Some(&key_derivation),
+                )?;
+                continue;
+            }
             written_len += chunk.write_chunk_in(&mut self.inner)?;
         }
+        self.progress.bytes_written(written_len);
         Ok(written_len)
     }
 
@@ -247,8 +317,11 @@ impl<W: Write> Archive<W> {
         let next_archive_number = self.header.archive_number + 1;
         let header = ArchiveHeader::new(0, 0, next_archive_number);
         self.add_next_archive_marker()?;
+        let progress = self.progress.clone();
         self.finalize()?;
-        Archive::write_header_with(writer, header)
+        let mut archive = Archive::write_header_with(writer, header)?;
+        archive.progress = progress;
+        Ok(archive)
     }
 
     /// Write an end marker to finalize the archive.
@@ -313,9 +386,17 @@ impl<W: AsyncWrite + Unpin> Archive<W> {
     /// Returns an error if an I/O error occurs while writing a given entry.
     #[inline]
     pub async fn add_entry_async(&mut self, entry: impl Entry) -> io::Result<usize> {
+        if let Some(name) = entry.name() {
+            self.progress.entry_started(name);
+        }
         let mut bytes = Vec::new();
+        write_key_derivation(&mut bytes, &mut self.key_derivation, entry.key_derivation())?;
         entry.write_in(&mut bytes)?;
         self.inner.write_all(&bytes).await?;
+        self.progress.bytes_written(bytes.len());
+        if let Some(name) = entry.name() {
+            self.progress.entry_finished(name);
+        }
         Ok(bytes.len())
     }
 
@@ -379,13 +460,22 @@ impl<W: Write> Archive<W> {
             option.encryption(),
             option.cipher_mode(),
         );
-        let context = get_writer_context(option)?;
-
-        (ChunkType::SHED, header.to_bytes()).write_chunk_in(&mut self.inner)?;
-        if let Some(WriteCipher { context: c, .. }) = &context.cipher {
-            (ChunkType::PHSF, c.phsf.as_bytes()).write_chunk_in(&mut self.inner)?;
-            (ChunkType::SDAT, c.iv.as_slice()).write_chunk_in(&mut self.inner)?;
+        if is_seeded(&option) {
+            // The solid data is held back until its digest is known at finalization.
+            let writer = get_writer(
+                ChunkStreamWriter::buffered(ChunkType::SDAT, self.inner),
+                &plain_writer_context(),
+            )?;
+            return Ok(SolidArchive {
+                archive_header: self.header,
+                inner: writer,
+                seeded: Some((header, WriteOptions::from_option(option))),
+                entries_key_derivation: None,
+                progress: self.progress,
+            });
         }
+        let context = get_writer_context(option, b"")?;
+        write_solid_head(&mut self.inner, &mut self.key_derivation, &header, &context)?;
         self.inner.flush()?;
         let writer = get_writer(
             ChunkStreamWriter::new(ChunkType::SDAT, self.inner),
@@ -395,11 +485,39 @@ impl<W: Write> Archive<W> {
         Ok(SolidArchive {
             archive_header: self.header,
             inner: writer,
+            seeded: None,
+            entries_key_derivation: None,
+            progress: self.progress,
         })
     }
 }
 
 impl<W: Write> SolidArchive<W> {
+    /// Sets the observer to report the progress of writing entries to, or removes it with
+    /// `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libpna::{Archive, ProgressObserver, WriteOptions};
+    /// use std::sync::Arc;
+    /// # use std::io;
+    ///
+    /// struct Observer;
+    ///
+    /// impl ProgressObserver for Observer {}
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let mut archive = Archive::write_solid_header(Vec::new(), WriteOptions::store())?;
+    /// archive.set_progress_observer(Some(Arc::new(Observer)));
+    /// archive.finalize()?;
+    /// #    Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn set_progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>) {
+        self.progress = Progress::new(observer);
+    }
+
     /// Adds a new entry to the archive.
     ///
     /// # Arguments
@@ -432,7 +550,16 @@ impl<W: Write> SolidArchive<W> {
     where
         NormalEntry<T>: Entry,
     {
-        entry.write_in(&mut self.inner)
+        self.progress.entry_started(entry.header().path());
+        let len = write_key_derivation(
+            &mut self.inner,
+            &mut self.entries_key_derivation,
+            entry.key_derivation(),
+        )?;
+        let len = len + entry.write_in(&mut self.inner)?;
+        self.progress.bytes_written(len);
+        self.progress.entry_finished(entry.header().path());
+        Ok(len)
     }
 
     /// Write a regular file as solid entry into archive.
@@ -465,11 +592,20 @@ impl<W: Write> SolidArchive<W> {
         F: FnMut(&mut SolidArchiveEntryDataWriter<W>) -> io::Result<()>,
     {
         let option = WriteOptions::store();
-        write_file_entry(&mut self.inner, name, metadata, option, |w| {
-            let mut w = SolidArchiveEntryDataWriter(w);
-            f(&mut w)?;
-            Ok(w.0)
-        })
+        let progress = &self.progress;
+        write_file_entry(
+            &mut self.inner,
+            &mut None,
+            name,
+            metadata,
+            option,
+            progress,
+            |w| {
+                let mut w = SolidArchiveEntryDataWriter(w, progress.clone());
+                f(&mut w)?;
+                Ok(w.0)
+            },
+        )
     }
 
     /// Write an end marker to finalize the archive.
@@ -505,17 +641,34 @@ impl<W: Write> SolidArchive<W> {
     #[inline]
     fn finalize_solid_entry(mut self) -> io::Result<Archive<W>> {
         self.inner.flush()?;
-        let mut inner = self.inner.try_into_inner()?.try_into_inner()?.into_inner();
+        let writer = self.inner.try_into_inner()?.try_into_inner()?;
+        let mut inner = match self.seeded {
+            None => writer.into_inner(),
+            Some((header, option)) => {
+                let (data, mut inner) = writer.into_buffered();
+                let context = get_seeded_writer_context(option, b"", [&data])?;
+                write_solid_head(&mut inner, &mut None, &header, &context)?;
+                let mut writer =
+                    get_writer(ChunkStreamWriter::new(ChunkType::SDAT, inner), &context)?;
+                writer.write_all(&data)?;
+                writer.flush()?;
+                writer.try_into_inner()?.try_into_inner()?.into_inner()
+            }
+        };
         (ChunkType::SEND, []).write_chunk_in(&mut inner)?;
-        Ok(Archive::new(inner, self.archive_header))
+        let mut archive = Archive::new(inner, self.archive_header);
+        archive.progress = self.progress;
+        Ok(archive)
     }
 }
 
 pub(crate) fn write_file_entry<W, F>(
     inner: &mut W,
+    key_derivation: &mut Option<String>,
     name: EntryName,
     metadata: Metadata,
     option: impl WriteOption,
+    progress: &Progress,
     mut f: F,
 ) -> io::Result<()>
 where
@@ -528,6 +681,46 @@ where
         option.cipher_mode(),
         name,
     );
+    progress.entry_started(header.path());
+    let inner = if is_seeded(&option) {
+        // The data is held back until its digest is known.
+        let writer = ChunkStreamWriter::buffered(ChunkType::FDAT, inner);
+        let writer = get_writer(writer, &plain_writer_context())?;
+        let mut writer = f(writer)?;
+        writer.flush()?;
+        let (data, inner) = writer.try_into_inner()?.try_into_inner()?.into_buffered();
+        let context =
+            get_seeded_writer_context(option, header.path().as_str().as_bytes(), [&data])?;
+        write_file_head(inner, key_derivation, &header, &metadata, &context)?;
+        let mut writer = get_writer(ChunkStreamWriter::new(ChunkType::FDAT, inner), &context)?;
+        writer.write_all(&data)?;
+        writer.flush()?;
+        writer.try_into_inner()?.try_into_inner()?.into_inner()
+    } else {
+        let context = get_writer_context(option, header.path().as_str().as_bytes())?;
+        write_file_head(inner, key_derivation, &header, &metadata, &context)?;
+        let writer = ChunkStreamWriter::new(ChunkType::FDAT, inner);
+        let writer = get_writer(writer, &context)?;
+        let mut writer = f(writer)?;
+        writer.flush()?;
+        writer.try_into_inner()?.try_into_inner()?.into_inner()
+    };
+    (ChunkType::FEND, Vec::<u8>::new()).write_chunk_in(inner)?;
+    progress.entry_finished(header.path());
+    Ok(())
+}
+
+/// Writes the chunks of a file entry that precede its data.
+fn write_file_head<W: Write>(
+    inner: &mut W,
+    key_derivation: &mut Option<String>,
+    header: &EntryHeader,
+    metadata: &Metadata,
+    context: &EntryWriterContext,
+) -> io::Result<()> {
+    if let Some(WriteCipher { context: c, .. }) = &context.cipher {
+        write_key_derivation(inner, key_derivation, c.key_derivation.as_deref())?;
+    }
     (ChunkType::FHED, header.to_bytes()).write_chunk_in(inner)?;
     if let Some(c) = metadata.created {
         (ChunkType::cTIM, c.as_secs().to_be_bytes()).write_chunk_in(inner)?;
@@ -538,22 +731,31 @@ where
     if let Some(a) = metadata.accessed {
         (ChunkType::aTIM, a.as_secs().to_be_bytes()).write_chunk_in(inner)?;
     }
-    if let Some(p) = metadata.permission {
+    if let Some(p) = &metadata.permission {
         (ChunkType::fPRM, p.to_bytes()).write_chunk_in(inner)?;
     }
-    let context = get_writer_context(option)?;
     if let Some(WriteCipher { context: c, .. }) = &context.cipher {
         (ChunkType::PHSF, c.phsf.as_bytes()).write_chunk_in(inner)?;
         (ChunkType::FDAT, &c.iv[..]).write_chunk_in(inner)?;
     }
-    let inner = {
-        let writer = ChunkStreamWriter::new(ChunkType::FDAT, inner);
-        let writer = get_writer(writer, &context)?;
-        let mut writer = f(writer)?;
-        writer.flush()?;
-        writer.try_into_inner()?.try_into_inner()?.into_inner()
-    };
-    (ChunkType::FEND, Vec::<u8>::new()).write_chunk_in(inner)?;
+    Ok(())
+}
+
+/// Writes the chunks of a solid entry that precede its data.
+fn write_solid_head<W: Write>(
+    inner: &mut W,
+    key_derivation: &mut Option<String>,
+    header: &SolidHeader,
+    context: &EntryWriterContext,
+) -> io::Result<()> {
+    if let Some(WriteCipher { context: c, .. }) = &context.cipher {
+        write_key_derivation(inner, key_derivation, c.key_derivation.as_deref())?;
+    }
+    (ChunkType::SHED, header.to_bytes()).write_chunk_in(inner)?;
+    if let Some(WriteCipher { context: c, .. }) = &context.cipher {
+        (ChunkType::PHSF, c.phsf.as_bytes()).write_chunk_in(inner)?;
+        (ChunkType::SDAT, c.iv.as_slice()).write_chunk_in(inner)?;
+    }
     Ok(())
 }
 
@@ -631,6 +833,54 @@ mod tests {
         assert_eq!(&data[..], b"text");
     }
 
+    #[test]
+    fn seeded_write_file_entry() {
+        let option = WriteOptions::builder()
+            .encryption(crate::Encryption::Aes)
+            .hash_algorithm(crate::HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+            .password(Some("password"))
+            .random_seed(Some([1; 32]))
+            .build();
+        let write = |data: &[u8]| {
+            let mut writer = Archive::write_header(Vec::new()).unwrap();
+            writer
+                .write_file("text.txt".into(), Metadata::new(), &option, |writer| {
+                    writer.write_all(data)
+                })
+                .unwrap();
+            writer.finalize().unwrap()
+        };
+        let write_solid = |data: &[u8]| {
+            let mut writer = Archive::write_solid_header(Vec::new(), &option).unwrap();
+            writer
+                .write_file("text.txt".into(), Metadata::new(), |writer| {
+                    writer.write_all(data)
+                })
+                .unwrap();
+            writer.finalize().unwrap()
+        };
+        let read = |file: &[u8]| {
+            let mut reader = Archive::read_header(file).unwrap();
+            let entry = reader
+                .entries_with_password(Some("password"))
+                .next()
+                .unwrap()
+                .unwrap();
+            let mut data = Vec::new();
+            entry
+                .reader(ReadOptions::with_password(Some("password")))
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        };
+        for write in [&write as &dyn Fn(&[u8]) -> Vec<u8>, &write_solid] {
+            assert_eq!(write(b"text"), write(b"text"));
+            assert_ne!(write(b"text"), write(b"other"));
+            assert_eq!(read(&write(b"text")), b"text");
+        }
+    }
+
     #[cfg(feature = "unstable-async")]
     #[tokio::test]
     async fn encode_async() {
@@ -87,7 +87,16 @@ impl<R> From<R> for ChunkReader<R> {
     }
 }
 
-pub(crate) fn read_chunk<R: Read>(mut r: R) -> io::Result<RawChunk> {
+pub(crate) fn read_chunk<R: Read>(r: R) -> io::Result<RawChunk> {
+    let (chunk, crc_matched) = read_chunk_unchecked(r)?;
+    if !crc_matched {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Broken chunk"));
+    }
+    Ok(chunk)
+}
+
+/// Reads a chunk without failing on a CRC mismatch, returning whether the CRC matched.
+pub(crate) fn read_chunk_unchecked<R: Read>(mut r: R) -> io::Result<(RawChunk, bool)> {
     let mut crc_hasher = Crc32::new();
 
     // read chunk length
@@ -112,15 +121,16 @@ pub(crate) fn read_chunk<R: Read>(mut r: R) -> io::Result<RawChunk> {
     r.read_exact(&mut crc)?;
     let crc = u32::from_be_bytes(crc);
 
-    if crc != crc_hasher.finalize() {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Broken chunk"));
-    }
-    Ok(RawChunk {
-        length,
-        ty: ChunkType(ty),
-        data,
-        crc,
-    })
+    let crc_matched = crc == crc_hasher.finalize();
+    Ok((
+        RawChunk {
+            length,
+            ty: ChunkType(ty),
+            data,
+            crc,
+        },
+        crc_matched,
+    ))
 }
 
 pub(crate) fn read_chunk_from_slice(bytes: &[u8]) -> io::Result<(RawChunk<&[u8]>, &[u8])> {
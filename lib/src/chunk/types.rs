@@ -42,6 +42,8 @@ impl ChunkType {
     pub const AEND: ChunkType = ChunkType(*b"AEND");
     /// Archive next part marker
     pub const ANXT: ChunkType = ChunkType(*b"ANXT");
+    /// Archive-level key derivation parameters
+    pub const AKDF: ChunkType = ChunkType(*b"AKDF");
     /// Entry header
     pub const FHED: ChunkType = ChunkType(*b"FHED");
     /// Password hash string format
@@ -45,6 +45,7 @@ impl<W: AsyncWrite + Unpin> ChunkWriter<W> {
 pub(crate) struct ChunkStreamWriter<W> {
     ty: ChunkType,
     w: ChunkWriter<W>,
+    buffer: Option<Vec<u8>>,
 }
 
 impl<W> ChunkStreamWriter<W> {
@@ -53,6 +54,17 @@ impl<W> ChunkStreamWriter<W> {
         Self {
             ty,
             w: ChunkWriter::new(inner),
+            buffer: None,
+        }
+    }
+
+    /// Creates a writer that holds the written data back instead of writing chunks, to be
+    /// taken with [ChunkStreamWriter::into_buffered].
+    #[inline]
+    pub(crate) fn buffered(ty: ChunkType, inner: W) -> Self {
+        Self {
+            buffer: Some(Vec::new()),
+            ..Self::new(ty, inner)
         }
     }
 
@@ -60,12 +72,22 @@ impl<W> ChunkStreamWriter<W> {
     pub(crate) fn into_inner(self) -> W {
         self.w.w
     }
+
+    /// Returns the data held back by a [ChunkStreamWriter::buffered] writer and the inner writer.
+    #[inline]
+    pub(crate) fn into_buffered(self) -> (Vec<u8>, W) {
+        (self.buffer.unwrap_or_default(), self.w.w)
+    }
 }
 
 impl<W: Write> Write for ChunkStreamWriter<W> {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.w.write_chunk((self.ty, buf))?;
+        if let Some(buffer) = &mut self.buffer {
+            buffer.extend_from_slice(buf);
+        } else {
+            self.w.write_chunk((self.ty, buf))?;
+        }
         Ok(buf.len())
     }
 
@@ -22,6 +22,8 @@ use crate::{
     chunk::{
         chunk_data_split, Chunk, ChunkExt, ChunkReader, ChunkType, RawChunk, MIN_CHUNK_BYTES_SIZE,
     },
+    hash,
+    progress::Progress,
     util::slice::skip_while,
 };
 use std::{
@@ -36,13 +38,71 @@ mod private {
     pub trait SealedEntryExt {
         fn into_chunks(self) -> Vec<RawChunk>;
         fn write_in<W: Write>(&self, writer: &mut W) -> io::Result<usize>;
+        /// PHSF of the archive-level key that the entry key is derived from.
+        fn key_derivation(&self) -> Option<&str>;
+        /// Name of the entry, reported to progress observers.
+        fn name(&self) -> Option<&EntryName>;
     }
 }
 
+/// Writes an `AKDF` chunk unless the same one was the last written.
+#[inline]
+pub(crate) fn write_key_derivation<W: Write>(
+    writer: &mut W,
+    written: &mut Option<String>,
+    key_derivation: Option<&str>,
+) -> io::Result<usize> {
+    match key_derivation {
+        Some(key_derivation) if written.as_deref() != Some(key_derivation) => {
+            let len = (ChunkType::AKDF, key_derivation.as_bytes()).write_chunk_in(writer)?;
+            *written = Some(key_derivation.into());
+            Ok(len)
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Converts the given raw entry into a [ReadEntry], consuming the `AKDF` chunks preceding it.
+///
+/// `key_derivation` holds the last `AKDF` chunk seen in the stream.
+#[inline]
+pub(crate) fn read_entry_with_key_derivation<T>(
+    mut entry: RawEntry<T>,
+    key_derivation: &mut Option<String>,
+) -> io::Result<ReadEntry<T>>
+where
+    RawChunk<T>: Chunk,
+{
+    let len = entry
+        .0
+        .iter()
+        .take_while(|it| it.ty == ChunkType::AKDF)
+        .count();
+    for chunk in entry.0.drain(..len) {
+        *key_derivation = Some(
+            String::from_utf8(chunk.data().into())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+    }
+    let mut entry = ReadEntry::try_from(entry)?;
+    let (phsf, entry_key_derivation) = match &mut entry {
+        ReadEntry::Solid(s) => (&s.phsf, &mut s.key_derivation),
+        ReadEntry::Normal(n) => (&n.phsf, &mut n.key_derivation),
+    };
+    if phsf
+        .as_deref()
+        .is_some_and(hash::is_derived_from_archive_key)
+    {
+        entry_key_derivation.clone_from(key_derivation);
+    }
+    Ok(entry)
+}
+
 /// A trait representing an entry in a PNA archive.
 pub trait Entry: SealedEntryExt {}
 
-/// Chunks from `FHED` to `FEND`, containing `FHED` and `FEND`
+/// Chunks from `FHED` to `FEND`, containing `FHED` and `FEND`,
+/// possibly preceded by the `AKDF` chunks read before them.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub(crate) struct RawEntry<T = Vec<u8>>(pub(crate) Vec<RawChunk<T>>);
 
@@ -67,6 +127,16 @@ where
         self.0.into_iter().map(Into::into).collect()
     }
 
+    #[inline]
+    fn key_derivation(&self) -> Option<&str> {
+        None
+    }
+
+    #[inline]
+    fn name(&self) -> Option<&EntryName> {
+        None
+    }
+
     #[inline]
     fn write_in<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
         chunks_write_in(self.0.iter(), writer)
@@ -104,12 +174,24 @@ impl<'a> From<RawEntry<&'a [u8]>> for RawEntry<Cow<'a, [u8]>> {
 }
 
 /// Reader for Entry data.
-pub struct EntryDataReader<'r>(EntryReader<crate::io::FlattenReader<'r>>);
+pub struct EntryDataReader<'r> {
+    reader: EntryReader<crate::io::FlattenReader<'r>>,
+    progress: Progress,
+    // the name of the entry until the end of its data is reported
+    unfinished: Option<&'r EntryName>,
+}
 
 impl Read for EntryDataReader<'_> {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+        let len = self.reader.read(buf)?;
+        self.progress.bytes_read(len);
+        if len == 0 && !buf.is_empty() {
+            if let Some(name) = self.unfinished.take() {
+                self.progress.entry_finished(name);
+            }
+        }
+        Ok(len)
     }
 }
 
@@ -158,6 +240,22 @@ where
             ReadEntry::Solid(s) => s.write_in(writer),
         }
     }
+
+    #[inline]
+    fn key_derivation(&self) -> Option<&str> {
+        match self {
+            ReadEntry::Normal(r) => r.key_derivation(),
+            ReadEntry::Solid(s) => s.key_derivation(),
+        }
+    }
+
+    #[inline]
+    fn name(&self) -> Option<&EntryName> {
+        match self {
+            ReadEntry::Normal(r) => r.name(),
+            ReadEntry::Solid(s) => s.name(),
+        }
+    }
 }
 
 impl<T> Entry for ReadEntry<T> where ReadEntry<T>: SealedEntryExt {}
@@ -236,14 +334,17 @@ impl<'a> From<ReadEntry<&'a [u8]>> for ReadEntry<Cow<'a, [u8]>> {
     }
 }
 
-pub(crate) struct EntryIterator<'s>(EntryReader<crate::io::FlattenReader<'s>>);
+pub(crate) struct EntryIterator<'s> {
+    reader: EntryReader<crate::io::FlattenReader<'s>>,
+    key_derivation: Option<String>,
+}
 
 impl Iterator for EntryIterator<'_> {
     type Item = io::Result<NormalEntry>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let mut chunk_reader = ChunkReader::from(&mut self.0);
+        let mut chunk_reader = ChunkReader::from(&mut self.reader);
         let mut chunks = Vec::new();
         loop {
             let chunk = chunk_reader.read_chunk();
@@ -265,7 +366,21 @@ impl Iterator for EntryIterator<'_> {
                 Err(e) => return Some(Err(e)),
             }
         }
-        Some(RawEntry(chunks).try_into())
+        Some(
+            read_entry_with_key_derivation(RawEntry(chunks), &mut self.key_derivation).and_then(
+                |entry| match entry {
+                    ReadEntry::Normal(entry) => Ok(entry),
+                    ReadEntry::Solid(_) => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Excepted {} chunk, but {} chunk was found",
+                            ChunkType::FHED,
+                            ChunkType::SHED
+                        ),
+                    )),
+                },
+            ),
+        )
     }
 }
 
@@ -279,6 +394,7 @@ impl Iterator for EntryIterator<'_> {
 pub struct SolidEntry<T = Vec<u8>> {
     header: SolidHeader,
     phsf: Option<String>,
+    key_derivation: Option<String>,
     data: Vec<T>,
     extra: Vec<RawChunk<T>>,
 }
@@ -313,6 +429,12 @@ where
 {
     fn into_chunks(self) -> Vec<RawChunk> {
         let mut chunks = vec![];
+        if let Some(key_derivation) = self.key_derivation {
+            chunks.push(RawChunk::from_data(
+                ChunkType::AKDF,
+                key_derivation.into_bytes(),
+            ));
+        }
         chunks.push(RawChunk::from_data(ChunkType::SHED, self.header.to_bytes()));
         chunks.extend(self.extra.into_iter().map(Into::into));
 
@@ -330,6 +452,16 @@ where
     fn write_in<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
         self.chunks_write_in(writer)
     }
+
+    #[inline]
+    fn key_derivation(&self) -> Option<&str> {
+        self.key_derivation.as_deref()
+    }
+
+    #[inline]
+    fn name(&self) -> Option<&EntryName> {
+        None
+    }
 }
 
 impl<T> Entry for SolidEntry<T> where SolidEntry<T>: SealedEntryExt {}
@@ -387,16 +519,156 @@ impl<T: AsRef<[u8]>> SolidEntry<T> {
         &self,
         password: Option<&str>,
     ) -> io::Result<impl Iterator<Item = io::Result<NormalEntry>> + '_> {
+        self.entries_with_password(password, &hash::KeyCache::default())
+    }
+
+    /// Returns an iterator over the entries in the [SolidEntry], decrypted with the first of
+    /// the candidate passwords of `option` that matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an I/O error occurs while reading from the [SolidEntry],
+    /// or if none of the candidate passwords of `option` matches this entry.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libpna::{Archive, ReadEntry, ReadOptions};
+    /// use std::fs;
+    /// # use std::io;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let file = fs::File::open("foo.pna")?;
+    /// let mut archive = Archive::read_header(file)?;
+    /// let read_options = ReadOptions::with_passwords(["first", "second"]);
+    /// for entry in archive.entries() {
+    ///     if let ReadEntry::Solid(solid_entry) = entry? {
+    ///         for entry in solid_entry.entries_with_options(&read_options)? {
+    ///             let entry = entry?;
+    ///             // fill your code
+    ///         }
+    ///     }
+    /// }
+    /// #    Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn entries_with_options(
+        &self,
+        option: impl ReadOption,
+    ) -> io::Result<impl Iterator<Item = io::Result<NormalEntry>> + '_> {
+        let key_cache = option.key_cache().cloned().unwrap_or_default();
+        let password = self.find_password(&option.passwords(), &key_cache)?;
+        self.entries_with_password(password, &key_cache)
+    }
+
+    /// Returns the first of the candidate `passwords` that decrypts this entry, which is
+    /// needed to encrypt its entries again with the same password.
+    ///
+    /// A single candidate is returned as it is, and none if this entry is not encrypted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if none of the candidate `passwords` matches this entry.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use libpna::{Archive, ReadEntry};
+    /// use std::fs;
+    /// # use std::io;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let file = fs::File::open("foo.pna")?;
+    /// let mut archive = Archive::read_header(file)?;
+    /// for entry in archive.entries() {
+    ///     if let ReadEntry::Solid(solid_entry) = entry? {
+    ///         let password = solid_entry.matching_password(&["first", "second"])?;
+    ///         for entry in solid_entry.entries(password)? {
+    ///             let entry = entry?;
+    ///             // fill your code
+    ///         }
+    ///     }
+    /// }
+    /// #    Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn matching_password<'p>(&self, passwords: &[&'p str]) -> io::Result<Option<&'p str>> {
+        self.find_password(passwords, &hash::KeyCache::default())
+    }
+
+    fn find_password<'p>(
+        &self,
+        passwords: &[&'p str],
+        key_cache: &hash::KeyCache,
+    ) -> io::Result<Option<&'p str>> {
+        if self.header.encryption == Encryption::No || passwords.len() < 2 {
+            return Ok(passwords.first().copied());
+        }
+        passwords
+            .iter()
+            .find(|password| self.check_password(password, key_cache).is_ok())
+            .map(|password| Some(*password))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No matching password"))
+    }
+
+    /// Checks that `password` decrypts this entry, by the key check of the archive-level key
+    /// if there is one, or else by reading the first chunk of the contained entries, which has
+    /// to be of a known type and to match its CRC. An entry that contains no entries is
+    /// decrypted with any password that passes the padding check.
+    fn check_password(&self, password: &str, key_cache: &hash::KeyCache) -> io::Result<()> {
+        let mut entries = self.entries_with_password(Some(password), key_cache)?;
+        if self
+            .key_derivation
+            .as_deref()
+            .is_some_and(hash::has_key_check)
+        {
+            return Ok(());
+        }
+        // Checks the chunk type first so that a wrong key does not allocate a chunk of a
+        // random length.
+        let mut head = Vec::with_capacity(8);
+        (&mut entries.reader).take(8).read_to_end(&mut head)?;
+        if head.is_empty() {
+            return Ok(());
+        }
+        if head.len() < 8
+            || ![ChunkType::FHED, ChunkType::AKDF]
+                .iter()
+                .any(|ty| ty.0 == head[4..])
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unexpected chunk in solid entry",
+            ));
+        }
+        self.entries_with_password(Some(password), key_cache)?
+            .next()
+            .transpose()
+            .map(|_| ())
+    }
+
+    fn entries_with_password(
+        &self,
+        password: Option<&str>,
+        key_cache: &hash::KeyCache,
+    ) -> io::Result<EntryIterator<'_>> {
         let reader = decrypt_reader(
             crate::io::FlattenReader::new(self.data.iter().map(|it| it.as_ref()).collect()),
             self.header.encryption,
             self.header.cipher_mode,
             self.phsf.as_deref(),
+            self.key_derivation.as_deref(),
             password.map(|it| it.as_bytes()),
+            Some(key_cache),
         )?;
         let reader = decompress_reader(reader, self.header.compression)?;
 
-        Ok(EntryIterator(EntryReader(reader)))
+        Ok(EntryIterator {
+            reader: EntryReader(reader),
+            key_derivation: None,
+        })
     }
 }
 
@@ -406,6 +678,7 @@ impl<'a> From<SolidEntry<Cow<'a, [u8]>>> for SolidEntry<Vec<u8>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             data: value.data.into_iter().map(Into::into).collect(),
             extra: value.extra.into_iter().map(Into::into).collect(),
         }
@@ -418,6 +691,7 @@ impl<'a> From<SolidEntry<&'a [u8]>> for SolidEntry<Vec<u8>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             data: value.data.into_iter().map(Into::into).collect(),
             extra: value.extra.into_iter().map(Into::into).collect(),
         }
@@ -430,6 +704,7 @@ impl<'a> From<SolidEntry<&'a [u8]>> for SolidEntry<Cow<'a, [u8]>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             data: value.data.into_iter().map(Into::into).collect(),
             extra: value.extra.into_iter().map(Into::into).collect(),
         }
@@ -442,6 +717,7 @@ impl From<SolidEntry<Vec<u8>>> for SolidEntry<Cow<'_, [u8]>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             data: value.data.into_iter().map(Into::into).collect(),
             extra: value.extra.into_iter().map(Into::into).collect(),
         }
@@ -494,6 +770,7 @@ where
         Ok(Self {
             header,
             phsf,
+            key_derivation: None,
             data,
             extra,
         })
@@ -509,6 +786,7 @@ where
 pub struct NormalEntry<T = Vec<u8>> {
     pub(crate) header: EntryHeader,
     pub(crate) phsf: Option<String>,
+    pub(crate) key_derivation: Option<String>,
     pub(crate) extra: Vec<RawChunk<T>>,
     pub(crate) data: Vec<T>,
     pub(crate) metadata: Metadata,
@@ -596,6 +874,7 @@ where
         Ok(Self {
             header,
             phsf,
+            key_derivation: None,
             extra,
             metadata: Metadata {
                 raw_file_size: size,
@@ -694,6 +973,12 @@ where
             permission,
         } = self.metadata;
         let mut vec = Vec::new();
+        if let Some(key_derivation) = self.key_derivation {
+            vec.push(RawChunk::from_data(
+                ChunkType::AKDF,
+                key_derivation.into_bytes(),
+            ));
+        }
         vec.push(RawChunk::from_data(ChunkType::FHED, self.header.to_bytes()));
         vec.extend(self.extra.into_iter().map(Into::into));
         if let Some(raw_file_size) = raw_file_size {
@@ -759,6 +1044,16 @@ where
     fn write_in<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
         self.chunks_write_in(writer)
     }
+
+    #[inline]
+    fn key_derivation(&self) -> Option<&str> {
+        self.key_derivation.as_deref()
+    }
+
+    #[inline]
+    fn name(&self) -> Option<&EntryName> {
+        Some(self.header.path())
+    }
 }
 
 impl<T> Entry for NormalEntry<T> where NormalEntry<T>: SealedEntryExt {}
@@ -858,7 +1153,8 @@ impl<T: AsRef<[u8]>> NormalEntry<T> {
     ///
     /// # Errors
     ///
-    /// Returns an error if an I/O error occurs while reading from the reader.
+    /// Returns an error if an I/O error occurs while reading from the reader,
+    /// or if none of the candidate passwords of `option` matches this entry.
     ///
     /// # Examples
     ///
@@ -881,6 +1177,117 @@ impl<T: AsRef<[u8]>> NormalEntry<T> {
     /// ```
     #[inline]
     pub fn reader(&self, option: impl ReadOption) -> io::Result<EntryDataReader<'_>> {
+        let mut reader = self.reader_with_passwords(&option)?;
+        if let Some(progress) = option.progress() {
+            progress.entry_started(self.header.path());
+            reader.progress = progress.clone();
+            reader.unfinished = Some(self.header.path());
+        }
+        Ok(reader)
+    }
+
+    fn reader_with_passwords(&self, option: &impl ReadOption) -> io::Result<EntryDataReader<'_>> {
+        let passwords = option.passwords();
+        let key_cache = option.key_cache().cloned().unwrap_or_default();
+        if self.header.encryption == Encryption::No
+            || passwords.len() < 2
+            || !self.is_password_verifiable()
+        {
+            return self.reader_with_password(passwords.first().copied(), &key_cache);
+        }
+        for password in passwords {
+            if self.check_password(password, &key_cache).is_ok() {
+                return self.reader_with_password(Some(password), &key_cache);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No matching password",
+        ))
+    }
+
+    /// Returns `false` if any password decrypts this entry without an error, which is the case
+    /// for data that is neither compressed nor padded and has no key check.
+    fn is_password_verifiable(&self) -> bool {
+        self.key_derivation
+            .as_deref()
+            .is_some_and(hash::has_key_check)
+            || self.header.compression != Compression::No
+            || self.header.cipher_mode != CipherMode::CTR
+    }
+
+    /// Checks that `password` decrypts this entry, by the key check of the archive-level key
+    /// if there is one, or else by the padding of the last block and by decompressing the
+    /// beginning of the data, without decoding all of it.
+    fn check_password(&self, password: &str, key_cache: &hash::KeyCache) -> io::Result<()> {
+        /// Length of the decompressed data that is decoded to check a password.
+        const CHECK_LEN: u64 = 64 * 1024;
+        let reader = self.reader_with_password(Some(password), key_cache)?;
+        if self
+            .key_derivation
+            .as_deref()
+            .is_some_and(hash::has_key_check)
+        {
+            return Ok(());
+        }
+        if self.header.cipher_mode == CipherMode::CBC {
+            self.check_padding(password, key_cache)?;
+        }
+        if self.header.compression == Compression::No {
+            return Ok(());
+        }
+        let size = io::copy(&mut reader.take(CHECK_LEN), &mut io::sink())?;
+        match self.metadata.raw_file_size {
+            Some(expected) if size < CHECK_LEN && expected != u128::from(size) => Err(
+                io::Error::new(io::ErrorKind::InvalidData, "Decoded size does not match"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decrypts only the last block of the data, with the block before it as the IV, which
+    /// fails if its padding is invalid or if it does not leave as many bytes as recorded.
+    fn check_padding(&self, password: &str, key_cache: &hash::KeyCache) -> io::Result<()> {
+        /// Block size of both AES and Camellia.
+        const BLOCK_SIZE: usize = 16;
+        let mut tail = Vec::with_capacity(BLOCK_SIZE * 2);
+        for chunk in self.data.iter().rev() {
+            let chunk = chunk.as_ref();
+            let len = chunk.len().min(tail.capacity() - tail.len());
+            tail.splice(0..0, chunk[chunk.len() - len..].iter().copied());
+            if tail.len() == tail.capacity() {
+                break;
+            }
+        }
+        let mut reader = decrypt_reader(
+            tail.as_slice(),
+            self.header.encryption,
+            self.header.cipher_mode,
+            self.phsf.as_deref(),
+            self.key_derivation.as_deref(),
+            Some(password.as_bytes()),
+            Some(key_cache),
+        )?;
+        let last = io::copy(&mut reader, &mut io::sink())?;
+        let len = self.data.iter().map(|it| it.as_ref().len()).sum::<usize>();
+        // The data starts with the IV, which is not part of the decrypted data.
+        let size = (len.saturating_sub(BLOCK_SIZE * 2) as u128) + u128::from(last);
+        match self.metadata.raw_file_size {
+            Some(expected) if self.header.compression == Compression::No && expected != size => {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Decoded size does not match",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn reader_with_password(
+        &self,
+        password: Option<&str>,
+        key_cache: &hash::KeyCache,
+    ) -> io::Result<EntryDataReader<'_>> {
         let raw_data_reader =
             crate::io::FlattenReader::new(self.data.iter().map(|it| it.as_ref()).collect());
         let decrypt_reader = decrypt_reader(
@@ -888,10 +1295,16 @@ impl<T: AsRef<[u8]>> NormalEntry<T> {
             self.header.encryption,
             self.header.cipher_mode,
             self.phsf.as_deref(),
-            option.password().map(|it| it.as_bytes()),
+            self.key_derivation.as_deref(),
+            password.map(|it| it.as_bytes()),
+            Some(key_cache),
         )?;
         let reader = decompress_reader(decrypt_reader, self.header.compression)?;
-        Ok(EntryDataReader(EntryReader(reader)))
+        Ok(EntryDataReader {
+            reader: EntryReader(reader),
+            progress: Progress::none(),
+            unfinished: None,
+        })
     }
 }
 
@@ -901,6 +1314,7 @@ impl<'a> From<NormalEntry<Cow<'a, [u8]>>> for NormalEntry<Vec<u8>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             extra: value.extra.into_iter().map(Into::into).collect(),
             data: value.data.into_iter().map(Into::into).collect(),
             metadata: value.metadata,
@@ -915,6 +1329,7 @@ impl<'a> From<NormalEntry<&'a [u8]>> for NormalEntry<Vec<u8>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             extra: value.extra.into_iter().map(Into::into).collect(),
             data: value.data.into_iter().map(Into::into).collect(),
             metadata: value.metadata,
@@ -929,6 +1344,7 @@ impl From<NormalEntry<Vec<u8>>> for NormalEntry<Cow<'_, [u8]>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             extra: value.extra.into_iter().map(Into::into).collect(),
             data: value.data.into_iter().map(Into::into).collect(),
             metadata: value.metadata,
@@ -943,6 +1359,7 @@ impl<'a> From<NormalEntry<&'a [u8]>> for NormalEntry<Cow<'a, [u8]>> {
         Self {
             header: value.header,
             phsf: value.phsf,
+            key_derivation: value.key_derivation,
             extra: value.extra.into_iter().map(Into::into).collect(),
             data: value.data.into_iter().map(Into::into).collect(),
             metadata: value.metadata,
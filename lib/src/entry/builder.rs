@@ -4,11 +4,13 @@ use crate::{
     cipher::CipherWriter,
     compress::CompressionWriter,
     entry::{
-        get_writer, get_writer_context, private::SealedEntryExt, DataKind, Entry, EntryHeader,
-        EntryName, EntryReference, ExtendedAttribute, Metadata, NormalEntry, Permission,
-        SolidEntry, SolidHeader, WriteCipher, WriteOption, WriteOptions,
+        get_seeded_writer_context, get_writer, get_writer_context, is_seeded, plain_writer_context,
+        private::SealedEntryExt, write_key_derivation, DataKind, Entry, EntryHeader, EntryName,
+        EntryReference, ExtendedAttribute, Metadata, NormalEntry, Permission, SolidEntry,
+        SolidHeader, WriteCipher, WriteOption, WriteOptions,
     },
     io::{FlattenWriter, TryIntoInner},
+    progress::Progress,
 };
 
 #[cfg(feature = "unstable-async")]
@@ -27,8 +29,11 @@ use std::{
 pub struct EntryBuilder {
     header: EntryHeader,
     phsf: Option<String>,
+    key_derivation: Option<String>,
     iv: Option<Vec<u8>>,
     data: Option<CompressionWriter<CipherWriter<FlattenWriter<MAX_CHUNK_DATA_LENGTH>>>>,
+    // options of the data buffered in plain until it is finished
+    seeded: Option<WriteOptions>,
     created: Option<Duration>,
     last_modified: Option<Duration>,
     accessed: Option<Duration>,
@@ -44,8 +49,10 @@ impl EntryBuilder {
         Self {
             header,
             phsf: None,
+            key_derivation: None,
             iv: None,
             data: None,
+            seeded: None,
             created: None,
             last_modified: None,
             accessed: None,
@@ -71,6 +78,23 @@ impl EntryBuilder {
         Self::new(EntryHeader::for_dir(name))
     }
 
+    /// Creates a new deletion marker (whiteout) with the given name.
+    ///
+    /// A whiteout has no data and records that the path was removed since the
+    /// archive it is based on, such as the previous archive of an incremental backup.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the removed entry.
+    ///
+    /// # Returns
+    ///
+    /// A new [EntryBuilder].
+    #[inline]
+    pub const fn new_whiteout(name: EntryName) -> Self {
+        Self::new(EntryHeader::for_whiteout(name))
+    }
+
     /// Creates a new file with the given name and write options.
     ///
     /// # Arguments
@@ -93,16 +117,23 @@ impl EntryBuilder {
             option.cipher_mode(),
             name,
         );
-        let context = get_writer_context(option)?;
+        let seeded = is_seeded(&option).then(|| WriteOptions::from_option(&option));
+        let context = if seeded.is_some() {
+            plain_writer_context()
+        } else {
+            get_writer_context(option, header.path().as_str().as_bytes())?
+        };
         let writer = get_writer(FlattenWriter::new(), &context)?;
-        let (iv, phsf) = match context.cipher {
-            None => (None, None),
-            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf)),
+        let (iv, phsf, key_derivation) = match context.cipher {
+            None => (None, None, None),
+            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf), c.key_derivation),
         };
         Ok(Self {
             data: Some(writer),
+            seeded,
             iv,
             phsf,
+            key_derivation,
             ..Self::new(header)
         })
     }
@@ -136,17 +167,18 @@ impl EntryBuilder {
     #[inline]
     pub fn new_symbolic_link(name: EntryName, source: EntryReference) -> io::Result<Self> {
         let option = WriteOptions::store();
-        let context = get_writer_context(option)?;
+        let context = get_writer_context(option, name.as_str().as_bytes())?;
         let mut writer = get_writer(FlattenWriter::new(), &context)?;
         writer.write_all(source.as_bytes())?;
-        let (iv, phsf) = match context.cipher {
-            None => (None, None),
-            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf)),
+        let (iv, phsf, key_derivation) = match context.cipher {
+            None => (None, None, None),
+            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf), c.key_derivation),
         };
         Ok(Self {
             data: Some(writer),
             iv,
             phsf,
+            key_derivation,
             ..Self::new(EntryHeader::for_symbolic_link(name))
         })
     }
@@ -180,17 +212,18 @@ impl EntryBuilder {
     #[inline]
     pub fn new_hard_link(name: EntryName, source: EntryReference) -> io::Result<Self> {
         let option = WriteOptions::store();
-        let context = get_writer_context(option)?;
+        let context = get_writer_context(option, name.as_str().as_bytes())?;
         let mut writer = get_writer(FlattenWriter::new(), &context)?;
         writer.write_all(source.as_bytes())?;
-        let (iv, phsf) = match context.cipher {
-            None => (None, None),
-            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf)),
+        let (iv, phsf, key_derivation) = match context.cipher {
+            None => (None, None, None),
+            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf), c.key_derivation),
         };
         Ok(Self {
             data: Some(writer),
             iv,
             phsf,
+            key_derivation,
             ..Self::new(EntryHeader::for_hard_link(name))
         })
     }
@@ -317,7 +350,19 @@ impl EntryBuilder {
         } else {
             Vec::new()
         };
-        if let Some(iv) = self.iv {
+        let (iv, phsf, key_derivation) = match self.seeded {
+            Some(option) => {
+                let label = self.header.path().as_str().as_bytes();
+                match seal_seeded(option, label, &mut data)? {
+                    None => (None, None, None),
+                    Some(WriteCipher { context: c, .. }) => {
+                        (Some(c.iv), Some(c.phsf), c.key_derivation)
+                    }
+                }
+            }
+            None => (self.iv, self.phsf, self.key_derivation),
+        };
+        if let Some(iv) = iv {
             data.insert(0, iv);
         }
         let metadata = Metadata {
@@ -333,7 +378,8 @@ impl EntryBuilder {
         };
         Ok(NormalEntry {
             header: self.header,
-            phsf: self.phsf,
+            phsf,
+            key_derivation,
             extra: self.extra_chunks,
             data,
             metadata,
@@ -402,9 +448,13 @@ impl Write for SolidEntryDataWriter<'_> {
 pub struct SolidEntryBuilder {
     header: SolidHeader,
     phsf: Option<String>,
+    key_derivation: Option<String>,
     iv: Option<Vec<u8>>,
     data: CompressionWriter<CipherWriter<FlattenWriter<MAX_CHUNK_DATA_LENGTH>>>,
+    // options of the data buffered in plain until it is finished
+    seeded: Option<WriteOptions>,
     extra: Vec<RawChunk>,
+    entries_key_derivation: Option<String>,
 }
 
 impl SolidEntryBuilder {
@@ -428,18 +478,26 @@ impl SolidEntryBuilder {
             option.encryption(),
             option.cipher_mode(),
         );
-        let context = get_writer_context(option)?;
+        let seeded = is_seeded(&option).then(|| WriteOptions::from_option(&option));
+        let context = if seeded.is_some() {
+            plain_writer_context()
+        } else {
+            get_writer_context(option, b"")?
+        };
         let writer = get_writer(FlattenWriter::new(), &context)?;
-        let (iv, phsf) = match context.cipher {
-            None => (None, None),
-            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf)),
+        let (iv, phsf, key_derivation) = match context.cipher {
+            None => (None, None, None),
+            Some(WriteCipher { context: c, .. }) => (Some(c.iv), Some(c.phsf), c.key_derivation),
         };
         Ok(Self {
             header,
             iv,
             phsf,
+            key_derivation,
             data: writer,
+            seeded,
             extra: Vec::new(),
+            entries_key_derivation: None,
         })
     }
 
@@ -478,7 +536,12 @@ impl SolidEntryBuilder {
     where
         NormalEntry<T>: Entry,
     {
-        entry.write_in(&mut self.data)
+        let len = write_key_derivation(
+            &mut self.data,
+            &mut self.entries_key_derivation,
+            entry.key_derivation(),
+        )?;
+        Ok(len + entry.write_in(&mut self.data)?)
     }
 
     /// Write a regular file to the solid entry.
@@ -511,11 +574,19 @@ impl SolidEntryBuilder {
         F: FnMut(&mut SolidEntryDataWriter) -> io::Result<()>,
     {
         let option = WriteOptions::store();
-        write_file_entry(&mut self.data, name, metadata, option, |w| {
-            let mut writer = SolidEntryDataWriter(w);
-            f(&mut writer)?;
-            Ok(writer.0)
-        })
+        write_file_entry(
+            &mut self.data,
+            &mut None,
+            name,
+            metadata,
+            option,
+            &Progress::none(),
+            |w| {
+                let mut writer = SolidEntryDataWriter(w);
+                f(&mut writer)?;
+                Ok(writer.0)
+            },
+        )
     }
 
     /// Adds extra chunk to the solid entry.
@@ -525,16 +596,24 @@ impl SolidEntryBuilder {
     }
 
     fn build_as_entry(self) -> io::Result<SolidEntry> {
-        Ok(SolidEntry {
-            header: self.header,
-            phsf: self.phsf,
-            data: {
-                let mut data = self.data.try_into_inner()?.try_into_inner()?.inner;
-                if let Some(iv) = self.iv {
-                    data.insert(0, iv);
+        let mut data = self.data.try_into_inner()?.try_into_inner()?.inner;
+        let (iv, phsf, key_derivation) = match self.seeded {
+            Some(option) => match seal_seeded(option, b"", &mut data)? {
+                None => (None, None, None),
+                Some(WriteCipher { context: c, .. }) => {
+                    (Some(c.iv), Some(c.phsf), c.key_derivation)
                 }
-                data
             },
+            None => (self.iv, self.phsf, self.key_derivation),
+        };
+        if let Some(iv) = iv {
+            data.insert(0, iv);
+        }
+        Ok(SolidEntry {
+            header: self.header,
+            phsf,
+            key_derivation,
+            data,
             extra: self.extra,
         })
     }
@@ -563,10 +642,28 @@ impl SolidEntryBuilder {
     }
 }
 
+/// Compresses and encrypts the `data` buffered in plain for a seeded `option` in place, and
+/// returns the cipher it is encrypted with.
+///
+/// The plain chunks are released as they are sealed, so the data is not held twice.
+fn seal_seeded(
+    option: WriteOptions,
+    label: &[u8],
+    data: &mut Vec<Vec<u8>>,
+) -> io::Result<Option<WriteCipher>> {
+    let context = get_seeded_writer_context(option, label, data.iter())?;
+    let mut writer = get_writer(FlattenWriter::<MAX_CHUNK_DATA_LENGTH>::new(), &context)?;
+    for chunk in std::mem::take(data) {
+        writer.write_all(&chunk)?;
+    }
+    *data = writer.try_into_inner()?.try_into_inner()?.inner;
+    Ok(context.cipher)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ChunkType, ReadOptions};
+    use crate::{Archive, ChunkType, CipherMode, DataKind, Encryption, HashAlgorithm, ReadOptions};
     #[cfg(all(target_family = "wasm", target_os = "unknown"))]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
@@ -618,4 +715,71 @@ mod tests {
 
         assert_eq!("テストデータ".as_bytes(), &buf[..]);
     }
+
+    #[test]
+    fn encrypted_entry_with_random_seed() {
+        fn build(seed: Option<[u8; 32]>) -> Vec<u8> {
+            let option = WriteOptions::builder()
+                .encryption(Encryption::Aes)
+                .cipher_mode(CipherMode::CTR)
+                .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+                .password(Some("password"))
+                .random_seed(seed)
+                .build();
+            let mut builder = EntryBuilder::new_file("entry".into(), option).unwrap();
+            builder.write_all(b"data").unwrap();
+            let mut archive = Archive::write_header(Vec::new()).unwrap();
+            archive.add_entry(builder.build().unwrap()).unwrap();
+            archive.finalize().unwrap()
+        }
+        assert_eq!(build(Some([1; 32])), build(Some([1; 32])));
+        assert_ne!(build(Some([1; 32])), build(Some([2; 32])));
+        assert_ne!(build(None), build(None));
+    }
+
+    #[test]
+    fn seeded_iv_depends_on_data() {
+        let option = WriteOptions::builder()
+            .encryption(Encryption::Aes)
+            .cipher_mode(CipherMode::CTR)
+            .hash_algorithm(HashAlgorithm::pbkdf2_sha256_with(Some(1)))
+            .password(Some("password"))
+            .random_seed(Some([1; 32]))
+            .build();
+        let entry = |data: &[u8]| {
+            let mut builder = EntryBuilder::new_file("entry".into(), &option).unwrap();
+            builder.write_all(data).unwrap();
+            builder.build().unwrap()
+        };
+        let solid = |data: &[u8]| {
+            let mut builder = SolidEntryBuilder::new(&option).unwrap();
+            builder.add_entry(entry(data)).unwrap();
+            builder.build_as_entry().unwrap()
+        };
+        assert_eq!(entry(b"a").data[0], entry(b"a").data[0]);
+        assert_ne!(entry(b"a").data[0], entry(b"b").data[0]);
+        assert_eq!(solid(b"a").data[0], solid(b"a").data[0]);
+        assert_ne!(solid(b"a").data[0], solid(b"b").data[0]);
+
+        let entry = entry(b"data");
+        let mut reader = entry
+            .reader(ReadOptions::with_password(Some("password")))
+            .unwrap();
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"data");
+    }
+
+    #[test]
+    fn whiteout_entry() {
+        let entry = EntryBuilder::new_whiteout("removed".into()).build().unwrap();
+        let mut archive = Archive::write_header(Vec::new()).unwrap();
+        archive.add_entry(entry).unwrap();
+        let buf = archive.finalize().unwrap();
+
+        let mut archive = Archive::read_header(&buf[..]).unwrap();
+        let entry = archive.entries_skip_solid().next().unwrap().unwrap();
+        assert_eq!(entry.header().data_kind(), DataKind::Whiteout);
+        assert_eq!(entry.header().path(), "removed");
+    }
 }
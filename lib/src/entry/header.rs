@@ -69,6 +69,11 @@ impl EntryHeader {
         Self::new(DataKind::HardLink, path)
     }
 
+    #[inline]
+    pub(crate) const fn for_whiteout(path: EntryName) -> Self {
+        Self::new(DataKind::Whiteout, path)
+    }
+
     /// Path of the entry.
     #[inline]
     pub fn path(&self) -> &EntryName {
@@ -1,9 +1,14 @@
-use crate::compress;
+use crate::{
+    compress,
+    hash::{ArchiveKey, KeyCache},
+    progress::{Progress, ProgressObserver},
+};
 pub(crate) use private::*;
 use std::{
     error::Error,
     fmt::{Display, Formatter},
     str::FromStr,
+    sync::Arc,
 };
 
 mod private {
@@ -25,6 +30,8 @@ mod private {
         pub(crate) hash_algorithm: HashAlgorithm,
         pub(crate) cipher_algorithm: CipherAlgorithm,
         pub(crate) mode: CipherMode,
+        pub(crate) archive_key: Option<ArchiveKey>,
+        pub(crate) random_seed: Option<[u8; 32]>,
     }
 
     impl Cipher {
@@ -35,12 +42,16 @@ mod private {
             hash_algorithm: HashAlgorithm,
             cipher_algorithm: CipherAlgorithm,
             mode: CipherMode,
+            archive_key: Option<ArchiveKey>,
+            random_seed: Option<[u8; 32]>,
         ) -> Self {
             Self {
                 password,
                 hash_algorithm,
                 cipher_algorithm,
                 mode,
+                archive_key,
+                random_seed,
             }
         }
     }
@@ -115,6 +126,19 @@ mod private {
     /// Entry read option getter trait.
     pub trait ReadOption {
         fn password(&self) -> Option<&str>;
+        /// Candidate passwords, tried in order against each entry.
+        #[inline]
+        fn passwords(&self) -> Vec<&str> {
+            self.password().into_iter().collect()
+        }
+        #[inline]
+        fn key_cache(&self) -> Option<&KeyCache> {
+            None
+        }
+        #[inline]
+        fn progress(&self) -> Option<&Progress> {
+            None
+        }
     }
 
     impl<T: ReadOption> ReadOption for &T {
@@ -122,12 +146,42 @@ mod private {
         fn password(&self) -> Option<&str> {
             T::password(self)
         }
+
+        #[inline]
+        fn passwords(&self) -> Vec<&str> {
+            T::passwords(self)
+        }
+
+        #[inline]
+        fn key_cache(&self) -> Option<&KeyCache> {
+            T::key_cache(self)
+        }
+
+        #[inline]
+        fn progress(&self) -> Option<&Progress> {
+            T::progress(self)
+        }
     }
 
     impl ReadOption for ReadOptions {
         #[inline]
         fn password(&self) -> Option<&str> {
-            self.password.as_deref()
+            self.passwords.first().map(String::as_str)
+        }
+
+        #[inline]
+        fn passwords(&self) -> Vec<&str> {
+            self.passwords.iter().map(String::as_str).collect()
+        }
+
+        #[inline]
+        fn key_cache(&self) -> Option<&KeyCache> {
+            Some(&self.key_cache)
+        }
+
+        #[inline]
+        fn progress(&self) -> Option<&Progress> {
+            Some(&self.progress)
         }
     }
 }
@@ -418,8 +472,12 @@ impl HashAlgorithm {
 }
 
 /// Type of entry.
+///
+/// The value is stored in the data kind field of the `FHED` chunk. New kinds may be added to
+/// the format, so readers must be prepared to see values they do not know.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum DataKind {
     /// Regular file
     File = 0,
@@ -429,6 +487,13 @@ pub enum DataKind {
     SymbolicLink = 2,
     /// Hard link
     HardLink = 3,
+    /// Deletion marker (whiteout).
+    /// Records that the path was removed since the archive this one is based on.
+    ///
+    /// Stored as data kind `4`. A whiteout entry has no `FDAT` chunks and its compression and
+    /// encryption fields are set to none. When the archive is applied on top of the one it is
+    /// based on, the path is removed.
+    Whiteout = 4,
 }
 
 impl TryFrom<u8> for DataKind {
@@ -441,6 +506,7 @@ impl TryFrom<u8> for DataKind {
             1 => Ok(Self::Directory),
             2 => Ok(Self::SymbolicLink),
             3 => Ok(Self::HardLink),
+            4 => Ok(Self::Whiteout),
             value => Err(UnknownValueError(value)),
         }
     }
@@ -454,6 +520,15 @@ pub struct WriteOptions {
 }
 
 impl WriteOptions {
+    /// Copies the options of any [WriteOption].
+    #[inline]
+    pub(crate) fn from_option(option: impl WriteOption) -> Self {
+        Self {
+            compress: option.compress(),
+            cipher: option.cipher().cloned(),
+        }
+    }
+
     /// A new [WriteOptions] to simply store.
     ///
     /// # Examples
@@ -519,6 +594,8 @@ pub struct WriteOptionsBuilder {
     cipher_mode: CipherMode,
     hash_algorithm: HashAlgorithm,
     password: Option<String>,
+    archive_key: bool,
+    random_seed: Option<[u8; 32]>,
 }
 
 impl Default for WriteOptionsBuilder {
@@ -544,6 +621,8 @@ impl From<WriteOptions> for WriteOptionsBuilder {
             cipher_mode: value.cipher_mode(),
             hash_algorithm: value.hash_algorithm(),
             password: value.password().map(Into::into),
+            archive_key: value.cipher().is_some_and(|it| it.archive_key.is_some()),
+            random_seed: value.cipher().and_then(|it| it.random_seed),
         }
     }
 }
@@ -557,6 +636,8 @@ impl WriteOptionsBuilder {
             cipher_mode: CipherMode::CTR,
             hash_algorithm: HashAlgorithm::argon2id(),
             password: None,
+            archive_key: false,
+            random_seed: None,
         }
     }
 
@@ -602,6 +683,37 @@ impl WriteOptionsBuilder {
         self
     }
 
+    /// Set whether to derive the encryption key once for the whole archive.
+    ///
+    /// When enabled, the password is stretched only once and shared by every entry
+    /// written with the built [WriteOptions], and each entry is encrypted with its own
+    /// key derived from it by HKDF. The parameters of the password stretching are stored in an
+    /// archive-level [`AKDF`] chunk, so that archives written this way can not be read by
+    /// versions that do not support it.
+    ///
+    /// [`AKDF`]: crate::ChunkType::AKDF
+    #[inline]
+    pub fn archive_key(&mut self, enabled: bool) -> &mut Self {
+        self.archive_key = enabled;
+        self
+    }
+
+    /// Set the seed to derive the salts and initialization vectors of encrypted entries from.
+    ///
+    /// By default they are random. With a seed, they are derived from the seed, the name of
+    /// the entry and the digest of its data, so that writing the same entries with the same
+    /// options and password produces the same bytes, which is needed for reproducible builds.
+    ///
+    /// As the digest is only known once all the data is written, the data of an encrypted
+    /// entry or solid entry is held in memory until it is finished. Entries with the same
+    /// name and the same data are still encrypted to the same bytes, which reveals that they
+    /// are equal.
+    #[inline]
+    pub fn random_seed(&mut self, seed: Option<[u8; 32]>) -> &mut Self {
+        self.random_seed = seed;
+        self
+    }
+
     /// Create new [WriteOptions] parameters set from this builder.
     ///
     /// ## Panics
@@ -622,6 +734,8 @@ impl WriteOptionsBuilder {
                     Encryption::No => unreachable!(),
                 },
                 self.cipher_mode,
+                self.archive_key.then(ArchiveKey::default),
+                self.random_seed,
             ))
         } else {
             None
@@ -639,9 +753,23 @@ impl WriteOptionsBuilder {
 }
 
 /// Options for reading an entry.
+///
+/// Keys derived from the password are cached in the [ReadOptions], and the cache is shared
+/// among its clones. A key is looked up by the password and the key derivation parameters,
+/// including the salt, so the cache only saves stretching the password again when an entry is
+/// read after its password was checked, and for entries encrypted with an archive-level key.
+/// Entries that have a salt of their own always derive their key again.
+///
+/// Passwords are tried in order for each entry and the first one that decrypts it is used.
+/// Since the archive does not store a hash of the password, a password is considered to match
+/// when its padding is valid and the beginning of the decrypted data can be decompressed. For
+/// an entry that is neither compressed nor padded, which any password decrypts without an
+/// error, the first password is used.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct ReadOptions {
-    password: Option<String>,
+    passwords: Vec<String>,
+    key_cache: KeyCache,
+    progress: Progress,
 }
 
 impl ReadOptions {
@@ -656,7 +784,30 @@ impl ReadOptions {
     #[inline]
     pub fn with_password<T: Into<String>>(password: Option<T>) -> Self {
         Self {
-            password: password.map(Into::into),
+            passwords: password.map(Into::into).into_iter().collect(),
+            key_cache: KeyCache::default(),
+            progress: Progress::none(),
+        }
+    }
+
+    /// Create a new [`ReadOptions`] with candidate passwords.
+    ///
+    /// # Examples
+    /// ```
+    /// use libpna::ReadOptions;
+    ///
+    /// let read_option = ReadOptions::with_passwords(["password", "other password"]);
+    /// ```
+    #[inline]
+    pub fn with_passwords<I, T>(passwords: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            passwords: passwords.into_iter().map(Into::into).collect(),
+            key_cache: KeyCache::default(),
+            progress: Progress::none(),
         }
     }
 
@@ -699,14 +850,16 @@ impl ReadOptions {
 /// Builder for [`ReadOptions`].
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 pub struct ReadOptionsBuilder {
-    password: Option<String>,
+    passwords: Vec<String>,
+    progress: Progress,
 }
 
 impl From<ReadOptions> for ReadOptionsBuilder {
     #[inline]
     fn from(value: ReadOptions) -> Self {
         Self {
-            password: value.password,
+            passwords: value.passwords,
+            progress: value.progress,
         }
     }
 }
@@ -714,14 +867,43 @@ impl From<ReadOptions> for ReadOptionsBuilder {
 impl ReadOptionsBuilder {
     #[inline]
     const fn new() -> Self {
-        Self { password: None }
+        Self {
+            passwords: Vec::new(),
+            progress: Progress::none(),
+        }
+    }
+
+    /// Set the password to this builder, replacing any candidate passwords.
+    #[inline]
+    pub fn password<S: AsRef<str>>(&mut self, password: Option<S>) -> &mut Self {
+        self.passwords = password.map(|it| it.as_ref().into()).into_iter().collect();
+        self
+    }
+
+    /// Add a candidate password to this builder.
+    ///
+    /// Candidate passwords are tried in the order they were added.
+    #[inline]
+    pub fn add_password<S: AsRef<str>>(&mut self, password: S) -> &mut Self {
+        self.passwords.push(password.as_ref().into());
+        self
+    }
+
+    /// Set the observer to report the progress of reading the data of entries to, or remove
+    /// it with `None`.
+    #[inline]
+    pub fn progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>) -> &mut Self {
+        self.progress = Progress::new(observer);
+        self
     }
 
     /// Create a new [`ReadOptions`]
     #[inline]
     pub fn build(&self) -> ReadOptions {
         ReadOptions {
-            password: self.password.clone(),
+            passwords: self.passwords.clone(),
+            key_cache: KeyCache::default(),
+            progress: self.progress.clone(),
         }
     }
 }
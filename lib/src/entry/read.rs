@@ -1,21 +1,45 @@
 use crate::{
     cipher::{Ctr128BEReader, DecryptCbcAes256Reader, DecryptCbcCamellia256Reader, DecryptReader},
     compress::DecompressReader,
-    hash::verify_password,
+    hash::{self, KeyCache},
     CipherMode, Compression, Encryption,
 };
 use aes::Aes256;
 use camellia::Camellia256;
 use crypto_common::BlockSizeUser;
+use password_hash::PasswordHash;
 use std::io::{self, Read};
 
+/// Key of an entry, derived either from the password directly or from the archive-level key.
+fn entry_key(
+    phsf: &str,
+    key_derivation: Option<&str>,
+    password: &[u8],
+    key_cache: &KeyCache,
+) -> io::Result<Vec<u8>> {
+    if !hash::is_derived_from_archive_key(phsf) {
+        return key_cache.get_or_derive(phsf, password);
+    }
+    let key_derivation = key_derivation
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "`AKDF` chunk not found"))?;
+    let archive_key = key_cache.get_or_derive(key_derivation, password)?;
+    hash::verify_key_check(key_derivation, &archive_key)?;
+    let salt = PasswordHash::new(phsf)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        .salt
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Salt not found in `PHSF`"))?;
+    hash::hkdf_sha256(&archive_key, salt.as_str().as_bytes(), archive_key.len())
+}
+
 /// Decrypt reader according to an encryption type.
 pub(crate) fn decrypt_reader<R: Read>(
     mut reader: R,
     encryption: Encryption,
     cipher_mode: CipherMode,
     phsf: Option<&str>,
+    key_derivation: Option<&str>,
     password: Option<&[u8]>,
+    key_cache: Option<&KeyCache>,
 ) -> io::Result<DecryptReader<R>> {
     Ok(match encryption {
         Encryption::No => DecryptReader::No(reader),
@@ -23,16 +47,16 @@ pub(crate) fn decrypt_reader<R: Read>(
             let s = phsf.ok_or_else(|| {
                 io::Error::new(io::ErrorKind::InvalidData, "`PHSF` chunk not found")
             })?;
-            let phsf = verify_password(
+            let password = password.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Password was not provided")
+            })?;
+            let key = entry_key(
                 s,
-                password.ok_or_else(|| {
-                    io::Error::new(io::ErrorKind::InvalidInput, "Password was not provided")
-                })?,
+                key_derivation,
+                password,
+                &key_cache.cloned().unwrap_or_default(),
             )?;
-            let hash = phsf
-                .hash
-                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Failed to get hash"))?;
-            let key = hash.as_bytes();
+            let key = key.as_slice();
             match (encryption, cipher_mode) {
                 (Encryption::Aes, CipherMode::CBC) => {
                     let mut iv = vec![0; Aes256::block_size()];
@@ -2,7 +2,9 @@ use crate::{
     cipher::{CipherWriter, Ctr128BEWriter, EncryptCbcAes256Writer, EncryptCbcCamellia256Writer},
     compress::CompressionWriter,
     entry::{CipherMode, Compress, HashAlgorithmParams, WriteOption},
-    hash, random, Cipher, CipherAlgorithm, HashAlgorithm,
+    hash,
+    random::Random,
+    Cipher, CipherAlgorithm, HashAlgorithm,
 };
 use aes::Aes256;
 use camellia::Camellia256;
@@ -10,11 +12,14 @@ use crypto_common::{BlockSizeUser, KeySizeUser};
 use flate2::write::ZlibEncoder;
 use liblzma::write::XzEncoder;
 use password_hash::{Output, SaltString};
+use sha2::{Digest, Sha256};
 use std::io::{self, Write};
 use zstd::stream::write::Encoder as ZstdEncoder;
 
 pub(crate) struct CipherContext {
     pub(crate) phsf: String,
+    /// PHSF of the archive-level key that [CipherContext::key] is derived from.
+    pub(crate) key_derivation: Option<String>,
     pub(crate) iv: Vec<u8>,
     pub(crate) key: Output,
     pub(crate) mode: CipherMode,
@@ -30,23 +35,54 @@ pub(crate) struct EntryWriterContext {
     pub(crate) cipher: Option<WriteCipher>,
 }
 
+/// Derives the key of an entry and generates its initialization vector. `label` tells the
+/// entries apart when the random values are derived from a seed.
 #[inline]
-fn to_hashed(cipher: &Cipher) -> io::Result<WriteCipher> {
-    let salt = random::salt_string();
-    let (key, phsf) = hash(
-        cipher.cipher_algorithm,
-        cipher.hash_algorithm,
-        cipher.password.as_bytes(),
-        &salt,
-    )?;
+fn to_hashed(cipher: &Cipher, label: &[u8]) -> io::Result<WriteCipher> {
+    let password = cipher.password.as_bytes();
+    let mut random = Random::new(cipher.random_seed.as_ref(), label);
+    let (key, phsf, key_derivation) = match &cipher.archive_key {
+        None => {
+            let salt = random.salt_string();
+            let (key, phsf) = hash(
+                cipher.cipher_algorithm,
+                cipher.hash_algorithm,
+                password,
+                &salt,
+            )?;
+            (key, phsf, None)
+        }
+        Some(archive_key) => {
+            let (archive_key, key_derivation) = archive_key.get_or_derive(|| {
+                // Derived by whichever entry comes first, so it must not depend on the label.
+                let salt = Random::new(cipher.random_seed.as_ref(), b"\0archive key").salt_string();
+                let (key, phsf) = hash(
+                    cipher.cipher_algorithm,
+                    cipher.hash_algorithm,
+                    password,
+                    &salt,
+                )?;
+                Ok((
+                    key.as_bytes().to_vec(),
+                    hash::with_key_check(&phsf, key.as_bytes())?,
+                ))
+            })?;
+            let salt = random.salt_string();
+            let key = hash::hkdf_sha256(&archive_key, salt.as_str().as_bytes(), archive_key.len())?;
+            let key =
+                Output::new(&key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            (key, hash::archive_key_phsf(&salt), Some(key_derivation))
+        }
+    };
     let iv = match cipher.cipher_algorithm {
-        CipherAlgorithm::Aes => random::random_vec(Aes256::block_size()),
-        CipherAlgorithm::Camellia => random::random_vec(Camellia256::block_size()),
+        CipherAlgorithm::Aes => random.vec(Aes256::block_size()),
+        CipherAlgorithm::Camellia => random.vec(Camellia256::block_size()),
     }?;
     Ok(WriteCipher {
         algorithm: cipher.cipher_algorithm,
         context: CipherContext {
             phsf,
+            key_derivation,
             iv,
             key,
             mode: cipher.mode,
@@ -54,9 +90,48 @@ fn to_hashed(cipher: &Cipher) -> io::Result<WriteCipher> {
     })
 }
 
+/// Returns whether the salts and initialization vectors of `option` are derived from a seed.
+///
+/// The data of such an entry is buffered in plain with [plain_writer_context] until it is
+/// finished, and then written with [get_seeded_writer_context].
+#[inline]
+pub(crate) fn is_seeded(option: &impl WriteOption) -> bool {
+    option.cipher().is_some_and(|it| it.random_seed.is_some())
+}
+
+#[inline]
+pub(crate) const fn plain_writer_context() -> EntryWriterContext {
+    EntryWriterContext {
+        compress: Compress::No,
+        cipher: None,
+    }
+}
+
+/// Like [get_writer_context], but also derives the key and the initialization vector from
+/// the digest of `data`, as in SIV, so that different data under the same label never share
+/// them. The `data` may be given in chunks as it is buffered.
+#[inline]
+pub(crate) fn get_seeded_writer_context<D: AsRef<[u8]>>(
+    option: impl WriteOption,
+    label: &[u8],
+    data: impl IntoIterator<Item = D>,
+) -> io::Result<EntryWriterContext> {
+    let digest = data
+        .into_iter()
+        .fold(Sha256::new(), |hasher, it| hasher.chain_update(it))
+        .finalize();
+    get_writer_context(option, &[label, digest.as_slice()].concat())
+}
+
 #[inline]
-pub(crate) fn get_writer_context(option: impl WriteOption) -> io::Result<EntryWriterContext> {
-    let cipher = option.cipher().map(to_hashed).transpose()?;
+pub(crate) fn get_writer_context(
+    option: impl WriteOption,
+    label: &[u8],
+) -> io::Result<EntryWriterContext> {
+    let cipher = option
+        .cipher()
+        .map(|cipher| to_hashed(cipher, label))
+        .transpose()?;
     Ok(EntryWriterContext {
         compress: option.compress(),
         cipher,
@@ -1,6 +1,22 @@
 use argon2::{Argon2, ParamsBuilder, Version};
-use password_hash::{PasswordHash, PasswordHasher, SaltString};
-use std::io;
+use hkdf::Hkdf;
+use password_hash::{Output, PasswordHash, PasswordHasher, SaltString};
+use sha2::Sha256;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Identifier of the per-entry key derived from an archive-level key.
+pub(crate) const HKDF_SHA256_IDENT: &str = "hkdf-sha256";
+
+const HKDF_ENTRY_KEY_INFO: &[u8] = b"libpna entry key";
+
+const HKDF_KEY_CHECK_INFO: &[u8] = b"libpna key check";
 
 pub(crate) fn argon2_with_salt<'a>(
     password: &'a [u8],
@@ -80,6 +96,176 @@ pub(crate) fn verify_password<'a>(
     }
 }
 
+/// Returns `true` if the given PHSF refers to a key derived from an archive-level key.
+#[inline]
+pub(crate) fn is_derived_from_archive_key(phsf: &str) -> bool {
+    PasswordHash::new(phsf).is_ok_and(|it| it.algorithm.as_str() == HKDF_SHA256_IDENT)
+}
+
+/// Builds the PHSF of an entry key derived from an archive-level key with the given salt.
+#[inline]
+pub(crate) fn archive_key_phsf(salt: &SaltString) -> String {
+    format!("${HKDF_SHA256_IDENT}${}", salt.as_str())
+}
+
+pub(crate) fn hkdf_sha256(master_key: &[u8], salt: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let mut key = vec![0; len];
+    Hkdf::<Sha256>::new(Some(salt), master_key)
+        .expand(HKDF_ENTRY_KEY_INFO, &mut key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    Ok(key)
+}
+
+/// Value stored in the hash field of the PHSF of an archive-level key to tell whether a key
+/// derived from a password is the right one, without revealing the key itself.
+fn key_check(key: &[u8], salt: &[u8]) -> io::Result<Output> {
+    let mut check = vec![0; key.len()];
+    Hkdf::<Sha256>::new(Some(salt), key)
+        .expand(HKDF_KEY_CHECK_INFO, &mut check)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    Output::new(&check).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Returns the PHSF of the archive-level `key` with a key check in its hash field.
+pub(crate) fn with_key_check(phsf: &str, key: &[u8]) -> io::Result<String> {
+    let mut password_hash =
+        PasswordHash::new(phsf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let salt = password_hash
+        .salt
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Salt not found in PHSF"))?;
+    password_hash.hash = Some(key_check(key, salt.as_str().as_bytes())?);
+    Ok(password_hash.to_string())
+}
+
+/// Returns `true` if the PHSF carries a key check.
+#[inline]
+pub(crate) fn has_key_check(phsf: &str) -> bool {
+    PasswordHash::new(phsf).is_ok_and(|it| it.hash.is_some())
+}
+
+/// Fails with [io::ErrorKind::InvalidInput] if the PHSF carries a key check that `key` does not
+/// satisfy, that is, if `key` was derived from a wrong password.
+pub(crate) fn verify_key_check(phsf: &str, key: &[u8]) -> io::Result<()> {
+    let password_hash =
+        PasswordHash::new(phsf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    match (password_hash.hash, password_hash.salt) {
+        (Some(expected), Some(salt)) if expected != key_check(key, salt.as_str().as_bytes())? => {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Wrong password",
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Shared state that does not take part in comparison and hashing.
+#[derive(Default)]
+pub(crate) struct Shared<T>(Arc<Mutex<T>>);
+
+impl<T> Shared<T> {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Debug for Shared<T> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared").finish_non_exhaustive()
+    }
+}
+
+impl<T> PartialEq for Shared<T> {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Shared<T> {}
+
+impl<T> PartialOrd for Shared<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Shared<T> {
+    #[inline]
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl<T> Hash for Shared<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+type KeySlot = Shared<Option<Vec<u8>>>;
+
+/// In-process cache of keys derived from passwords, keyed by PHSF and password.
+///
+/// Since the PHSF includes the salt, a key is only found again for the same entry, or for
+/// entries that share an archive-level key.
+///
+/// Cloned caches share their contents.
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct KeyCache(Shared<HashMap<(String, Vec<u8>), KeySlot>>);
+
+impl KeyCache {
+    /// Returns the key for the given PHSF and password, deriving it at most once.
+    /// Different keys are derived concurrently.
+    pub(crate) fn get_or_derive(&self, phsf: &str, password: &[u8]) -> io::Result<Vec<u8>> {
+        let slot = self
+            .0
+            .lock()
+            .entry((phsf.into(), password.into()))
+            .or_default()
+            .clone();
+        let mut slot = slot.lock();
+        if let Some(key) = &*slot {
+            return Ok(key.clone());
+        }
+        let hash = verify_password(phsf, password)?
+            .hash
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Failed to get hash"))?;
+        let key = hash.as_bytes().to_vec();
+        *slot = Some(key.clone());
+        Ok(key)
+    }
+}
+
+/// Archive-level key, derived from the password on first use and shared afterwards.
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub(crate) struct ArchiveKey(Shared<Option<(Vec<u8>, String)>>);
+
+impl ArchiveKey {
+    /// Returns the key and its PHSF, deriving them with `f` only on the first call.
+    pub(crate) fn get_or_derive<F>(&self, f: F) -> io::Result<(Vec<u8>, String)>
+    where
+        F: FnOnce() -> io::Result<(Vec<u8>, String)>,
+    {
+        let mut key = self.0.lock();
+        if let Some(key) = &*key {
+            return Ok(key.clone());
+        }
+        let derived = f()?;
+        *key = Some(derived.clone());
+        Ok(derived)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +309,68 @@ mod tests {
         let ph = verify_password(&ps, b"pass").unwrap();
         assert!(ph.hash.is_some());
     }
+
+    #[test]
+    fn key_cache_derives_once() {
+        let salt = random::salt_string();
+        let mut ph = pbkdf2_with_salt(
+            b"pass",
+            pbkdf2::Algorithm::Pbkdf2Sha256,
+            pbkdf2::Params::default(),
+            &salt,
+        )
+        .unwrap();
+        let expected = ph.hash.take().unwrap();
+        let ps = ph.to_string();
+        let cache = KeyCache::default();
+        assert_eq!(
+            cache.get_or_derive(&ps, b"pass").unwrap(),
+            expected.as_bytes()
+        );
+        assert_eq!(cache.0.lock().len(), 1);
+        assert_eq!(
+            cache.clone().get_or_derive(&ps, b"pass").unwrap(),
+            expected.as_bytes()
+        );
+        assert_eq!(cache.0.lock().len(), 1);
+    }
+
+    #[test]
+    fn archive_key_phsf_roundtrip() {
+        let salt = random::salt_string();
+        let phsf = archive_key_phsf(&salt);
+        assert!(is_derived_from_archive_key(&phsf));
+        assert!(!is_derived_from_archive_key(
+            "$pbkdf2-sha256$i=1,l=32$c2FsdHNhbHQ"
+        ));
+    }
+
+    #[test]
+    fn key_check_detects_wrong_key() {
+        let salt = random::salt_string();
+        let mut ph = pbkdf2_with_salt(
+            b"pass",
+            pbkdf2::Algorithm::Pbkdf2Sha256,
+            pbkdf2::Params {
+                rounds: 1,
+                output_length: 32,
+            },
+            &salt,
+        )
+        .unwrap();
+        let key = ph.hash.take().unwrap();
+        let phsf = with_key_check(&ph.to_string(), key.as_bytes()).unwrap();
+        assert!(has_key_check(&phsf));
+        assert!(!has_key_check(&ph.to_string()));
+
+        let cache = KeyCache::default();
+        let derived = cache.get_or_derive(&phsf, b"pass").unwrap();
+        assert_eq!(derived, key.as_bytes());
+        verify_key_check(&phsf, &derived).unwrap();
+        let wrong = cache.get_or_derive(&phsf, b"wrong").unwrap();
+        assert_eq!(
+            verify_key_check(&phsf, &wrong).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
 }
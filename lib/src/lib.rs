@@ -21,12 +21,14 @@ pub(crate) mod entry;
 pub(crate) mod hash;
 pub(crate) mod io;
 pub mod prelude;
+pub(crate) mod progress;
 pub(crate) mod random;
 pub(crate) mod util;
 
 pub use archive::*;
 pub use chunk::*;
 pub use entry::*;
+pub use progress::ProgressObserver;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,253 @@
+use crate::EntryName;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// Observer of the progress of reading and writing entries.
+///
+/// An observer is registered with [`Archive::set_progress_observer`] for writing, and with
+/// [`ReadOptionsBuilder::progress_observer`] for reading the data of entries. Its methods are
+/// called from the thread that reads or writes, and do nothing by default.
+///
+/// # Examples
+/// ```
+/// use libpna::{Archive, Metadata, ProgressObserver, WriteOptions};
+/// use std::{
+///     io::{self, prelude::*},
+///     sync::{
+///         atomic::{AtomicU64, Ordering},
+///         Arc,
+///     },
+/// };
+///
+/// #[derive(Default)]
+/// struct Counter(AtomicU64);
+///
+/// impl ProgressObserver for Counter {
+///     fn bytes_written(&self, len: u64) {
+///         self.0.fetch_add(len, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// let counter = Arc::new(Counter::default());
+/// let mut archive = Archive::write_header(Vec::new())?;
+/// archive.set_progress_observer(Some(counter.clone()));
+/// archive.write_file(
+///     "foo.txt".into(),
+///     Metadata::new(),
+///     WriteOptions::store(),
+///     |writer| writer.write_all(b"text"),
+/// )?;
+/// archive.finalize()?;
+/// assert_eq!(counter.0.load(Ordering::Relaxed), 4);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Archive::set_progress_observer`]: crate::Archive::set_progress_observer
+/// [`ReadOptionsBuilder::progress_observer`]: crate::ReadOptionsBuilder::progress_observer
+pub trait ProgressObserver: Send + Sync {
+    /// Called when reading or writing the entry `name` starts.
+    #[inline]
+    fn entry_started(&self, name: &EntryName) {
+        let _ = name;
+    }
+
+    /// Called when reading or writing the entry `name` is finished.
+    #[inline]
+    fn entry_finished(&self, name: &EntryName) {
+        let _ = name;
+    }
+
+    /// Called when `len` bytes are read from the data of an entry.
+    #[inline]
+    fn bytes_read(&self, len: u64) {
+        let _ = len;
+    }
+
+    /// Called when `len` bytes are written: the data given to `write_file`, or the encoded
+    /// size of an entry given to `add_entry` or `add_entry_part`.
+    #[inline]
+    fn bytes_written(&self, len: u64) {
+        let _ = len;
+    }
+}
+
+/// Optional [ProgressObserver] that does not take part in comparison and hashing.
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<dyn ProgressObserver>>);
+
+impl Progress {
+    #[inline]
+    pub(crate) const fn none() -> Self {
+        Self(None)
+    }
+
+    #[inline]
+    pub(crate) fn new(observer: Option<Arc<dyn ProgressObserver>>) -> Self {
+        Self(observer)
+    }
+
+    #[inline]
+    pub(crate) fn entry_started(&self, name: &EntryName) {
+        if let Some(observer) = &self.0 {
+            observer.entry_started(name);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn entry_finished(&self, name: &EntryName) {
+        if let Some(observer) = &self.0 {
+            observer.entry_finished(name);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bytes_read(&self, len: usize) {
+        if let Some(observer) = &self.0 {
+            observer.bytes_read(len as u64);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bytes_written(&self, len: usize) {
+        if let Some(observer) = &self.0 {
+            observer.bytes_written(len as u64);
+        }
+    }
+}
+
+impl Debug for Progress {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Progress {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Progress {}
+
+impl PartialOrd for Progress {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Progress {
+    #[inline]
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Progress {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Archive, EntryBuilder, Metadata, ReadOptions, WriteOptions};
+    use std::{
+        io::{Read, Write},
+        sync::Mutex,
+    };
+    #[cfg(all(target_family = "wasm", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ProgressObserver for Recorder {
+        fn entry_started(&self, name: &EntryName) {
+            self.0.lock().unwrap().push(format!("start {name}"));
+        }
+
+        fn entry_finished(&self, name: &EntryName) {
+            self.0.lock().unwrap().push(format!("finish {name}"));
+        }
+
+        fn bytes_read(&self, len: u64) {
+            if len != 0 {
+                self.0.lock().unwrap().push(format!("read {len}"));
+            }
+        }
+
+        fn bytes_written(&self, len: u64) {
+            self.0.lock().unwrap().push(format!("write {len}"));
+        }
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn report_progress() {
+        let recorder = Arc::new(Recorder::default());
+        let mut archive = Archive::write_header(Vec::new()).unwrap();
+        archive.set_progress_observer(Some(recorder.clone()));
+        archive
+            .write_file(
+                "a.txt".into(),
+                Metadata::new(),
+                WriteOptions::store(),
+                |w| w.write_all(b"text"),
+            )
+            .unwrap();
+        let mut builder = EntryBuilder::new_file("b.txt".into(), WriteOptions::store()).unwrap();
+        builder.write_all(b"data").unwrap();
+        let len = archive.add_entry(builder.build().unwrap()).unwrap();
+        let buf = archive.finalize().unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                "start a.txt".into(),
+                "write 4".into(),
+                "finish a.txt".into(),
+                "start b.txt".into(),
+                format!("write {len}"),
+                "finish b.txt".into(),
+            ]
+        );
+
+        let option = ReadOptions::builder()
+            .progress_observer(Some(recorder.clone()))
+            .build();
+        let mut archive = Archive::read_header(&buf[..]).unwrap();
+        for entry in archive.entries_skip_solid() {
+            let entry = entry.unwrap();
+            let mut data = Vec::new();
+            entry
+                .reader(&option)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+        }
+        assert_eq!(
+            recorder.take(),
+            [
+                "start a.txt",
+                "read 4",
+                "finish a.txt",
+                "start b.txt",
+                "read 4",
+                "finish b.txt",
+            ]
+        );
+    }
+}
@@ -1,19 +1,60 @@
 use password_hash::SaltString;
 use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
 use std::io;
 
-pub(crate) fn random_bytes(dist: &mut [u8]) -> io::Result<()> {
-    let mut rand = ChaCha20Rng::from_entropy();
-    rand.try_fill_bytes(dist).map_err(io::Error::other)
-}
+/// Generator of salts and initialization vectors.
+///
+/// It is seeded from the system entropy, or derived from a seed and a label so that the
+/// same seed and label always generate the same values.
+pub(crate) struct Random(ChaCha20Rng);
+
+impl Random {
+    #[inline]
+    pub(crate) fn new(seed: Option<&[u8; 32]>, label: &[u8]) -> Self {
+        Self(match seed {
+            None => ChaCha20Rng::from_entropy(),
+            Some(seed) => ChaCha20Rng::from_seed(
+                Sha256::new()
+                    .chain_update(seed)
+                    .chain_update(label)
+                    .finalize()
+                    .into(),
+            ),
+        })
+    }
+
+    #[inline]
+    pub(crate) fn vec(&mut self, size: usize) -> io::Result<Vec<u8>> {
+        let mut v = vec![0; size];
+        self.0.try_fill_bytes(&mut v).map_err(io::Error::other)?;
+        Ok(v)
+    }
 
-pub(crate) fn random_vec(size: usize) -> io::Result<Vec<u8>> {
-    let mut v = vec![0; size];
-    random_bytes(&mut v)?;
-    Ok(v)
+    #[inline]
+    pub(crate) fn salt_string(&mut self) -> SaltString {
+        SaltString::generate(&mut self.0)
+    }
 }
 
+#[cfg(test)]
 pub(crate) fn salt_string() -> SaltString {
-    SaltString::generate(ChaCha20Rng::from_entropy())
+    Random::new(None, &[]).salt_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(target_family = "wasm", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn seeded_is_deterministic() {
+        let seed = [7; 32];
+        let a = Random::new(Some(&seed), b"a").vec(16).unwrap();
+        assert_eq!(a, Random::new(Some(&seed), b"a").vec(16).unwrap());
+        assert_ne!(a, Random::new(Some(&seed), b"b").vec(16).unwrap());
+        assert_ne!(a, Random::new(None, b"a").vec(16).unwrap());
+    }
 }